@@ -0,0 +1 @@
+ALTER TABLE file_analysis_reports ADD COLUMN canary_plan TEXT DEFAULT '' NOT NULL;
@@ -0,0 +1,8 @@
+-- full text index of the console output produced by the analysed samples
+CREATE VIRTUAL TABLE console_logs USING fts5(
+    text,
+    file_hash UNINDEXED,
+    analysis_id UNINDEXED,
+    executed_on UNINDEXED,
+    timestamp UNINDEXED
+);
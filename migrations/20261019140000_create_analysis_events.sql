@@ -0,0 +1,11 @@
+-- events reported by the sandbox, one row per batch received on the iocs queue
+CREATE TABLE analysis_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    analysis_id TEXT NOT NULL,
+    file_hash TEXT NOT NULL,
+    received_at INTEGER NOT NULL,
+    iocs BLOB NOT NULL
+);
+
+CREATE INDEX analysis_events_analysis_id ON analysis_events (analysis_id);
+CREATE INDEX analysis_events_file_hash ON analysis_events (file_hash);
@@ -0,0 +1,2 @@
+-- events are stored zstd compressed, rows written before this migration are plain json
+ALTER TABLE analysis_events ADD COLUMN compression TEXT DEFAULT 'none' NOT NULL;
@@ -0,0 +1,2 @@
+-- weighted risk score, verdict and explanation of the report, as json
+ALTER TABLE file_analysis_reports ADD COLUMN assessment TEXT DEFAULT '' NOT NULL;
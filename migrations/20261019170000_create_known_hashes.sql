@@ -0,0 +1,8 @@
+-- hashes of known malware imported from threat intelligence exports
+CREATE TABLE known_hashes (
+    hash TEXT PRIMARY KEY NOT NULL,
+    hash_type TEXT NOT NULL,
+    family TEXT NOT NULL,
+    source TEXT NOT NULL,
+    first_seen TEXT DEFAULT '' NOT NULL
+);
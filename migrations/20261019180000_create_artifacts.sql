@@ -0,0 +1,24 @@
+-- samples and downloaded payloads kept by content hash, the files are under
+-- the artifacts dir. ref_count is the number of report links, unreferenced
+-- artifacts are deleted
+CREATE TABLE artifacts (
+    sha256 TEXT PRIMARY KEY NOT NULL,
+    size INTEGER NOT NULL,
+    stored_size INTEGER NOT NULL,
+    compression TEXT NOT NULL,
+    encryption TEXT NOT NULL,
+    ref_count INTEGER DEFAULT 0 NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+-- which reports an artifact belongs to and how: the analysed sample or a
+-- file downloaded during the analysis
+CREATE TABLE file_report_artifacts (
+    file_report_uid TEXT NOT NULL,
+    artifact_sha256 TEXT NOT NULL,
+    relation TEXT NOT NULL,
+    name TEXT DEFAULT '' NOT NULL,
+    PRIMARY KEY (file_report_uid, artifact_sha256, relation)
+);
+
+CREATE INDEX idx_file_report_artifacts_artifact_sha256 ON file_report_artifacts (artifact_sha256);
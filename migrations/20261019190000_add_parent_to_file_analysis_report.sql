@@ -0,0 +1,6 @@
+-- second stage payloads are analysed in child reports of the report that fetched them
+ALTER TABLE file_analysis_reports ADD COLUMN parent_uid TEXT;
+ALTER TABLE file_analysis_reports ADD COLUMN source_url TEXT DEFAULT '' NOT NULL;
+ALTER TABLE file_analysis_reports ADD COLUMN depth INTEGER DEFAULT 0 NOT NULL;
+
+CREATE INDEX idx_file_analysis_reports_parent_uid ON file_analysis_reports (parent_uid);
@@ -0,0 +1,10 @@
+-- control runs of the bait websites without a sample, their events are stored
+-- in analysis_events under the same analysis_id
+CREATE TABLE baseline_runs (
+    analysis_id TEXT PRIMARY KEY NOT NULL,
+    bait_website TEXT NOT NULL,
+    requested_at INTEGER NOT NULL,
+    completed_at INTEGER
+);
+
+CREATE INDEX idx_baseline_runs_bait_website ON baseline_runs (bait_website, requested_at);
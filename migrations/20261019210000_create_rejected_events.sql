@@ -0,0 +1,15 @@
+-- events of the sandbox batches that did not validate against the event schema
+CREATE TABLE rejected_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    analysis_id TEXT NOT NULL,
+    file_hash TEXT NOT NULL,
+    received_at INTEGER NOT NULL,
+    schema_version INTEGER NOT NULL,
+    event_index INTEGER NOT NULL,
+    ioc_type TEXT NOT NULL,
+    error TEXT NOT NULL,
+    event TEXT NOT NULL
+);
+
+CREATE INDEX idx_rejected_events_analysis_id ON rejected_events (analysis_id);
+CREATE INDEX idx_rejected_events_file_hash ON rejected_events (file_hash);
@@ -10,6 +10,7 @@ from datetime import datetime
 import yaml
 import logging
 import sys
+import json
 
 logger = logging.getLogger(__name__)
 
@@ -28,6 +29,10 @@ class FileForAnalysis:
     analysis_id: str
     bait_websites: list[str]
     file_bytes: bytes
+    canary_plan: dict
+
+# field order of the CanaryPlan struct as serialized by the core
+CANARY_PLAN_FIELDS = ["text", "card", "password", "email", "cookie_value", "local_storage_value"]
         
 def run(samples_dir="", sandbox_lib="", config_folder="", bait_website=""):
     logger.info("running consumer ...")
@@ -47,7 +52,8 @@ def run(samples_dir="", sandbox_lib="", config_folder="", bait_website=""):
                     file_hash=file_for_analysis_raw[1],
                     analysis_id=file_for_analysis_raw[2],
                     bait_websites=file_for_analysis_raw[3],
-                    file_bytes=file_for_analysis_raw[4]
+                    file_bytes=file_for_analysis_raw[4],
+                    canary_plan=dict(zip(CANARY_PLAN_FIELDS, file_for_analysis_raw[5])) if len(file_for_analysis_raw) > 5 else {}
                 )
             except msgpack.FormatError as e:
                 logger.warning("data received from queue is not valid msgpack bytes")
@@ -65,22 +71,34 @@ def run(samples_dir="", sandbox_lib="", config_folder="", bait_website=""):
 
                 # looping with index
                 for idx, bw in enumerate(file_for_analysis.bait_websites):
-
-                    # adding the index of the loop as suffix to create different files for every analysis
-                    # this does not mess with the javascript analyzer state and makes the analyses independent
-                    samples_file_path = samples_dir + "/" + file_for_analysis.file_hash + "_" + str(dt.microsecond) + "_" + str(idx)
-                    with open(samples_file_path, "w") as f:
-                        byte_string = ""
-                        for b in file_for_analysis.file_bytes:
-                            byte_string = byte_string + chr(b)
-                        f.write(byte_string)
-                    analysis_command = [
-                            "node", sandbox_lib,
-                            "--sample-file", samples_file_path,
-                            "--bait-website", bw,
-                            "--conf-folder", config_folder,
-                            "--analysis-id", file_for_analysis.analysis_id
-                        ]
+                    # control runs of a bait website come without a sample, the
+                    # sandbox only visits the page and reports what it does on its own
+                    if not file_for_analysis.file_bytes:
+                        analysis_command = [
+                                "node", sandbox_lib,
+                                "--just-check-page",
+                                "--bait-website", bw,
+                                "--conf-folder", config_folder,
+                                "--analysis-id", file_for_analysis.analysis_id
+                            ]
+                    else:
+                        # adding the index of the loop as suffix to create different files for every analysis
+                        # this does not mess with the javascript analyzer state and makes the analyses independent
+                        samples_file_path = samples_dir + "/" + file_for_analysis.file_hash + "_" + str(dt.microsecond) + "_" + str(idx)
+                        with open(samples_file_path, "w") as f:
+                            byte_string = ""
+                            for b in file_for_analysis.file_bytes:
+                                byte_string = byte_string + chr(b)
+                            f.write(byte_string)
+                        analysis_command = [
+                                "node", sandbox_lib,
+                                "--sample-file", samples_file_path,
+                                "--bait-website", bw,
+                                "--conf-folder", config_folder,
+                                "--analysis-id", file_for_analysis.analysis_id
+                            ]
+                    if file_for_analysis.canary_plan:
+                        analysis_command += ["--canary-plan", json.dumps(file_for_analysis.canary_plan)]
                     logger.info("running analysis command: ", analysis_command)
                     subprocess.Popen(analysis_command, stdin=None, stdout=None, stderr=None)
         except Exception as e:
@@ -8,11 +8,13 @@ import rsg from "random-string-generator";
 import yaml from 'yaml';
 import { sha256 } from 'js-sha256';
 import * as log from "log4js";
-import { validate_logging_level } from "./utils";
-import { default_rabbitmq_conf, malicious_mime_types } from "./const"
+import { is_same_site, validate_logging_level } from "./utils";
+import { default_rabbitmq_conf, ioc_schema_version, malicious_mime_types, textual_mime_types } from "./const"
 import commandLineArgs from 'command-line-args'
 
 const MAX_SET_TIMEOUT_DELAY_TO_WAIT = 5000;
+const MAX_RESPONSE_DATA_LENGTH = 128 * 1024;
+const MAX_CONSOLE_TEXT_LENGTH = 64 * 1024;
 const log_level = process.env.LOG_LEVEL;
 let logger = log.getLogger();
 logger.level = validate_logging_level(log_level ? log_level : 'info')
@@ -23,7 +25,8 @@ const cli_arg_options = [
     { name: 'sample-file', alias: 'f', type: String, defaultValue: "" },
     { name: 'bait-website', alias: 'w', type: String, defaultValue: "https://google.com" },
     { name: 'conf-folder', alias: 'c', type: String, defaultValue: "/sandbox/config" },
-    { name: 'analysis-id', alias: 'a', type: String, defaultValue: ""}
+    { name: 'analysis-id', alias: 'a', type: String, defaultValue: ""},
+    { name: 'canary-plan', alias: 'k', type: String, defaultValue: ""}
 ]
 
 const cli_args = commandLineArgs(cli_arg_options)
@@ -35,6 +38,14 @@ var justCheckPage = cli_args["just-check-page"]
 var baitWebsite = cli_args["bait-website"]
 var configFolder = cli_args["conf-folder"]
 var analysisId = cli_args["analysis-id"]
+var canaryPlan: types.CanaryPlan | null = null
+if (cli_args["canary-plan"]) {
+    try {
+        canaryPlan = JSON.parse(cli_args["canary-plan"]) as types.CanaryPlan
+    } catch (e) {
+        logger.warn("could not parse canary plan, using default inputs: ", e)
+    }
+}
 
 if (!sampleFile && !justCheckPage) {
     logger.error("Error: No JavaScript file provided!");
@@ -85,6 +96,28 @@ if (!fs.existsSync(sampleFile) && !justCheckPage) {
 
     page.on('response', async response => {
         const headers = response.headers();
+        const content_type = (headers["content-type"] || "").split(";")[0].trim();
+        let response_data = "";
+        // the bait website's own pages and scripts are not second stages
+        if (textual_mime_types.includes(content_type) && !is_same_site(response.url(), baitWebsite)) {
+            try {
+                response_data = (await response.text()).slice(0, MAX_RESPONSE_DATA_LENGTH);
+            } catch(err) {
+                logger.debug('[analysis-debug] could not read response body: ', response.url());
+            }
+        }
+        iocs.push({
+            type: types.IoCType.HttpResposne,
+            timestamp: Date.now(),
+            executed_on: baitWebsite,
+            value: {
+                status: String(response.status()),
+                url: response.url(),
+                data: response_data,
+                content_type: content_type
+            } as types.IoCHttpResponse
+        });
+
         if (malicious_mime_types.includes(headers["content-type"])) {
             logger.debug('[analysis-debug] suspicious file download');
             let file_extension = "";
@@ -116,10 +149,35 @@ if (!fs.existsSync(sampleFile) && !justCheckPage) {
     await page.goto(baitWebsite, {
         waitUntil: ['domcontentloaded', 'networkidle0']
     });
+
+    // plant the canary cookie and localStorage value before hooking, so that
+    // planting them is not reported as activity of the sample. The analyzer
+    // knows their names (src/analysis/storage_access.rs)
+    if (canaryPlan) {
+        await page.evaluate((plan: types.CanaryPlan) => {
+            document.cookie = `session_token=${plan.cookie_value}; path=/`
+            try {
+                window.localStorage.setItem("auth_token", plan.local_storage_value)
+            } catch (e) {}
+        }, canaryPlan)
+    }
     logger.info("[analysis-info] Hooking JavaScript APIs...");
 
     page.on('console', message => {
-        logger.debug(`[dom-console]: ${message.text()}`);
+        const text = message.text();
+        logger.debug(`[dom-console]: ${text}`);
+        // errors of the hooks themselves are not activity of the sample
+        if (text.startsWith("[analysis-") || text.startsWith("Oops! Uncaught error in hooks")) {
+            return
+        }
+        iocs.push({
+            type: types.IoCType.ConsoleLog,
+            timestamp: Date.now(),
+            executed_on: baitWebsite,
+            value: {
+                text: text.slice(0, MAX_CONSOLE_TEXT_LENGTH)
+            } as types.IoCConsoleLog
+        });
     })
 
     let max_delay = 0;
@@ -175,7 +233,7 @@ if (!fs.existsSync(sampleFile) && !justCheckPage) {
         }
         
         // run the Lure          
-        const lure = new Lure(page)
+        const lure = new Lure(page, canaryPlan)
         logger.debug(`[analysis-debug] Starting Lure`);
         await lure.start_lure()
 
@@ -188,16 +246,20 @@ if (!fs.existsSync(sampleFile) && !justCheckPage) {
 
         if (!cli_args.dev) {
             let iocs_for_analysis: types.IoCsFromAnalysis = {
+                schema_version: ioc_schema_version,
                 file_hash: fileHash,
                 analysis_id: analysisId,
                 iocs: iocs
             }
             logger.info(`[analysis-info] found ${iocs_for_analysis.iocs.length} iocs`)
             await rbmqc.publish(rabbitmq_config.queues.sandbox_iocs_queue.name, iocs_for_analysis)
-            try {
-                fs.rmSync(sampleFile)
-            } catch(e) {
-                logger.error("Could not remove sample file")
+            // control runs of the bait website have no sample file
+            if (!justCheckPage) {
+                try {
+                    fs.rmSync(sampleFile)
+                } catch(e) {
+                    logger.error("Could not remove sample file")
+                }
             }
             await rbmqc.close();
         }
@@ -54,6 +54,16 @@ const malicious_mime_types = [
     "application/json",
 ];
 
+// response bodies of these types are kept in the http_response iocs, they
+// are the scripts and pages analysed as second stages
+const textual_mime_types = [
+    "text/javascript",
+    "application/javascript",
+    "application/x-javascript",
+    "application/ecmascript",
+    "text/html",
+];
+
 const default_rabbitmq_conf: types.RabbitMQConfig = {
     connection: {
             host: "",
@@ -80,8 +90,14 @@ const default_rabbitmq_conf: types.RabbitMQConfig = {
         }
 }
 
+// version of the event batches published to the core, the core validates
+// every event against the schema of the version and rejects the others
+const ioc_schema_version = 2;
+
 export {
+    ioc_schema_version,
     default_rabbitmq_conf,
     malicious_mime_types,
+    textual_mime_types,
     known_network_dom_elements
 }
\ No newline at end of file
@@ -35,6 +35,38 @@ function place_hooks(reportFnName: string) {
             return originalGetItem.apply(this, [key] as [key: string]);
         };
 
+        const originalSessionSetItem = window.sessionStorage.setItem;
+        window.sessionStorage.setItem = function (key: string, value: string) {
+            let _event: types.IoC = {
+                type: types.IoCType.FunctionCall,
+                timestamp: Date.now(),
+                executed_on: "",
+                value: {
+                    callee: "window.sessionStorage.setItem",
+                    arguments: [key, value]
+                } as types.IoCFunctionCall
+            };
+            (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
+
+            return originalSessionSetItem.apply(this, [key, value] as [key: string, value: string]);
+        };
+
+        const originalSessionGetItem = window.sessionStorage.getItem;
+        window.sessionStorage.getItem = function (key: string) {
+            let _event: types.IoC = {
+                type: types.IoCType.FunctionCall,
+                timestamp: Date.now(),
+                executed_on: "",
+                value: {
+                    callee: "window.sessionStorage.getItem",
+                    arguments: [key]
+                } as types.IoCFunctionCall
+            };
+            (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
+
+            return originalSessionGetItem.apply(this, [key] as [key: string]);
+        };
+
         const originalDocumentWrite = document.write;
         document.write = function (code: string) {
             let _event: types.IoC = {
@@ -88,16 +120,39 @@ function place_hooks(reportFnName: string) {
             };
         }
 
+        // url of the script that made the call: the first http(s) frame of the
+        // stack. The sample and these hooks are evaluated without a url, so an
+        // empty initiator means the sample itself
+        const requestInitiator = function(): string {
+            const frames = (new Error().stack || "").split("\n");
+            for (const frame of frames) {
+                const match = frame.match(/(https?:\/\/[^\s()]+?)(?::\d+){0,2}\)?\s*$/);
+                if (match) {
+                    return match[1]
+                }
+            }
+            return ""
+        }
+
         const originalFetch = window.fetch;
         window.fetch = function (...args) {
+            let _input: any = args[0];
+            let _init: any = args[1] || {};
+            let _headers: {[key: string]: string} = {};
+            try {
+                new Headers(_init.headers || (_input instanceof Request ? _input.headers : {}))
+                    .forEach((value: string, key: string) => { _headers[key] = value; });
+            } catch(err) {}
             let _event: types.IoC = {
                 type: types.IoCType.HttpRequest,
                 timestamp: Date.now(),
                 executed_on: "",
                 value: {
-                    url: args[0],
-                    method: "GET",
-                    data: ""
+                    url: _input instanceof Request ? _input.url : String(_input),
+                    method: _init.method || (_input instanceof Request ? _input.method : "GET"),
+                    data: typeof _init.body === "string" ? _init.body : "",
+                    headers: _headers,
+                    initiator: requestInitiator()
                 } as types.IoCHttpRequest
             };
             (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
@@ -114,7 +169,8 @@ function place_hooks(reportFnName: string) {
                 value: {
                     url: args[0],
                     method: "GET",
-                    data: ""
+                    data: "",
+                    initiator: requestInitiator()
                 } as types.IoCHttpRequest
             };
             (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
@@ -123,16 +179,35 @@ function place_hooks(reportFnName: string) {
         };
 
 
+        // every XMLHttpRequest gets an id so that the open, setRequestHeader and
+        // send events can be merged into one request by the analyzer. The WeakMap
+        // lives in this closure, so the page cannot see or tamper with it
+        const xhrRequests = new WeakMap<XMLHttpRequest, {
+            request_id: string,
+            method: string,
+            url: string,
+            headers: {[key: string]: string}
+        }>();
+
         const originalXHROpen = window.XMLHttpRequest.prototype.open;
         window.XMLHttpRequest.prototype.open = function(...args: any) {
+            let _request = {
+                request_id: Math.random().toString(36).slice(2) + Date.now().toString(36),
+                method: String(args[0]),
+                url: String(args[1]),
+                headers: {}
+            };
+            xhrRequests.set(this, _request);
             let _event: types.IoC = {
                 type: types.IoCType.HttpRequest,
                 timestamp: Date.now(),
                 executed_on: "",
                 value: {
-                    url: args[1],
-                    method: args[0],
-                    data: ""
+                    url: _request.url,
+                    method: _request.method,
+                    data: "",
+                    request_id: _request.request_id,
+                    initiator: requestInitiator()
                 } as types.IoCHttpRequest
             };
             (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
@@ -142,18 +217,29 @@ function place_hooks(reportFnName: string) {
 
         // XMLHttpRequest.send hook logs will appear right below the logs
         // of XMLHttpRequest.open hook
+        const originalXHRSetRequestHeader = window.XMLHttpRequest.prototype.setRequestHeader;
+        window.XMLHttpRequest.prototype.setRequestHeader = function(name: string, value: string) {
+            let _request = xhrRequests.get(this);
+            if (_request) {
+                _request.headers[String(name).toLowerCase()] = String(value);
+            }
+            return originalXHRSetRequestHeader.apply(this, [name, value] as [name: string, value: string]);
+        };
+
         const originalXHRSend = window.XMLHttpRequest.prototype.send;
         window.XMLHttpRequest.prototype.send = function(...args: any) {
             try {
-
+                let _request = xhrRequests.get(this);
                 let _event: types.IoC = {
                     type: types.IoCType.HttpRequest,
                     timestamp: Date.now(),
                     executed_on: "",
                     value: {
-                        url: "",
-                        method: "",
-                        data: JSON.stringify(args)
+                        url: _request ? _request.url : "",
+                        method: _request ? _request.method : "",
+                        data: JSON.stringify(args),
+                        headers: _request ? _request.headers : {},
+                        request_id: _request ? _request.request_id : ""
                     } as types.IoCHttpRequest
                 };
                 (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
@@ -194,21 +280,69 @@ function place_hooks(reportFnName: string) {
             }
         });
 
-        let originalAddEventListener = document.addEventListener
-        document.addEventListener = function(listener: string, fn: any) {
+        // short description of the object a listener is attached to,
+        // e.g. "document", "window", "input#card[name=cc_number]"
+        const describeEventTarget = function(target: any): string {
+            if (target === window) {
+                return "window"
+            }
+            if (target === document) {
+                return "document"
+            }
+            if (target instanceof Element) {
+                let description = target.tagName.toLowerCase()
+                if (target.id) {
+                    description += "#" + target.id
+                }
+                const name = target.getAttribute("name")
+                if (name) {
+                    description += "[name=" + name + "]"
+                }
+                return description
+            }
+            return Object.prototype.toString.call(target)
+        }
+
+        // listeners on any target (document, window, inputs, forms) are reported,
+        // keyloggers usually attach them to the input fields directly
+        let originalAddEventListener = EventTarget.prototype.addEventListener
+        EventTarget.prototype.addEventListener = function(listener: string, fn: any, options?: any) {
             let _event: types.IoC = {
                 type: types.IoCType.AddEventListener,
                 timestamp: Date.now(),
                 executed_on: "",
                 value: {
-                    listener: listener
+                    listener: listener,
+                    target: describeEventTarget(this)
                 } as types.IoCAddEventListener
             };
             (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
 
-            return originalAddEventListener.apply(this, [listener, fn] as [listener: string, fn: any]);
+            return originalAddEventListener.apply(this, [listener, fn, options] as [listener: string, fn: any, options?: any]);
         };
 
+        // html injected through innerHTML, analysed like document.write
+        const originalInnerHTML = Object.getOwnPropertyDescriptor(Element.prototype, "innerHTML");
+        if (originalInnerHTML && originalInnerHTML.set) {
+            Object.defineProperty(Element.prototype, "innerHTML", {
+                ...originalInnerHTML,
+                set: function (html: string) {
+                    let _event: types.IoC = {
+                        type: types.IoCType.FunctionCall,
+                        timestamp: Date.now(),
+                        executed_on: "",
+                        value: {
+                            callee: "Element.innerHTML",
+                            arguments: [String(html), describeEventTarget(this)]
+                        } as types.IoCFunctionCall
+                    };
+                    (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
+
+                    return originalInnerHTML.set!.call(this, html);
+                }
+            });
+        }
+
         let originalSetTimeout = setTimeout
         const hookedSetTimeout = function (
             callback: TimerHandler,
@@ -260,6 +394,24 @@ function place_hooks(reportFnName: string) {
 
         const documentObserver = new MutationObserver((mutationList) => {
             for (const mutation of mutationList) {
+                // form action rewritten to send the submitted data elsewhere
+                if (mutation.type == "attributes" && mutation.attributeName == "action") {
+                    let _event: types.IoC = {
+                        type: types.IoCType.FunctionCall,
+                        timestamp: Date.now(),
+                        executed_on: "",
+                        value: {
+                            callee: "HTMLFormElement.action",
+                            arguments: [
+                                (mutation.target as HTMLFormElement).getAttribute("action") || "",
+                                mutation.oldValue || "",
+                                describeEventTarget(mutation.target)
+                            ]
+                        } as types.IoCFunctionCall
+                    };
+                    (window[reportFnName as keyof typeof window] as (event: types.IoC) => void)(_event)
+                    continue
+                }
                 mutation.addedNodes.forEach((node: Node, key: number, parent: NodeList) => {
                     if (node.ELEMENT_NODE == node.nodeType) {
                         if(node.nodeName.toLowerCase() in known_network_dom_elements) {
@@ -281,7 +433,13 @@ function place_hooks(reportFnName: string) {
             }
         });
 
-        documentObserver.observe(document, { childList: true, subtree: true });
+        documentObserver.observe(document, {
+            childList: true,
+            subtree: true,
+            attributes: true,
+            attributeFilter: ["action"],
+            attributeOldValue: true
+        });
     } catch(err) {
         console.log("Oops! Uncaught error in hooks: ", err)
     }
@@ -1,20 +1,52 @@
-import { Page } from "puppeteer";
+import { Page, ElementHandle } from "puppeteer";
+import { CanaryPlan } from "./types";
 
 // Lure tries to trigger possibly hidden functionalities
 // of the sample by performing actions on the page 
 // (e.g. submit the form found on the loaded page)
 class Lure {
     private page: Page;
+    private canaryPlan: CanaryPlan | null;
  
-    constructor(page: Page) {
+    constructor(page: Page, canaryPlan: CanaryPlan | null = null) {
         this.page = page;
+        this.canaryPlan = canaryPlan;
+    }
+
+    // pick the canary token matching the kind of data the input expects,
+    // so that the core can tell which data class was exfiltrated
+    private async _input_value(input: ElementHandle<HTMLInputElement>, idx: string): Promise<string> {
+        if (!this.canaryPlan) {
+            return `fake_input_from_sandbox_${idx}`
+        }
+        const hints = await input.evaluate((_input: HTMLInputElement) => {
+            return {
+                type: (_input.type || "").toLowerCase(),
+                description: [
+                    _input.name,
+                    _input.id,
+                    _input.autocomplete,
+                    _input.placeholder
+                ].join(" ").toLowerCase()
+            }
+        })
+        if (hints.type == "password") {
+            return this.canaryPlan.password
+        }
+        if (hints.type == "email" || /e-?mail/.test(hints.description)) {
+            return this.canaryPlan.email
+        }
+        if (/cc-number|card|cc_?num|pan\b/.test(hints.description)) {
+            return this.canaryPlan.card
+        }
+        return this.canaryPlan.text
     }
 
     // find a form, place dummy input and submit it
     private async _forms_lure() {
         const inputs = await this.page.$$("input");
         for (const _i in inputs) {
-            await inputs[_i].type(`fake_input_from_sandbox_${_i}`)
+            await inputs[_i].type(await this._input_value(inputs[_i], _i))
         }
     
         const forms = await this.page.$$("form");
@@ -32,4 +64,4 @@ class Lure {
 
 export {
     Lure
-}
\ No newline at end of file
+}
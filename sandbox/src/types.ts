@@ -40,13 +40,17 @@ const enum IoCType {
 type IoCHttpRequest = {
     method: string,
     url: string,
-    data: string
+    data: string,
+    headers?: {[key: string]: string},
+    request_id?: string,
+    initiator?: string
 }
 
 type IoCHttpResponse = {
     status: String,
     url: string,
-    data: string
+    data: string,
+    content_type?: string
 }
 
 type IoCConsoleLog = {
@@ -83,7 +87,8 @@ type IoCGetCookie = {
 }
 
 type IoCAddEventListener = {
-    listener: string
+    listener: string,
+    target?: string
 }
 
 type IoC = {
@@ -102,7 +107,19 @@ type IoC = {
         IoCAddEventListener,
 }
 
+// tokens planted in the bait website, generated by the core for every analysis.
+// Each kind of data gets its own token so a leak can be attributed to it
+type CanaryPlan = {
+    text: string,
+    card: string,
+    password: string,
+    email: string,
+    cookie_value: string,
+    local_storage_value: string
+}
+
 type IoCsFromAnalysis = {
+    schema_version: number,
     file_hash: string,
     analysis_id: string,
     iocs: IoC[]
@@ -122,6 +139,7 @@ export {
     IoCSetTimeout,
     IoCSuspiciousFileDownload,
     IoCsFromAnalysis,
+    CanaryPlan,
     RabbitMQConfig,
     RabbitMQQueue,
     RabbitMQExchange
@@ -9,6 +9,18 @@ function validate_logging_level(lv: string): string {
     }
 }
 
+// whether the url is served by the given site or one of its subdomains
+function is_same_site(url: string, site: string): boolean {
+    try {
+        const host = new URL(url).hostname.toLowerCase();
+        const site_host = new URL(site).hostname.toLowerCase().replace(/^www\./, "");
+        return host === site_host || host.endsWith("." + site_host);
+    } catch(err) {
+        return false;
+    }
+}
+
 export {
-    validate_logging_level
+    validate_logging_level,
+    is_same_site
 }
\ No newline at end of file
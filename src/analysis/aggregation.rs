@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::analyzer::{AnalysisType, Finding, Severity};
+use crate::analysis::dast_ioc_types::IoCValue;
+
+// identical findings (same analysis type, title, poc and tags) reported once with
+// how often, when and where they happened
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AggregatedFinding {
+    pub r#type: AnalysisType,
+    pub title: String,
+    pub poc: String,
+    pub severity: Severity,
+    pub occurrences: usize,
+    // 0 for static findings
+    pub first_timestamp: u64,
+    pub last_timestamp: u64,
+    // bait websites the finding was raised on, in order of first occurrence
+    pub executed_on: Vec<String>,
+    // event of the first occurrence
+    pub ioc: IoCValue,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>
+}
+
+pub fn aggregate(findings: &[Finding]) -> Vec<AggregatedFinding> {
+    let mut aggregated: Vec<AggregatedFinding> = Vec::new();
+    for _f in findings {
+        let existing = aggregated
+            .iter_mut()
+            .find(|a| a.r#type == _f.r#type && a.title == _f.title && a.poc == _f.poc && a.tags == _f.tags);
+        match existing {
+            Some(_a) => {
+                _a.occurrences += 1;
+                _a.first_timestamp = _a.first_timestamp.min(_f.timestamp);
+                _a.last_timestamp = _a.last_timestamp.max(_f.timestamp);
+                if _f.severity > _a.severity {
+                    _a.severity = _f.severity.clone();
+                }
+                if !_f.executed_on.is_empty() && !_a.executed_on.contains(&_f.executed_on) {
+                    _a.executed_on.push(_f.executed_on.clone());
+                }
+            },
+            None => aggregated.push(AggregatedFinding {
+                r#type: _f.r#type.clone(),
+                title: _f.title.clone(),
+                poc: _f.poc.clone(),
+                severity: _f.severity.clone(),
+                occurrences: 1,
+                first_timestamp: _f.timestamp,
+                last_timestamp: _f.timestamp,
+                executed_on: match _f.executed_on.is_empty() {
+                    true => Vec::new(),
+                    false => vec![_f.executed_on.clone()]
+                },
+                ioc: _f.ioc.clone(),
+                tags: _f.tags.clone()
+            })
+        }
+    }
+    aggregated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(executed_on: &str, timestamp: u64, poc: &str) -> Finding {
+        Finding {
+            r#type: AnalysisType::Dynamic,
+            executed_on: executed_on.to_string(),
+            timestamp,
+            severity: Severity::VeryHigh,
+            poc: poc.to_string(),
+            ioc: IoCValue::None,
+            title: "window.eval was called".to_string(),
+            tags: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_identical_findings_are_aggregated() {
+        let mut findings: Vec<Finding> = (0..500).map(|i| finding("https://a.com", 1000 + i, "window.eval")).collect();
+        findings.push(finding("https://b.com", 900, "window.eval"));
+        findings.push(finding("https://a.com", 950, "window.eval(atob)"));
+        let aggregated = aggregate(&findings);
+        assert_eq!(aggregated.len(), 2);
+        assert_eq!(aggregated[0].occurrences, 501);
+        assert_eq!(aggregated[0].first_timestamp, 900);
+        assert_eq!(aggregated[0].last_timestamp, 1499);
+        assert_eq!(aggregated[0].executed_on, vec!["https://a.com", "https://b.com"]);
+        assert_eq!(aggregated[1].occurrences, 1);
+    }
+}
@@ -2,7 +2,7 @@ use core::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{analysis::dast_ioc_types::{self, IoC, IoCValue}, store::models::FileAnalysisReport};
+use crate::{analysis::dast_ioc_types::{self, IoC, IoCValue}, analysis::runtime_decoded, store::models::FileAnalysisReport};
 
 #[allow(dead_code)]
 #[repr(i64)]
@@ -46,10 +46,25 @@ pub enum AnalysisType {
 pub struct Finding {
     pub r#type: AnalysisType,
     pub executed_on: String,
+    // when the event behind the finding was reported, 0 for static findings
+    #[serde(default)]
+    pub timestamp: u64,
     pub severity: Severity,
     pub poc: String,
     pub ioc: IoCValue,
     pub title: String,
+    // where the finding comes from when the analysis type alone does not tell,
+    // e.g. "runtime-decoded" for code captured in the sandbox and analysed statically
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Finding {
+    // raised while analysing sandbox events, including the static findings of
+    // runtime decoded code, so replaced whenever the events are analysed again
+    pub fn is_from_events(&self) -> bool {
+        self.r#type == AnalysisType::Dynamic || self.tags.iter().any(|t| t == runtime_decoded::RUNTIME_DECODED_TAG)
+    }
 }
 
 impl fmt::Display for Finding {
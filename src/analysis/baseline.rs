@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::analysis::analyzer::{Finding, Severity};
+use crate::analysis::dast_ioc_types::{IoC, IoCValue};
+use crate::analysis::storage_access::parse_cookie_pairs;
+
+// findings whose event also happened in the control run of the bait website
+pub const BASELINE_TAG: &str = "baseline";
+
+// arguments and console output are compared on their beginning only
+const MAX_SIGNATURE_TEXT_LEN: usize = 256;
+// hex chars of the request body digest kept in the signature
+const BODY_DIGEST_LEN: usize = 16;
+
+// control runs of the bait websites without the sample, their events are the
+// noise of the site itself: its ads, analytics and own scripts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BaselineConf {
+    pub enabled: bool,
+    // a control run older than this is run again with the next analysis
+    pub max_age_hours: u64,
+    // drop the matching findings instead of lowering their weight
+    pub suppress: bool
+}
+
+impl Default for BaselineConf {
+    fn default() -> Self {
+        BaselineConf {
+            enabled: true,
+            max_age_hours: 24,
+            suppress: false
+        }
+    }
+}
+
+// scheme, host and path, query strings and fragments carry per visit ids
+fn url_signature(url: &str) -> String {
+    match Url::parse(url.trim()) {
+        Ok(_u) => format!("{}://{}{}", _u.scheme(), _u.host_str().unwrap_or(""), _u.path()),
+        Err(_) => url.trim().split(['?', '#']).next().unwrap_or("").to_string()
+    }
+}
+
+fn text_signature(text: &str) -> String {
+    text.trim().chars().take(MAX_SIGNATURE_TEXT_LEN).collect()
+}
+
+// a skimmer posting to an endpoint the bait website also uses only differs
+// in what it sends
+fn body_signature(body: &str) -> String {
+    match body.trim().is_empty() {
+        true => String::new(),
+        false => format!(" body:{}", &sha256::digest(body.trim())[..BODY_DIGEST_LEN])
+    }
+}
+
+// what identifies an event across runs, None for values that tell nothing
+pub fn event_signature(value: &IoCValue) -> Option<String> {
+    let signature = match value {
+        IoCValue::IoCHttpRequest(_v) => format!("request {} {}{}", _v.method.to_uppercase(), url_signature(&_v.url), body_signature(&_v.data)),
+        IoCValue::IoCHttpResponse(_v) => format!("response {}", url_signature(&_v.url)),
+        IoCValue::IoCFunctionCall(_v) => format!("call {} {}", _v.callee,
+            text_signature(_v.arguments.first().map(|a| a.as_str()).unwrap_or(""))),
+        IoCValue::IoCNewNetworkHtmlElement(_v) => format!("element {} {}", _v.element_type.to_lowercase(), url_signature(&_v.src)),
+        // get and set cookie values have the same shape, the names are compared
+        IoCValue::IoCSetCookie(_v) => format!("cookie {}", cookie_names(&_v.cookie)),
+        IoCValue::IoCGetCookie(_v) => format!("cookie {}", cookie_names(&_v.cookie)),
+        IoCValue::IoCConsoleLog(_v) => format!("console {}", text_signature(&_v.text)),
+        IoCValue::IoCAddEventListener(_v) => format!("listener {} {}", _v.listener, _v.target),
+        IoCValue::IoCSuspiciousFileDownload(_v) => format!("download {}", url_signature(&_v.url)),
+        IoCValue::None => return None
+    };
+    Some(signature)
+}
+
+fn cookie_names(cookies: &str) -> String {
+    let mut names: Vec<String> = parse_cookie_pairs(cookies).into_iter().map(|(n, _)| n).collect();
+    names.sort();
+    names.dedup();
+    names.join(",")
+}
+
+// events of a control run of a bait website
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    signatures: HashSet<String>
+}
+
+impl Baseline {
+    pub fn from_iocs(iocs: &[IoC]) -> Self {
+        Baseline {
+            signatures: iocs.iter().filter_map(|i| event_signature(&i.value)).collect()
+        }
+    }
+
+    pub fn contains(&self, value: &IoCValue) -> bool {
+        match event_signature(value) {
+            Some(_s) => self.signatures.contains(&_s),
+            None => false
+        }
+    }
+}
+
+// the planted canary values are never part of the bait website's own noise
+fn carries_canary(finding: &Finding, canaries: &[String]) -> bool {
+    let event = serde_json::to_string(&finding.ioc).unwrap_or_default();
+    canaries
+        .iter()
+        .filter(|c| !c.is_empty())
+        .any(|c| finding.poc.contains(c.as_str()) || event.contains(c.as_str()))
+}
+
+// tags the findings raised on events the bait website also produced without
+// the sample and lowers them to low severity, or drops them with `suppress`.
+// Findings whose event or poc carries one of the `canaries` are kept as they
+// are. `baselines` are keyed by bait website. Returns how many findings matched
+pub fn apply_baselines(findings: &mut Vec<Finding>, baselines: &HashMap<String, Baseline>, suppress: bool, canaries: &[String]) -> usize {
+    let in_baseline = |f: &Finding| f.is_from_events()
+        && baselines.get(&f.executed_on).is_some_and(|b| b.contains(&f.ioc))
+        && !carries_canary(f, canaries);
+    let before = findings.len();
+    if suppress {
+        findings.retain(|f| !in_baseline(f));
+        return before - findings.len();
+    }
+    let mut matched = 0;
+    for _f in findings.iter_mut() {
+        if _f.tags.iter().any(|t| t == BASELINE_TAG) || !in_baseline(_f) {
+            continue;
+        }
+        _f.tags.push(BASELINE_TAG.to_string());
+        _f.severity = Severity::Low;
+        matched += 1;
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyzer::AnalysisType;
+    use crate::analysis::dast_ioc_types::{IoCFunctionCall, IoCHttpRequest, IoCType};
+
+    fn request(url: &str, data: &str) -> IoCValue {
+        IoCValue::IoCHttpRequest(IoCHttpRequest {
+            method: "get".to_string(),
+            url: url.to_string(),
+            data: data.to_string(),
+            headers: Default::default(),
+            request_id: String::new(),
+            initiator: String::new()
+        })
+    }
+
+    fn finding(title: &str, ioc: IoCValue) -> Finding {
+        Finding {
+            r#type: AnalysisType::Dynamic,
+            executed_on: "https://news.com".to_string(),
+            timestamp: 0,
+            severity: Severity::High,
+            poc: String::new(),
+            ioc,
+            title: title.to_string(),
+            tags: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_findings_of_the_bait_website_noise() {
+        let control = vec![
+            IoC { ioc_type: IoCType::HttpRequest, executed_on: "https://news.com".to_string(), timestamp: 0,
+                value: request("https://ads.adnet.com/serve?slot=1&rnd=8812", "") },
+            IoC { ioc_type: IoCType::HttpRequest, executed_on: "https://news.com".to_string(), timestamp: 0,
+                value: request("https://news.com/api/cart", "{\"items\":[]}") },
+            IoC { ioc_type: IoCType::FunctionCall, executed_on: "https://news.com".to_string(), timestamp: 0,
+                value: IoCValue::IoCFunctionCall(IoCFunctionCall { callee: "window.eval".to_string(), arguments: vec!["adInit()".to_string()] }) }
+        ];
+        let baselines = HashMap::from([("https://news.com".to_string(), Baseline::from_iocs(&control))]);
+        let mut findings = vec![
+            finding("bad reputation url called", request("https://ads.adnet.com/serve?slot=1&rnd=1907", "")),
+            finding("window.eval was called", IoCValue::IoCFunctionCall(IoCFunctionCall {
+                callee: "window.eval".to_string(), arguments: vec!["skim()".to_string()] })),
+            finding("bad reputation url called", request("https://collect.evil.com/c", "")),
+            // same endpoint as the bait website, different payload
+            finding("http request sent containing credit card data", request("https://news.com/api/cart", "cc=4111111111111111")),
+            // a canary in the query string of a known endpoint
+            finding("http request sent containing email data", request("https://ads.adnet.com/serve?u=c4f1@mailbox.org", ""))
+        ];
+        let canaries = vec!["c4f1@mailbox.org".to_string()];
+        assert_eq!(apply_baselines(&mut findings, &baselines, false, &canaries), 1);
+        assert_eq!(findings[0].tags, vec![BASELINE_TAG.to_string()]);
+        assert_eq!(findings[0].severity, Severity::Low);
+        assert!(findings[1..].iter().all(|f| f.tags.is_empty()));
+        // already tagged findings are not counted again
+        assert_eq!(apply_baselines(&mut findings, &baselines, false, &canaries), 0);
+
+        assert_eq!(apply_baselines(&mut findings, &baselines, true, &canaries), 1);
+        assert_eq!(findings.len(), 4);
+    }
+}
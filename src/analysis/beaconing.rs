@@ -0,0 +1,120 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+// fewer requests than this cannot tell a beacon from a few page requests
+const MIN_BEACON_REQUESTS: usize = 4;
+// bursts (retries, parallel asset loading) are not beacons
+const MIN_BEACON_INTERVAL_MS: f64 = 250.0;
+// standard deviation of the intervals relative to their mean. Timers in the
+// sandbox are precise, so real beacons stay well below this
+const MAX_BEACON_JITTER: f64 = 0.25;
+
+// regular-interval requests to the same host, typical of c2 polling
+// and of keyloggers flushing their buffer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Beacon {
+    pub executed_on: String,
+    pub host: String,
+    pub count: usize,
+    // mean time between two requests
+    pub interval_ms: f64,
+    // standard deviation of the intervals relative to `interval_ms`
+    pub jitter: f64,
+    pub first_timestamp: u64,
+    pub last_timestamp: u64
+}
+
+impl fmt::Display for Beacon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {} requests every {:.0}ms (jitter {:.1}%) over {}ms",
+            self.host, self.count, self.interval_ms, self.jitter * 100.0,
+            self.last_timestamp - self.first_timestamp)
+    }
+}
+
+// a request as needed by the detection: who sent it, where and when
+#[derive(Debug, Clone)]
+pub struct TimedRequest {
+    pub executed_on: String,
+    pub host: String,
+    pub timestamp: u64
+}
+
+// groups the requests by bait website and destination host and reports the
+// groups whose requests are evenly spaced in time
+pub fn detect_beacons(requests: &[TimedRequest]) -> Vec<Beacon> {
+    let mut groups: HashMap<(&str, &str), Vec<u64>> = HashMap::new();
+    let mut order: Vec<(&str, &str)> = Vec::new();
+    for _r in requests {
+        let key = (_r.executed_on.as_str(), _r.host.as_str());
+        if !groups.contains_key(&key) {
+            order.push(key);
+        }
+        groups.entry(key).or_default().push(_r.timestamp);
+    }
+
+    let mut beacons: Vec<Beacon> = Vec::new();
+    for key in order {
+        let mut timestamps = groups.remove(&key).unwrap_or_default();
+        if timestamps.len() < MIN_BEACON_REQUESTS {
+            continue;
+        }
+        timestamps.sort();
+        let intervals: Vec<f64> = timestamps.windows(2).map(|w| (w[1] - w[0]) as f64).collect();
+        let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+        if mean < MIN_BEACON_INTERVAL_MS {
+            continue;
+        }
+        let variance = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+        let jitter = variance.sqrt() / mean;
+        if jitter > MAX_BEACON_JITTER {
+            continue;
+        }
+        beacons.push(Beacon {
+            executed_on: key.0.to_string(),
+            host: key.1.to_string(),
+            count: timestamps.len(),
+            interval_ms: mean,
+            jitter,
+            first_timestamp: timestamps[0],
+            last_timestamp: timestamps[timestamps.len() - 1]
+        });
+    }
+    beacons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requests(host: &str, timestamps: &[u64]) -> Vec<TimedRequest> {
+        timestamps
+            .iter()
+            .map(|t| TimedRequest {
+                executed_on: "https://bait.com".to_string(),
+                host: host.to_string(),
+                timestamp: *t
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_regular_requests_are_beacons() {
+        let mut r = requests("c2.com", &[1000, 2010, 2995, 4005, 5000]);
+        r.append(&mut requests("cdn.com", &[1000, 1100, 3000, 3050]));
+        let beacons = detect_beacons(&r);
+        assert_eq!(beacons.len(), 1);
+        assert_eq!(beacons[0].host, "c2.com");
+        assert_eq!(beacons[0].count, 5);
+        assert!((beacons[0].interval_ms - 1000.0).abs() < 1.0);
+        assert!(beacons[0].jitter < 0.02);
+    }
+
+    #[test]
+    fn test_bursts_and_few_requests_are_not_beacons() {
+        assert!(detect_beacons(&requests("api.com", &[0, 10, 20, 30, 40])).is_empty());
+        assert!(detect_beacons(&requests("api.com", &[0, 1000, 2000])).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// prefix of the generic input token. Older sandboxes fill every input
+// with this prefix followed by the index of the input
+pub const SANDBOX_INPUT_PREFIX: &str = "fake_input_from_sandbox_";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DataClass {
+    UserInput,
+    CreditCard,
+    Password,
+    Email,
+    Cookie,
+    LocalStorage,
+    SessionStorage
+}
+
+impl DataClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataClass::UserInput => "user input",
+            DataClass::CreditCard => "credit card",
+            DataClass::Password => "password",
+            DataClass::Email => "email",
+            DataClass::Cookie => "cookie",
+            DataClass::LocalStorage => "local storage",
+            DataClass::SessionStorage => "session storage",
+        }
+    }
+}
+
+impl fmt::Display for DataClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// CanaryPlan holds the values that the sandbox plants in the bait website:
+// one token per kind of input field plus a cookie and a localStorage value.
+// Every token is unique per analysis, so a token found in an outgoing
+// request tells exactly which kind of data was stolen.
+// The field order is part of the msgpack message sent to the sandbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CanaryPlan {
+    pub text: String,
+    pub card: String,
+    pub password: String,
+    pub email: String,
+    pub cookie_value: String,
+    pub local_storage_value: String
+}
+
+impl CanaryPlan {
+    pub fn generate() -> Self {
+        CanaryPlan {
+            text: format!("{}{}", SANDBOX_INPUT_PREFIX, &random_hex()[..12]),
+            card: luhn_card_number(),
+            password: format!("Pw{}!", &random_hex()[..14]),
+            email: format!("{}@mailbox.org", &random_hex()[..16]),
+            cookie_value: random_hex(),
+            local_storage_value: random_hex()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn tokens(&self) -> Vec<(String, DataClass)> {
+        vec![
+            (self.text.clone(), DataClass::UserInput),
+            (self.card.clone(), DataClass::CreditCard),
+            (self.password.clone(), DataClass::Password),
+            (self.email.clone(), DataClass::Email),
+            (self.cookie_value.clone(), DataClass::Cookie),
+            (self.local_storage_value.clone(), DataClass::LocalStorage),
+        ]
+        .into_iter()
+        .filter(|(t, _)| !t.is_empty())
+        .collect()
+    }
+}
+
+fn random_hex() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+// a visa-like 16 digit number that passes the luhn check, so that
+// skimmers validating the card before sending it still take it
+fn luhn_card_number() -> String {
+    let mut digits: Vec<u32> = vec![4];
+    for b in Uuid::new_v4().as_bytes().iter().take(14) {
+        digits.push(*b as u32 % 10);
+    }
+    digits.push(luhn_check_digit(&digits));
+    digits.iter().map(|d| char::from_digit(*d, 10).unwrap_or('0')).collect()
+}
+
+fn luhn_check_digit(digits: &[u32]) -> u32 {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| {
+            if i % 2 == 0 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                *d
+            }
+        })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luhn_valid(number: &str) -> bool {
+        let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+        let (body, check) = digits.split_at(digits.len() - 1);
+        luhn_check_digit(body) == check[0]
+    }
+
+    #[test]
+    fn test_generated_plan_has_distinct_tokens() {
+        let plan = CanaryPlan::generate();
+        let tokens = plan.tokens();
+        assert_eq!(tokens.len(), 6);
+        for (i, (a, _)) in tokens.iter().enumerate() {
+            for (b, _) in tokens.iter().skip(i + 1) {
+                assert!(!a.contains(b.as_str()) && !b.contains(a.as_str()));
+            }
+        }
+        assert!(plan.text.starts_with(SANDBOX_INPUT_PREFIX));
+    }
+
+    #[test]
+    fn test_card_number_is_luhn_valid() {
+        assert!(luhn_valid("4111111111111111"));
+        for _ in 0..20 {
+            let card = CanaryPlan::generate().card;
+            assert_eq!(card.len(), 16);
+            assert!(luhn_valid(&card), "{}", card);
+        }
+    }
+}
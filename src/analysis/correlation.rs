@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::analysis::dast_ioc_types::{IoC, IoCHttpRequest, IoCHttpResponse, IoCType, IoCValue};
+
+// A complete http request as seen by the sandbox. XMLHttpRequest calls
+// are reported in multiple events (open/setRequestHeader/send) and the
+// response is reported separately by the browser, so these are merged here
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpRequestRecord {
+    pub request_id: String,
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    pub executed_on: String,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub initiator: String,
+    pub response: Option<IoCHttpResponse>
+}
+
+impl HttpRequestRecord {
+    fn from_ioc(ioc: &IoC, v: &IoCHttpRequest) -> Self {
+        HttpRequestRecord {
+            request_id: v.request_id.clone(),
+            method: v.method.clone(),
+            url: v.url.clone(),
+            headers: v.headers.clone(),
+            body: match is_xhr_event(v) {
+                true => unwrap_send_arguments(&v.data),
+                false => v.data.clone()
+            },
+            executed_on: ioc.executed_on.clone(),
+            timestamp: ioc.timestamp,
+            initiator: v.initiator.clone(),
+            response: None
+        }
+    }
+
+    fn merge(&mut self, v: &IoCHttpRequest) {
+        if self.method.is_empty() {
+            self.method = v.method.clone();
+        }
+        if self.url.is_empty() {
+            self.url = v.url.clone();
+        }
+        if self.body.is_empty() {
+            self.body = unwrap_send_arguments(&v.data);
+        }
+        if self.initiator.is_empty() {
+            self.initiator = v.initiator.clone();
+        }
+        for (_k, _v) in v.headers.iter() {
+            self.headers.insert(_k.clone(), _v.clone());
+        }
+    }
+
+    pub fn to_ioc_value(&self) -> IoCValue {
+        IoCValue::IoCHttpRequest(IoCHttpRequest {
+            method: self.method.clone(),
+            url: self.url.clone(),
+            data: self.body.clone(),
+            headers: self.headers.clone(),
+            request_id: self.request_id.clone(),
+            initiator: self.initiator.clone()
+        })
+    }
+
+    // a request whose url is known but was not sent yet (XMLHttpRequest.open)
+    fn is_waiting_for_send(&self) -> bool {
+        !self.url.is_empty() && self.body.is_empty()
+    }
+
+    fn answers(&self, response: &IoCHttpResponse) -> bool {
+        if self.response.is_some() || self.url.is_empty() {
+            return false;
+        }
+        if response.url == self.url {
+            return true;
+        }
+        // requests are often made with relative or protocol relative urls
+        // while the browser reports the absolute response url
+        match Url::parse(&response.url) {
+            Ok(_response_url) => match _response_url.join(&self.url) {
+                Ok(_request_url) => _request_url == _response_url,
+                Err(_) => false
+            },
+            Err(_) => false
+        }
+    }
+}
+
+// position of an event in the correlated output: either a merged
+// request record or an event that is kept as is
+enum Slot {
+    Request(usize),
+    Event(IoC)
+}
+
+#[derive(Debug, Clone)]
+pub struct CorrelatedIoCs {
+    // the original events where every group of related http request events
+    // is replaced by one complete IoCHttpRequest
+    pub iocs: Vec<IoC>,
+    // one record per complete request, with its response when seen
+    pub requests: Vec<HttpRequestRecord>
+}
+
+// events of XMLHttpRequest calls: tagged with a request id, or untagged `send`
+// events of older sandboxes that have neither url nor method. Fetch and
+// window.open events carry the body as it was sent
+fn is_xhr_event(v: &IoCHttpRequest) -> bool {
+    !v.request_id.is_empty() || (v.url.is_empty() && v.method.is_empty())
+}
+
+// the sandbox reports `XMLHttpRequest.send` arguments as a json array
+fn unwrap_send_arguments(data: &str) -> String {
+    match serde_json::from_str::<Vec<serde_json::Value>>(data) {
+        Ok(_args) => {
+            match _args.first() {
+                Some(serde_json::Value::String(_s)) => _s.clone(),
+                Some(serde_json::Value::Null) | None => String::new(),
+                Some(_v) => _v.to_string()
+            }
+        },
+        Err(_) => data.to_string()
+    }
+}
+
+// merges the events that belong to the same http request and pairs
+// requests with responses. Events are processed in timestamp order
+pub fn correlate(mut iocs: Vec<IoC>) -> CorrelatedIoCs {
+    iocs.sort_by_key(|i| i.timestamp);
+
+    let mut requests: Vec<HttpRequestRecord> = Vec::new();
+    let mut slots: Vec<Slot> = Vec::new();
+    let mut by_request_id: HashMap<(String, String), usize> = HashMap::new();
+
+    for ioc in iocs {
+        match &ioc.value {
+            IoCValue::IoCHttpRequest(_v) => {
+                // events tagged by the sandbox with a request id
+                if !_v.request_id.is_empty() {
+                    let key = (ioc.executed_on.clone(), _v.request_id.clone());
+                    match by_request_id.get(&key) {
+                        Some(_idx) => requests[*_idx].merge(_v),
+                        None => {
+                            by_request_id.insert(key, requests.len());
+                            slots.push(Slot::Request(requests.len()));
+                            requests.push(HttpRequestRecord::from_ioc(&ioc, _v));
+                        }
+                    }
+                    continue;
+                }
+                // untagged XMLHttpRequest.send: it belongs to the latest opened
+                // request of the same bait website that has not been sent yet
+                if _v.url.is_empty() && _v.method.is_empty() {
+                    let pending = requests
+                        .iter()
+                        .rposition(|r| r.executed_on == ioc.executed_on && r.request_id.is_empty() && r.is_waiting_for_send());
+                    if let Some(_idx) = pending {
+                        debug!("merging request body into {}", requests[_idx].url);
+                        requests[_idx].merge(_v);
+                        continue;
+                    }
+                }
+                slots.push(Slot::Request(requests.len()));
+                requests.push(HttpRequestRecord::from_ioc(&ioc, _v));
+            },
+            IoCValue::IoCHttpResponse(_v) => {
+                let answered = requests
+                    .iter()
+                    .position(|r| r.executed_on == ioc.executed_on && r.answers(_v));
+                if let Some(_idx) = answered {
+                    requests[_idx].response = Some(_v.clone());
+                }
+                slots.push(Slot::Event(ioc));
+            },
+            _ => slots.push(Slot::Event(ioc))
+        }
+    }
+
+    let correlated_iocs: Vec<IoC> = slots
+        .into_iter()
+        .map(|s| match s {
+            Slot::Request(_idx) => {
+                let r = &requests[_idx];
+                IoC {
+                    ioc_type: IoCType::HttpRequest,
+                    executed_on: r.executed_on.clone(),
+                    timestamp: r.timestamp,
+                    value: r.to_ioc_value()
+                }
+            },
+            Slot::Event(_ioc) => _ioc
+        })
+        .collect();
+
+    CorrelatedIoCs {
+        iocs: correlated_iocs,
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(ts: u64, method: &str, url: &str, data: &str, request_id: &str) -> IoC {
+        IoC {
+            ioc_type: IoCType::HttpRequest,
+            executed_on: "https://bait.com".to_string(),
+            timestamp: ts,
+            value: IoCValue::IoCHttpRequest(IoCHttpRequest {
+                method: method.to_string(),
+                url: url.to_string(),
+                data: data.to_string(),
+                headers: HashMap::new(),
+                request_id: request_id.to_string(),
+                initiator: String::new()
+            })
+        }
+    }
+
+    fn response(ts: u64, url: &str) -> IoC {
+        IoC {
+            ioc_type: IoCType::HttpResponse,
+            executed_on: "https://bait.com".to_string(),
+            timestamp: ts,
+            value: IoCValue::IoCHttpResponse(IoCHttpResponse {
+                status: "200".to_string(),
+                url: url.to_string(),
+                data: String::new(),
+                content_type: String::new()
+            })
+        }
+    }
+
+    #[test]
+    fn test_untagged_open_send_are_merged() {
+        let c = correlate(vec![
+            request(1, "POST", "https://evil.com/c", "", ""),
+            request(2, "", "", "[\"cc=4111\"]", ""),
+            response(3, "https://evil.com/c"),
+        ]);
+        assert_eq!(c.requests.len(), 1);
+        assert_eq!(c.requests[0].method, "POST");
+        assert_eq!(c.requests[0].body, "cc=4111");
+        assert!(c.requests[0].response.is_some());
+        // merged request + response
+        assert_eq!(c.iocs.len(), 2);
+    }
+
+    #[test]
+    fn test_tagged_events_are_merged_by_request_id() {
+        let c = correlate(vec![
+            request(1, "GET", "/a", "", "r1"),
+            request(2, "POST", "//evil.com/b", "", "r2"),
+            request(3, "", "", "[\"x\"]", "r1"),
+            request(4, "", "", "[\"y\"]", "r2"),
+            response(5, "https://evil.com/b"),
+        ]);
+        assert_eq!(c.requests.len(), 2);
+        assert_eq!(c.requests[0].body, "x");
+        assert_eq!(c.requests[1].body, "y");
+        assert!(c.requests[0].response.is_none());
+        assert!(c.requests[1].response.is_some());
+    }
+
+    #[test]
+    fn test_send_without_open_is_kept() {
+        let c = correlate(vec![request(1, "", "", "[null]", "")]);
+        assert_eq!(c.requests.len(), 1);
+        assert_eq!(c.requests[0].body, "");
+    }
+
+    #[test]
+    fn test_fetch_json_array_body_is_kept_whole() {
+        let body = "[\"user@x.com\",\"Pw0123456789abcd!\"]";
+        let c = correlate(vec![
+            request(1, "POST", "https://evil.com/f", body, ""),
+            request(2, "POST", "https://evil.com/x", "", "r1"),
+            request(3, "POST", "https://evil.com/x", &serde_json::to_string(&vec![body]).unwrap(), "r1"),
+        ]);
+        assert_eq!(c.requests.len(), 2);
+        assert_eq!(c.requests[0].body, body);
+        // the send argument is unwrapped once, the json array the page sent stays whole
+        assert_eq!(c.requests[1].body, body);
+    }
+}
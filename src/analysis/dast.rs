@@ -1,23 +1,30 @@
-use std::fs;
-use std::fs::create_dir_all;
-use std::io::Read;
-use std::fs::File;
 use std::path::PathBuf;
+use std::sync::Arc;
 use serde::Deserialize;
 use serde_json::Value;
 use log::{error, warn, debug, info};
-use std::collections::HashMap;
-use url::Url;
-use publicsuffix::{Psl, List};
-use std::str;
+use std::collections::{HashMap, HashSet};
 use sha256;
 
 use crate::analysis::analyzer::Finding;
 use crate::integrations::virus_total::VTClient;
 use crate::store::models::FileAnalysisReport;
-use crate::utils;
 use crate::analysis::analyzer;
 use crate::analysis::dast_ioc_types;
+use crate::analysis::correlation;
+use crate::analysis::domain_heuristics;
+use crate::analysis::domain_intel::{DomainIntel, HostInfo, HostKind};
+use crate::analysis::payload_decoder;
+use crate::analysis::runtime_decoded;
+use crate::analysis::html_injection;
+use crate::analysis::storage_access::{self, SensitiveStorage, StorageKind};
+use crate::analysis::event_rules;
+use crate::analysis::beaconing::{self, TimedRequest};
+use crate::analysis::input_capture::{self, Leak, ListenerRegistration};
+use crate::analysis::rule_engine::{ReputationVerdict, RuleSet, TimelineEvent};
+use crate::analysis::canary::{CanaryPlan, DataClass, SANDBOX_INPUT_PREFIX};
+use crate::analysis::hash_intel::{self, KnownHash};
+use crate::store::{HashIntelStoreTrait, StoreError};
 
 use dast_ioc_types::IoCValue;
 
@@ -29,7 +36,12 @@ const KNOWN_SENSITIVE_DATA_KEYS: [&str; 5] = [
     "connect.sid"
 ];
 
-const DEFAULT_DOMAIN_REPUTATION: f32 = 15.0;
+// shorter cookie/storage values (flags, counters) are too common to be tracked
+const MIN_SENSITIVE_VALUE_LEN: usize = 8;
+
+// reputation of domains the reputation service knows nothing about.
+// These are scored offline by `domain_heuristics` instead
+const UNKNOWN_DOMAIN_REPUTATION: f32 = -1.0;
 
 // DynamicAnalysisIoC is supposed to be used in multiple different scanners
 // Currently, in our simple implementation, we use analyzer::Finding without
@@ -41,6 +53,15 @@ struct DynamicAnalysisIoC {
     title: String,
 }
 
+// a value planted by the sandbox or observed in the page (cookie/storage)
+// together with where it came from, used to detect exfiltration
+struct SensitiveValue {
+    value: String,
+    origin: DataClass,
+    // cookie name or storage key the value was read from
+    name: Option<String>
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct SpamHausResponse {
@@ -57,89 +78,371 @@ struct SpamHausResponse {
 #[derive(Clone)]
 pub struct DastAnalyzer {
     cached_domain_reputations: HashMap<String, f32>,
-    malsmug_dir: PathBuf,
-    tmp_dir: PathBuf
+    domain_intel: Arc<DomainIntel>,
+    rules: RuleSet,
+    // local database of known malware hashes, checked before VirusTotal
+    hash_intel: Box<dyn HashIntelStoreTrait>,
+    // shared by all analyzers so they respect a single request quota
+    virus_total: Option<Arc<VTClient>>,
+    // cookie names and storage keys holding credentials or session identifiers
+    sensitive_storage: SensitiveStorage,
+    malsmug_dir: PathBuf
     // file_hash_events:  Vec<dast_event_types::Event>,
     // file_hash_findings: Vec<Finding>,
 }
 
 impl DastAnalyzer {
-    pub fn new(h_dir: PathBuf) -> Self {
-        let _tmp_dir = h_dir.join(PathBuf::from("dynamic_analysis_tmp"));
-        match create_dir_all(&_tmp_dir) {
-            Ok(_) => (),
-            Err(_) => {
-                panic!("could not create home dir");
-            }
-        };
+    pub fn new(h_dir: PathBuf, domain_intel: Arc<DomainIntel>, rules: RuleSet, hash_intel: Box<dyn HashIntelStoreTrait>, virus_total: Option<Arc<VTClient>>,
+        sensitive_storage: SensitiveStorage) -> Self {
         DastAnalyzer { 
             cached_domain_reputations: HashMap::new(),
-            malsmug_dir: h_dir.clone(),
-            tmp_dir: _tmp_dir
+            domain_intel,
+            rules,
+            hash_intel,
+            virus_total,
+            sensitive_storage,
+            malsmug_dir: h_dir.clone()
         }
     }
 
-    // normalize url to make parsing easier
-    fn _normalize_url(&self, url: &str) -> String {
-        let mut url_normalized: String = url.to_string();
+    fn _is_bad_domain_reputation(&self, s: f32) -> bool {
+        return s <= 20.0 && s > 0.0
+    }
 
-        if url.starts_with("//") {
-            url_normalized = format!("https:{}", url);
+    // findings that come from the host itself rather than its reputation:
+    // ip literals, private ranges and domains that imitate the bait websites
+    // or one of the configured brands
+    fn _host_findings(&self, host: &HostInfo, ioc: &dast_ioc_types::IoC, bait_websites: &[String]) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = Vec::new();
+        match host.kind {
+            HostKind::ReservedIp => {
+                findings.push(
+                    analyzer::Finding {
+                        r#type: analyzer::AnalysisType::Dynamic,
+                        ioc: ioc.value.clone(),
+                        executed_on: ioc.executed_on.clone(),
+                        timestamp: ioc.timestamp,
+                        severity: analyzer::Severity::High,
+                        poc: host.host.clone(),
+                        title: "private or reserved ip range was contacted".to_string(),
+                        tags: Vec::new()
+                    });
+            },
+            HostKind::Ip => {
+                findings.push(
+                    analyzer::Finding {
+                        r#type: analyzer::AnalysisType::Dynamic,
+                        ioc: ioc.value.clone(),
+                        executed_on: ioc.executed_on.clone(),
+                        timestamp: ioc.timestamp,
+                        severity: analyzer::Severity::Moderate,
+                        poc: host.host.clone(),
+                        title: "ip address was contacted directly".to_string(),
+                        tags: Vec::new()
+                    });
+            },
+            HostKind::RegistrableDomain | HostKind::Idn => {
+                let mut targets: Vec<String> = bait_websites.to_vec();
+                targets.push(ioc.executed_on.clone());
+                if let Some(_i) = self.domain_intel.find_imitation(host, &targets) {
+                    findings.push(
+                        analyzer::Finding {
+                            r#type: analyzer::AnalysisType::Dynamic,
+                            ioc: ioc.value.clone(),
+                            executed_on: ioc.executed_on.clone(),
+                            timestamp: ioc.timestamp,
+                            severity: analyzer::Severity::VeryHigh,
+                            poc: _i.to_string(),
+                            title: format!("lookalike domain of {} was contacted", _i.imitated_domain),
+                            tags: Vec::new()
+                        });
+                }
+            }
         }
-        return url_normalized;
+        findings
     }
 
-    fn _is_bad_domain_reputation(self, s: f32) -> bool {
-        return s <= 20.0 && s > 0.0
+    // offline verdict for domains without reputation: generated looking
+    // names, abused tlds and free dynamic dns
+    fn _domain_heuristics_findings(&self, host: &HostInfo, ioc: &dast_ioc_types::IoC) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = Vec::new();
+        if host.is_ip() {
+            return findings;
+        }
+        let domain_score = domain_heuristics::score_host(host);
+        debug!("offline domain score: {}", domain_score);
+        if domain_score.is_suspicious() {
+            findings.push(
+                analyzer::Finding {
+                    r#type: analyzer::AnalysisType::Dynamic,
+                    ioc: ioc.value.clone(),
+                    executed_on: ioc.executed_on.clone(),
+                    timestamp: ioc.timestamp,
+                    severity: if domain_score.score >= 0.9 { analyzer::Severity::High } else { analyzer::Severity::Moderate },
+                    poc: domain_score.to_string(),
+                    title: "unknown domain looks generated or abusive".to_string(),
+                    tags: Vec::new()
+                });
+        }
+        findings
     }
 
-    // fetches given domain reputation score from spamhaus.com
-    async fn _get_domain_reputation(&mut self, url: &str) -> f32 {
-        let url_normalized: String = self._normalize_url(url);
+    // host checks shared by every event that contacts a url: the host itself,
+    // its reputation and, when no reputation is available, the offline heuristics.
+    // Returns the reputation verdict for the dynamic rules
+    async fn _network_findings(&mut self, url: &str, ioc: &dast_ioc_types::IoC, bait_websites: &[String], findings: &mut Vec<Finding>) -> Option<ReputationVerdict> {
+        let host = self.domain_intel.classify_url(url)?;
+        findings.append(&mut self._host_findings(&host, ioc, bait_websites));
 
-        info!("get domain reputation for: {}", url_normalized);
-        let domain = match Url::parse(&url_normalized) {
-            Ok(_u) => {
-                _u
-            },
-            Err(_e) => {
-                error!("could not parse url: {}", _e);
-                return -1.0;
+        let reputation = self._get_domain_reputation(&host).await;
+        if self._is_bad_domain_reputation(reputation) {
+            return Some(ReputationVerdict::Bad);
+        }
+        if reputation < 0.0 {
+            // analysis: no reputation available, score the domain offline
+            findings.append(&mut self._domain_heuristics_findings(&host, ioc));
+            return Some(ReputationVerdict::Unknown);
+        }
+        Some(ReputationVerdict::Good)
+    }
+
+    // c2 polling and keylogger flushes show up as evenly spaced requests.
+    // The bait website polling its own backend is left out
+    fn _beaconing_findings(&self, timeline: &[TimelineEvent]) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = Vec::new();
+        let requests: Vec<TimedRequest> = timeline
+            .iter()
+            .filter(|e| matches!(e.ioc.ioc_type, dast_ioc_types::IoCType::HttpRequest))
+            .filter_map(|e| e.host.as_ref().map(|h| TimedRequest {
+                executed_on: e.ioc.executed_on.clone(),
+                host: h.clone(),
+                timestamp: e.ioc.timestamp
+            }))
+            .collect();
+        for _b in beaconing::detect_beacons(&requests) {
+            let bait_domain = self.domain_intel.classify_url(&_b.executed_on).map(|h| h.registrable_domain);
+            let beacon_domain = self.domain_intel.classify_domain(&_b.host).map(|h| h.registrable_domain);
+            if bait_domain.is_some() && bait_domain == beacon_domain {
+                debug!("skipping first party beacon {}", _b);
+                continue;
             }
-        };
-        // load all public suffixes to achieve parsing arbitrary urls 
-        let mut public_suffixes = match File::open("./public_suffix.txt") {
-            Ok(_f) => _f,
-            Err(_e) => {
-                error!("{}", _e);
-                return -1.0;
+            let last_request = timeline
+                .iter()
+                .rev()
+                .find(|e| e.ioc.executed_on == _b.executed_on && e.host.as_ref() == Some(&_b.host));
+            if let Some(_e) = last_request {
+                findings.push(
+                    analyzer::Finding {
+                        r#type: analyzer::AnalysisType::Dynamic,
+                        ioc: _e.ioc.value.clone(),
+                        executed_on: _b.executed_on.clone(),
+                        timestamp: _e.ioc.timestamp,
+                        severity: analyzer::Severity::High,
+                        poc: _b.to_string(),
+                        title: "requests sent to the same host at regular intervals".to_string(),
+                        tags: Vec::new()
+                    });
             }
+        }
+        findings
+    }
+
+    // listeners able to capture what the user types, cited together with the
+    // request that later sent the typed canary values. The bait website
+    // registers listeners too, so only leaks leaving its domain are considered
+    fn _input_capture_findings(&self, timeline: &[TimelineEvent], leaks: &[Leak]) -> Vec<Finding> {
+        let third_party_leaks: Vec<Leak> = leaks
+            .iter()
+            .filter(|l| {
+                let bait_domain = self.domain_intel.classify_url(&l.request.executed_on).map(|h| h.registrable_domain);
+                let destination_domain = self.domain_intel.classify_domain(&l.destination).map(|h| h.registrable_domain);
+                destination_domain.is_some() && destination_domain != bait_domain
+            })
+            .cloned()
+            .collect();
+        let listeners: Vec<ListenerRegistration> = timeline
+            .iter()
+            .filter_map(|e| match &e.ioc.value {
+                IoCValue::IoCAddEventListener(_v) => Some(ListenerRegistration {
+                    executed_on: e.ioc.executed_on.clone(),
+                    timestamp: e.ioc.timestamp,
+                    listener: _v.listener.clone(),
+                    target: _v.target.clone()
+                }),
+                _ => None
+            })
+            .collect();
+        input_capture::detect_captures(&listeners, &third_party_leaks)
+            .into_iter()
+            .map(|_c| analyzer::Finding {
+                r#type: analyzer::AnalysisType::Dynamic,
+                ioc: _c.leak.request.value.clone(),
+                executed_on: _c.leak.request.executed_on.clone(),
+                timestamp: _c.leak.request.timestamp,
+                severity: analyzer::Severity::VeryHigh,
+                poc: _c.to_string(),
+                title: _c.kind.title().to_string(),
+                tags: Vec::new()
+            })
+            .collect()
+    }
+
+    // form actions rewritten at runtime to post the form to another domain
+    fn _form_action_finding(&self, ioc: &dast_ioc_types::IoC, arguments: &[String]) -> Option<Finding> {
+        let action = arguments.first()?;
+        let form = arguments.get(2).map(|f| f.as_str()).unwrap_or("form");
+        // relative actions stay on the bait website
+        let action_domain = self.domain_intel.classify_url(action)?.registrable_domain;
+        let bait_domain = self.domain_intel.classify_url(&ioc.executed_on).map(|h| h.registrable_domain);
+        if bait_domain.as_ref() == Some(&action_domain) {
+            return None;
+        }
+        Some(analyzer::Finding {
+            r#type: analyzer::AnalysisType::Dynamic,
+            ioc: ioc.value.clone(),
+            executed_on: ioc.executed_on.clone(),
+            timestamp: ioc.timestamp,
+            severity: analyzer::Severity::VeryHigh,
+            poc: format!("{} action changed from {:?} to {:?}", form,
+                arguments.get(1).map(|a| a.as_str()).unwrap_or(""), action),
+            title: "form action was rewritten to another domain".to_string(),
+            tags: Vec::new()
+        })
+    }
+
+    // elements of html written into the page (document.write, innerHTML).
+    // Elements loading, framing or posting to the bait website itself or to
+    // relative urls are its own first party content and are left out
+    fn _html_injection_findings(&self, ioc: &dast_ioc_types::IoC, call: &dast_ioc_types::IoCFunctionCall) -> Vec<Finding> {
+        let html = match call.arguments.first() {
+            Some(_h) => _h,
+            None => return Vec::new()
         };
-        let mut buf: String = String::new();
-        let _ = public_suffixes.read_to_string(&mut buf);
-        let list: List = match buf.parse() {
-            Ok(_l) => _l,
-            Err(_e) => {
-                error!("could not open public suffix file");
-                return -1.0;
+        let bait_domain = self.domain_intel.classify_url(&ioc.executed_on).map(|h| h.registrable_domain);
+        let mut findings: Vec<Finding> = Vec::new();
+        for _e in html_injection::injected_elements(html) {
+            if let Some(_url) = _e.url.as_ref() {
+                if html_injection::is_relative_url(_url) {
+                    continue;
+                }
+                let url_domain = self.domain_intel.classify_url(_url).map(|h| h.registrable_domain);
+                if url_domain.is_some() && url_domain == bait_domain {
+                    continue;
+                }
             }
-        };
-        // parse the given url's domain
-        let domain = match list.domain(domain.host_str().unwrap_or("").as_bytes()) {
-            Some(_d) => _d,
-            None => {
-                error!("could not parse domain: {}", &url_normalized);
-                return -1.0;
+            let poc = match call.arguments.get(1) {
+                Some(_target) => format!("{} into {}: {}", call.callee, _target, _e),
+                None => format!("{}: {}", call.callee, _e)
+            };
+            findings.push(
+                analyzer::Finding {
+                    r#type: analyzer::AnalysisType::Dynamic,
+                    ioc: ioc.value.clone(),
+                    executed_on: ioc.executed_on.clone(),
+                    timestamp: ioc.timestamp,
+                    severity: _e.severity(),
+                    poc,
+                    title: _e.title().to_string(),
+                    tags: Vec::new()
+                });
+        }
+        findings
+    }
+
+    async fn _find_known_hash(&self, data: &[u8]) -> Option<KnownHash> {
+        match self.hash_intel.find_known_hash(&hash_intel::digests(data)).await {
+            Ok(r) => Some(r),
+            Err(StoreError::NotFoundError) => None,
+            Err(e) => {
+                error!("could not look up the hash of the downloaded file: {:?}", e);
+                None
             }
+        }
+    }
+
+    fn _rule_hit_finding(&self, hit: event_rules::RuleHit, ioc: &dast_ioc_types::IoC) -> Finding {
+        Finding {
+            r#type: analyzer::AnalysisType::Dynamic,
+            ioc: ioc.value.clone(),
+            executed_on: ioc.executed_on.clone(),
+            timestamp: ioc.timestamp,
+            severity: hit.severity,
+            poc: hit.poc,
+            title: hit.title,
+            tags: Vec::new()
+        }
+    }
+
+    fn _collect_sensitive_values(&self, canary_plan: &CanaryPlan, iocs: &[dast_ioc_types::IoC]) -> Vec<SensitiveValue> {
+        let mut values: Vec<SensitiveValue> = match canary_plan.is_empty() {
+            // reports created before canary plans: the sandbox fills every input
+            // field with the same prefix, so only "user input" can be told
+            false => canary_plan
+                .tokens()
+                .into_iter()
+                .map(|(value, origin)| SensitiveValue { value, origin, name: None })
+                .collect(),
+            true => vec![SensitiveValue { value: SANDBOX_INPUT_PREFIX.to_string(), origin: DataClass::UserInput, name: None }]
         };
+        // cookies read or written and storage values written by the page, their
+        // values are traced in the outgoing requests
+        for _a in iocs.iter().flat_map(storage_access::storage_accesses) {
+            let value = _a.value.as_deref().unwrap_or("").trim();
+            if value.len() < MIN_SENSITIVE_VALUE_LEN {
+                continue;
+            }
+            match values.iter_mut().find(|v| v.value == value) {
+                // planted canaries get the name they were stored under
+                Some(_v) if _v.name.is_none() && _v.origin == _a.kind.data_class() => _v.name = Some(_a.name.clone()),
+                Some(_) => {},
+                None => values.push(SensitiveValue { value: value.to_string(), origin: _a.kind.data_class(), name: Some(_a.name.clone()) })
+            }
+        }
+        values
+    }
 
-        let domain_string = match str::from_utf8(domain.as_bytes()) {
-            Ok(_s) => _s,
-            Err(_e) => {
-                error!("could not parse domain: {}", &url_normalized);
-                return -1.0;
+    // cookies and storage entries read by the page whose name or value looks
+    // like a credential or a session identifier, and whose value a later
+    // request sent out. Pages read their own cookies all the time, and the
+    // entries planted by the sandbox are reported when they leak
+    fn _storage_access_findings(&self, timeline: &[TimelineEvent], leaks: &[Leak], canaries: &[String]) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = Vec::new();
+        for _e in timeline {
+            for _a in storage_access::storage_accesses(&_e.ioc) {
+                if _a.write || _a.is_planted(canaries) {
+                    continue;
+                }
+                let reason = match self.sensitive_storage.reason(&_a) {
+                    Some(_r) => _r,
+                    None => continue
+                };
+                if let Some(_l) = storage_access::leak_of_read(&_a, &_e.ioc, leaks) {
+                    findings.push(analyzer::Finding {
+                        r#type: analyzer::AnalysisType::Dynamic,
+                        ioc: _e.ioc.value.clone(),
+                        executed_on: _e.ioc.executed_on.clone(),
+                        timestamp: _e.ioc.timestamp,
+                        severity: analyzer::Severity::High,
+                        poc: format!("{} ({}) sent to {} by {}", _a.name, reason, _l.destination, _l.description),
+                        title: match _a.kind {
+                            StorageKind::Cookie => "sensitive cookie was read".to_string(),
+                            _ => format!("sensitive {} entry was read", _a.kind)
+                        },
+                        tags: Vec::new()
+                    });
+                }
             }
-        };
+        }
+        findings
+    }
+
+    // fetches given domain reputation score from spamhaus.com
+    async fn _get_domain_reputation(&mut self, host: &HostInfo) -> f32 {
+        // reputation services work on domains only
+        if host.is_ip() {
+            return -1.0;
+        }
+        let domain_string = host.registrable_domain.as_str();
 
         // get the domain reputation from the cache
         if self.cached_domain_reputations.contains_key(domain_string) {
@@ -176,12 +479,12 @@ impl DastAnalyzer {
                         _r
                     },
                     Err(_e) => {
-                        warn!("could not determine domain reputation, putting default value: {}", DEFAULT_DOMAIN_REPUTATION);
+                        warn!("could not determine domain reputation of {}, falling back to offline heuristics", domain_string);
                         self.cached_domain_reputations
                             .entry(domain_string.to_string())
-                            .and_modify(|e| { *e = DEFAULT_DOMAIN_REPUTATION })
-                            .or_insert(DEFAULT_DOMAIN_REPUTATION);
-                        return DEFAULT_DOMAIN_REPUTATION; // return a value for domains that are not found
+                            .and_modify(|e| { *e = UNKNOWN_DOMAIN_REPUTATION })
+                            .or_insert(UNKNOWN_DOMAIN_REPUTATION);
+                        return UNKNOWN_DOMAIN_REPUTATION; // return a value for domains that are not found
                     }
                 };
                 // self.cached_domain_reputations.entry(domain_string.to_string()).or_insert(_domain_resp.score);
@@ -202,7 +505,7 @@ impl DastAnalyzer {
 }
 
 impl<'a> analyzer::DastAnalyze<'a> for DastAnalyzer {
-    async fn analyze(&mut self, _: FileAnalysisReport, iocs: Vec<dast_ioc_types::IoC>) -> Result<Vec<Finding>, String> {
+    async fn analyze(&mut self, file_report: FileAnalysisReport, iocs: Vec<dast_ioc_types::IoC>) -> Result<Vec<Finding>, String> {
         // ---------------------------------------------------
         // dynamic analysis
         //
@@ -211,185 +514,236 @@ impl<'a> analyzer::DastAnalyze<'a> for DastAnalyzer {
         // we can send iocs in and out of the sandbox by running a server
         // on the host
         let mut findings: Vec<Finding> = Vec::new();
-        
-        for ioc in iocs {
-            // let inner_self = &mut self.clone();
+
+        // merge the events of the same http request (XMLHttpRequest open/send)
+        // and pair requests with their responses before analysing them
+        let correlated = correlation::correlate(iocs);
+        debug!("correlated {} http requests", correlated.requests.len());
+
+        // values that must never leave the page, searched in every outgoing request
+        let sensitive_values = self._collect_sensitive_values(&file_report.canary_plan, &correlated.iocs);
+        let leak_needles: Vec<String> = sensitive_values.iter().map(|s| s.value.clone()).collect();
+
+        // pick up rules shipped since the last analysis
+        self.rules.reload_if_changed();
+
+        // every event with the context computed below, for the sequence rules
+        let mut timeline: Vec<TimelineEvent> = Vec::new();
+        let mut contacted_domains: HashSet<(String, String)> = HashSet::new();
+        // (bait website, name) of the cookies read or set so far
+        let mut seen_cookies: HashSet<(String, String)> = HashSet::new();
+        // requests that carried planted values, for the keylogger detection
+        let mut leaks: Vec<Leak> = Vec::new();
+
+        for ioc in correlated.iocs {
+            // reputation of the host the event talks to, used by the dynamic rules
+            let mut reputation: Option<ReputationVerdict> = None;
+            let mut contacted_url: Option<String> = None;
+            let known_hash = match &ioc.value {
+                IoCValue::IoCSuspiciousFileDownload(_v) => self._find_known_hash(&_v.data).await,
+                _ => None
+            };
             match ioc.clone().value {
                 IoCValue::IoCHttpRequest(_v) => {
-                    // analysis: check response url domain reputation
-                    if self.clone()._is_bad_domain_reputation(self._get_domain_reputation(_v.url.as_str()).await) {
-                        findings.push(
-                                analyzer::Finding {
-                                    r#type: analyzer::AnalysisType::Dynamic,
-                                    ioc: IoCValue::IoCHttpRequest(_v.clone()),
-                                    executed_on: ioc.executed_on.clone(),
-                                    severity: analyzer::Severity::High,
-                                    poc: _v.clone().url,
-                                    title: "bad reputation url called".to_string()
-                                });
-                    }
+                    reputation = self._network_findings(&_v.url, &ioc, &file_report.bait_websites, &mut findings).await;
+                    contacted_url = Some(_v.url.clone());
 
-                    // analysis: check for user input sent in request
-                    // "fake_input_from_sandbox_" is the default input prefix that the sandbox puts inside the input fields 
-                    if _v.data.contains("fake_input_from_sandbox_") {
+                    // analysis: check for user input, cookie or storage values sent in request.
+                    // The url (query string) and the body are decoded first as exfiltrated data
+                    // is usually encoded (base64, hex, url, xor ...). The bait website sending its
+                    // own cookies and form data back to its servers (or relative urls) is not exfiltration
+                    let payload = format!("{}\n{}", _v.url, _v.data);
+                    let bait_domain = self.domain_intel.classify_url(&ioc.executed_on).map(|h| h.registrable_domain);
+                    let (destination, payload_matches) = match self.domain_intel.classify_url(_v.url.as_str()) {
+                        Some(_host) if bait_domain.as_ref() != Some(&_host.registrable_domain) =>
+                            (_host.host, payload_decoder::find_needles(&payload, &leak_needles)),
+                        _ => (ioc.executed_on.clone(), Vec::new())
+                    };
+                    let mut origins: Vec<DataClass> = Vec::new();
+                    let mut sources: Vec<(DataClass, String)> = Vec::new();
+                    for _m in payload_matches {
+                        let (origin, name) = match sensitive_values.iter().find(|s| s.value == _m.needle) {
+                            Some(_s) => (_s.origin, _s.name.as_ref()),
+                            None => continue
+                        };
+                        let found = match name {
+                            Some(_n) => format!("{} {:?} value {:?}", origin, _n, _m.needle),
+                            None => format!("{} {:?}", origin, _m.needle)
+                        };
+                        if !origins.contains(&origin) {
+                            origins.push(origin);
+                        }
+                        if let Some(_n) = name {
+                            sources.push((origin, _n.clone()));
+                        }
                         findings.push(
                             analyzer::Finding {
                                 r#type: analyzer::AnalysisType::Dynamic,
                                 ioc: IoCValue::IoCHttpRequest(_v.clone()),
-                                executed_on: ioc.executed_on.clone(), 
+                                executed_on: ioc.executed_on.clone(),
+                                timestamp: ioc.timestamp,
                                 severity: analyzer::Severity::VeryHigh,
-                                poc: _v.data,
-                                title: "http request sent containing user input data".to_string()
+                                poc: format!("{} {} {} (found {} sent to {} decoded via: {})",
+                                    _v.method, _v.url, _v.data, found, destination, _m.chain_description()),
+                                title: format!("http request sent containing {} data", origin),
+                                tags: Vec::new()
                             });
                     }
+                    if !origins.is_empty() {
+                        leaks.push(Leak {
+                            request: ioc.clone(),
+                            description: format!("{} {}", _v.method, _v.url),
+                            destination,
+                            origins,
+                            sources
+                        });
+                    }
                 },
                 IoCValue::IoCHttpResponse(_v) => {
-                    // analysis: check response url domain reputation
-                    if self.clone()._is_bad_domain_reputation(self._get_domain_reputation(_v.url.as_str()).await) {
+                    reputation = self._network_findings(&_v.url, &ioc, &file_report.bait_websites, &mut findings).await;
+                    contacted_url = Some(_v.url.clone());
+                },
+                IoCValue::IoCNewNetworkHtmlElement(_v) => {
+                    reputation = self._network_findings(&_v.src, &ioc, &file_report.bait_websites, &mut findings).await;
+                    contacted_url = Some(_v.src.clone());
+                },
+                // analysis: payload of known malware, nothing is sent to VirusTotal
+                IoCValue::IoCSuspiciousFileDownload(_v) if known_hash.is_some() => {
+                    if let Some(_k) = known_hash.as_ref() {
                         findings.push(
                             analyzer::Finding {
                                 r#type: analyzer::AnalysisType::Dynamic,
-                                ioc: IoCValue::IoCHttpResponse(_v.clone()),
-                                executed_on: ioc.executed_on.clone(), 
-                                severity: analyzer::Severity::High,
-                                poc: _v.url,
-                                title: "bad reputation url called".to_string()
+                                ioc: IoCValue::IoCSuspiciousFileDownload(_v.clone()),
+                                executed_on: ioc.executed_on.clone(),
+                                timestamp: ioc.timestamp,
+                                severity: analyzer::Severity::VeryHigh,
+                                poc: format!("{} {} {} is {} (source: {})", _v.url, _k.hash_type, _k.hash, _k.family, _k.source),
+                                title: "known malware file was downloaded".to_string(),
+                                tags: Vec::new()
                             });
                     }
                 },
+                // analysis: engines of VirusTotal flagging the file. Only the hash is
+                // sent unless the upload policy allows unknown files to be uploaded
                 IoCValue::IoCSuspiciousFileDownload(_v) => {
-                    match utils::get_env_var("VIRUS_TOTAL_API_KEY") {
-                        Some(vt_api_key) => {
-                            debug!("VT api key: {:?}", vt_api_key);
-                            let file_sha256 = sha256::digest(&_v.clone().data).to_string();
-                            let tmp_file_path = self.tmp_dir.join(PathBuf::from(&file_sha256));
-                            match fs::write(&tmp_file_path, _v.clone().data) {
-                                Ok(_) => {
-                                    let inner_value = vt_api_key.clone();
-                                    let _res = tokio::task::spawn_blocking(move || {
-                                        let vtclient_inner: VTClient = VTClient::new(&inner_value);
-                                        vtclient_inner.submit_file(tmp_file_path.as_os_str().to_str().unwrap())
-                                    }).await;
-                                    let vtclient: VTClient = VTClient::new(&vt_api_key);
-                                    match vtclient.get_file_report(&file_sha256).await {
-                                        Ok(r) => {
-                                            info!("file: {:?}, VT score: {:?}", file_sha256, r);
-                                            if r > 6 {
-                                                findings.push(
-                                                    analyzer::Finding {
-                                                        r#type: analyzer::AnalysisType::Dynamic,
-                                                        ioc: IoCValue::IoCSuspiciousFileDownload(_v.clone()),
-                                                        executed_on: ioc.executed_on.clone(), 
-                                                        severity: analyzer::Severity::High,
-                                                        poc: _v.url,
-                                                        title: "malicious file was downloaded".to_string()
-                                                    }
-                                                );
+                    match self.virus_total.as_ref() {
+                        Some(_vt) => {
+                            let file_sha256 = sha256::digest(&_v.data).to_string();
+                            match _vt.scan(&file_sha256, &_v.data).await {
+                                Ok(Some(_r)) => {
+                                    info!("file: {:?}, VT detections: {}", file_sha256, _r.summary());
+                                    if _r.stats.malicious >= _vt.min_detections() {
+                                        findings.push(
+                                            analyzer::Finding {
+                                                r#type: analyzer::AnalysisType::Dynamic,
+                                                ioc: IoCValue::IoCSuspiciousFileDownload(_v.clone()),
+                                                executed_on: ioc.executed_on.clone(),
+                                                timestamp: ioc.timestamp,
+                                                severity: analyzer::Severity::High,
+                                                poc: format!("{} {} ({})", _v.url, file_sha256, _r.summary()),
+                                                title: "malicious file was downloaded".to_string(),
+                                                tags: Vec::new()
                                             }
-                                        },
-                                        Err(e) =>{
-                                            error!("error analysing the file: {:?}", e);
-                                        }
+                                        );
                                     }
                                 },
-                                Err(_) => {
-                                    error!("Could not create tmp file for: {:?}", file_sha256)
+                                Ok(None) => debug!("no VirusTotal report for: {:?}", file_sha256),
+                                Err(e) => {
+                                    error!("error analysing the file: {:?}", e);
                                 }
                             }
                         },
                         None => {
-                            warn!("could not get VIRUS_TOTAL_API_KEY")
+                            warn!("VirusTotal is not configured, skipping download of {}", _v.url)
                         }
                     }
-                }
-                IoCValue::IoCNewNetworkHtmlElement(_v) => {
-                    if self.clone()._is_bad_domain_reputation(self._get_domain_reputation(&_v.src.as_str()).await) {
-                        findings.push(
-                            analyzer::Finding {
-                                r#type: analyzer::AnalysisType::Dynamic,
-                                ioc: IoCValue::IoCNewNetworkHtmlElement(_v.clone()),
-                                executed_on: ioc.executed_on.clone(), 
-                                severity: analyzer::Severity::VeryHigh,
-                                poc: _v.element_type,
-                                title: "dangerous html element was created with low reputation src".to_string()
-                            });
+                },
+                // cookie reads are checked once every request is known, and by the dynamic rules
+                IoCValue::IoCGetCookie(_v) => {
+                    for (_name, _) in storage_access::parse_cookie_pairs(&_v.cookie) {
+                        seen_cookies.insert((ioc.executed_on.clone(), _name));
                     }
-                    // }
                 },
-                IoCValue::IoCFunctionCall(_v) => {
-                    // analysis: check document.write call with the first argument being an html-like element
-                    if matches!(_v.callee.as_str(), "document.write") && _v.arguments.len() > 0 {
-                        if utils::contains_html_like_code(_v.arguments[0].as_str()) {
-                            findings.push(
-                                analyzer::Finding {
-                                    r#type: analyzer::AnalysisType::Dynamic,
-                                    ioc: IoCValue::IoCFunctionCall(_v.clone()),
-                                    executed_on: ioc.executed_on.clone(), 
-                                    severity: analyzer::Severity::VeryHigh,
-                                    poc: _v.callee,
-                                    title: "document.write was called with html element as parameter".to_string()
-                                });
-                        }
-                    } else if matches!(_v.callee.as_str(), "window.eval") {
-                        // analysis: check window.eval call
-                        // here we could also check whether the eval paramater is actually a malicious Javascript code
-                        // for now we check just the dangerous call to `.eval`
-                        findings.push(
-                                analyzer::Finding {
-                                    r#type: analyzer::AnalysisType::Dynamic,
-                                    ioc: IoCValue::IoCFunctionCall(_v.clone()),
-                                    executed_on: ioc.executed_on.clone(), 
-                                severity: analyzer::Severity::VeryHigh,
-                                poc: _v.callee,
-                                title: "window.eval was called".to_string()
-                            });
-                    } else if matches!(_v.callee.as_str(), "window.execScript") {
-                        // analysis: check window.execScript call
-                        findings.push(
-                            analyzer::Finding {
-                                r#type: analyzer::AnalysisType::Dynamic,
-                                ioc: IoCValue::IoCFunctionCall(_v.clone()),
-                                executed_on: ioc.executed_on.clone(), 
-                                severity: analyzer::Severity::VeryHigh,
-                                poc: _v.callee,
-                                title: "window.execScript was called".to_string()
-                            });
-                    } else if matches!(_v.callee.as_str(), "window.localStorage.getItem")  && _v.arguments.len() > 0 {
-                        // analysis: check whether the target tries to access sinsitive data keys
-                        if KNOWN_SENSITIVE_DATA_KEYS.contains(&_v.arguments[0].as_str()) {
-                            findings.push(
-                                analyzer::Finding {
-                                    r#type: analyzer::AnalysisType::Dynamic,
-                                    ioc: IoCValue::IoCFunctionCall(_v.clone()),
-                                    executed_on: ioc.executed_on.clone(), 
-                                    severity: analyzer::Severity::VeryHigh,
-                                    poc: format!("{}({})", _v.callee, &_v.arguments[0].as_str()),
-                                    title: "window.localStorage tried to access sensitive information".to_string()
-                                });
-                        }
+                IoCValue::IoCSetCookie(_v) => {
+                    // analysis: session identifiers overwritten and long lived tracking cookies
+                    let set_on = (ioc.timestamp / 1000) as i64;
+                    match event_rules::parse_set_cookie(&_v.cookie, set_on) {
+                        Some(_c) => {
+                            let overwrites = !seen_cookies.insert((ioc.executed_on.clone(), _c.name.clone()));
+                            for _h in event_rules::set_cookie_rules(&_c, &KNOWN_SENSITIVE_DATA_KEYS, overwrites) {
+                                findings.push(self._rule_hit_finding(_h, &ioc));
+                            }
+                        },
+                        None => debug!("could not parse cookie: {}", _v.cookie)
                     }
                 },
-                IoCValue::IoCGetCookie(_v) => {
-                    if KNOWN_SENSITIVE_DATA_KEYS.contains(&_v.cookie.as_str()) {
-                            findings.push(
-                                analyzer::Finding {
-                                    r#type: analyzer::AnalysisType::Dynamic,
-                                    ioc: IoCValue::IoCGetCookie(_v),
-                                    executed_on: ioc.executed_on.clone(), 
-                                    severity: analyzer::Severity::VeryHigh,
-                                    poc: "document.cookie".to_string(),
-                                    title: "document.cookie tried to access sensitive data key".to_string()
-                                });
+                IoCValue::IoCConsoleLog(_v) => {
+                    // analysis: decoded payloads, stolen data and debug strings printed by the sample
+                    for _h in event_rules::console_rules(&_v.text, &leak_needles) {
+                        findings.push(self._rule_hit_finding(_h, &ioc));
                     }
                 },
+                // listeners are checked against the leaks once every request is known
                 IoCValue::IoCAddEventListener(_v) => {
-                    debug!("added event_listener: {}", _v.listener);
-                }
+                    debug!("added event_listener: {} on {}", _v.listener, _v.target);
+                },
+                IoCValue::IoCFunctionCall(_v) if _v.callee == "HTMLFormElement.action" => {
+                    // analysis: form posted to a domain other than the bait website
+                    if let Some(_f) = self._form_action_finding(&ioc, &_v.arguments) {
+                        findings.push(_f);
+                    }
+                },
+                // analysis: code decoded by the sample and handed to eval/document.write
+                // is analysed statically as well, the rest of the calls are only checked by the dynamic rules
+                IoCValue::IoCFunctionCall(_v) => {
+                    // analysis: scripts, frames, forms, redirects and password inputs written into the page
+                    if html_injection::HTML_WRITING_CALLEES.contains(&_v.callee.as_str()) {
+                        findings.append(&mut self._html_injection_findings(&ioc, &_v));
+                    }
+                    findings.append(&mut runtime_decoded::runtime_decoded_findings(&file_report, &ioc, &_v));
+                },
                 _ => {
                     warn!("event of type {} was not handled", ioc.ioc_type)
                 }
             }
+
+            // analysis: declarative rules from the rules file
+            findings.append(&mut self.rules.evaluate(&ioc, reputation.as_ref()));
+
+            // a domain other than the bait website contacted, and for the first time
+            let host = contacted_url.and_then(|u| self.domain_intel.classify_url(&u));
+            let (third_party, new_domain) = match &host {
+                Some(_h) => {
+                    let bait_domain = self.domain_intel
+                        .classify_url(&ioc.executed_on)
+                        .map(|b| b.registrable_domain)
+                        .unwrap_or_default();
+                    let third_party = _h.registrable_domain != bait_domain;
+                    (third_party, contacted_domains.insert((ioc.executed_on.clone(), _h.registrable_domain.clone())) && third_party)
+                },
+                None => (false, false)
+            };
+            timeline.push(TimelineEvent {
+                ioc,
+                reputation,
+                host: host.map(|h| h.host),
+                new_domain,
+                third_party
+            });
         }
+
+        // analysis: ordered behaviour sequences over the timeline
+        findings.append(&mut self.rules.evaluate_sequences(&timeline));
+
+        // analysis: requests sent to the same host at regular intervals
+        findings.append(&mut self._beaconing_findings(&timeline));
+
+        // analysis: keyloggers and hijacked form submissions
+        findings.append(&mut self._input_capture_findings(&timeline, &leaks));
+
+        // analysis: credentials and session identifiers read from cookies and web storage and sent out
+        let canaries: Vec<String> = file_report.canary_plan.tokens().into_iter().map(|(t, _)| t).collect();
+        findings.append(&mut self._storage_access_findings(&timeline, &leaks, &canaries));
         // end of analysis
         // ---------------------------------------------------
         Ok(findings.clone())
@@ -1,5 +1,13 @@
-use serde::{Serialize, Deserialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use core::fmt;
+use std::collections::HashMap;
+
+// version of the event batches the sandbox publishes. Version 1 batches have
+// no version field, their events have the same shape
+pub const IOC_SCHEMA_VERSION: u32 = 2;
+pub const LEGACY_IOC_SCHEMA_VERSION: u32 = 1;
+// raw json kept of an invalid event
+const MAX_INVALID_EVENT_LEN: usize = 4096;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "snake_case")]
@@ -36,31 +44,46 @@ impl fmt::Display for IoCType {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCHttpRequest {
     pub method: String,
     pub url: String,
-    pub data: String
+    pub data: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    // set by the sandbox on every event of the same XMLHttpRequest
+    #[serde(default)]
+    pub request_id: String,
+    // url of the script that sent the request, empty for the sample itself
+    #[serde(default)]
+    pub initiator: String
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCHttpResponse {
     pub status: String,
     pub url: String,
-    pub data: String
+    pub data: String,
+    #[serde(default)]
+    pub content_type: String
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCConsoleLog {
     pub text: String
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCFunctionCall {
     pub callee: String,
     pub arguments: Vec<String>
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCNewNetworkHtmlElement {
     #[serde(rename = "elementType")]
     pub element_type: String,
@@ -68,16 +91,19 @@ pub struct IoCNewNetworkHtmlElement {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCSetCookie {
     pub cookie: String
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCGetCookie {
     pub cookie: String
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCSuspiciousFileDownload {
     pub url: String,
     pub extension: String,
@@ -97,10 +123,16 @@ pub struct IoCSuspiciousFileDownload {
 // }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct IoCAddEventListener {
-    pub listener: String
+    pub listener: String,
+    // object the listener was attached to, e.g. "document" or "input#card"
+    #[serde(default)]
+    pub target: String
 }
 
+// untagged on its own, as findings keep the value without its type. Events
+// are deserialised according to their type, see `IoC`
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum IoCValue {
@@ -118,7 +150,43 @@ pub enum IoCValue {
     None
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl IoCValue {
+    // the value of an event of the given type, get and set cookie values have
+    // the same shape so only the type tells them apart
+    pub fn from_typed(ioc_type: &IoCType, value: serde_json::Value) -> Result<IoCValue, serde_json::Error> {
+        let ioc_value = match ioc_type {
+            IoCType::HttpRequest => IoCValue::IoCHttpRequest(serde_json::from_value(value)?),
+            IoCType::HttpResponse => IoCValue::IoCHttpResponse(serde_json::from_value(value)?),
+            IoCType::FunctionCall => IoCValue::IoCFunctionCall(serde_json::from_value(value)?),
+            IoCType::NewNetworkHtmlElement => IoCValue::IoCNewNetworkHtmlElement(serde_json::from_value(value)?),
+            IoCType::SetCookie => IoCValue::IoCSetCookie(serde_json::from_value(value)?),
+            IoCType::GetCookie => IoCValue::IoCGetCookie(serde_json::from_value(value)?),
+            IoCType::ConsoleLog => IoCValue::IoCConsoleLog(serde_json::from_value(value)?),
+            IoCType::AddEventListener => IoCValue::IoCAddEventListener(serde_json::from_value(value)?),
+            IoCType::SuspiciousFileDownload => IoCValue::IoCSuspiciousFileDownload(serde_json::from_value(value)?)
+        };
+        Ok(ioc_value)
+    }
+
+    // short human readable description of the event, used to cite it in findings
+    pub fn summary(&self) -> String {
+        let text: String = match self {
+            IoCValue::IoCHttpRequest(_v) => format!("{} {}", _v.method, _v.url),
+            IoCValue::IoCHttpResponse(_v) => format!("{} {}", _v.status, _v.url),
+            IoCValue::IoCFunctionCall(_v) => format!("{}({})", _v.callee, _v.arguments.join(", ")),
+            IoCValue::IoCNewNetworkHtmlElement(_v) => format!("<{} src={}>", _v.element_type, _v.src),
+            IoCValue::IoCSetCookie(_v) => _v.cookie.clone(),
+            IoCValue::IoCGetCookie(_v) => _v.cookie.clone(),
+            IoCValue::IoCConsoleLog(_v) => _v.text.clone(),
+            IoCValue::IoCAddEventListener(_v) => format!("{} on {}", _v.listener, _v.target),
+            IoCValue::IoCSuspiciousFileDownload(_v) => format!("{} ({})", _v.url, _v.extension),
+            IoCValue::None => String::new()
+        };
+        text.chars().take(120).collect()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct IoC {
     #[serde(rename = "type")]
     pub ioc_type: IoCType,
@@ -126,3 +194,103 @@ pub struct IoC {
     pub timestamp: u64,
     pub value: IoCValue,
 }
+
+#[derive(Deserialize)]
+struct TypedIoC {
+    #[serde(rename = "type")]
+    ioc_type: IoCType,
+    executed_on: String,
+    timestamp: u64,
+    value: serde_json::Value
+}
+
+impl<'de> Deserialize<'de> for IoC {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let typed = TypedIoC::deserialize(deserializer)?;
+        let value = IoCValue::from_typed(&typed.ioc_type, typed.value)
+            .map_err(|e| serde::de::Error::custom(format!("invalid {} value: {}", typed.ioc_type, e)))?;
+        Ok(IoC {
+            ioc_type: typed.ioc_type,
+            executed_on: typed.executed_on,
+            timestamp: typed.timestamp,
+            value
+        })
+    }
+}
+
+// an event of a batch that did not validate
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InvalidEvent {
+    // position in the batch
+    pub index: usize,
+    // the type field as sent, empty when missing
+    pub ioc_type: String,
+    pub error: String,
+    // raw json, truncated
+    pub event: String
+}
+
+// validates the events of a batch one by one, the invalid ones are returned
+// apart instead of failing the batch. Batches of unknown schema versions are
+// rejected whole, their fields may not mean what this version expects
+pub fn parse_events(schema_version: u32, raw_events: Vec<serde_json::Value>) -> (Vec<IoC>, Vec<InvalidEvent>) {
+    let mut iocs: Vec<IoC> = Vec::new();
+    let mut invalid: Vec<InvalidEvent> = Vec::new();
+    let supported = (LEGACY_IOC_SCHEMA_VERSION..=IOC_SCHEMA_VERSION).contains(&schema_version);
+    for (index, _e) in raw_events.into_iter().enumerate() {
+        let parsed = match supported {
+            true => serde_json::from_value::<IoC>(_e.clone()).map_err(|e| e.to_string()),
+            false => Err(format!("unsupported schema version {}", schema_version))
+        };
+        match parsed {
+            Ok(_i) => iocs.push(_i),
+            Err(_err) => invalid.push(InvalidEvent {
+                index,
+                ioc_type: _e.get("type").and_then(|t| t.as_str()).unwrap_or("").to_string(),
+                error: _err,
+                event: _e.to_string().chars().take(MAX_INVALID_EVENT_LEN).collect()
+            })
+        }
+    }
+    (iocs, invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_events_are_parsed_by_type() {
+        let (iocs, invalid) = parse_events(IOC_SCHEMA_VERSION, vec![
+            json!({"type": "get_cookie", "executed_on": "https://bait.com", "timestamp": 1, "value": {"cookie": "sid=1"}}),
+            json!({"type": "set_cookie", "executed_on": "https://bait.com", "timestamp": 2, "value": {"cookie": "sid=2"}}),
+            // typo in a field name
+            json!({"type": "function_call", "executed_on": "https://bait.com", "timestamp": 3, "value": {"calee": "window.eval", "arguments": []}}),
+            json!({"type": "set_timeout", "executed_on": "https://bait.com", "timestamp": 4, "value": {"delay": 10}}),
+            json!({"type": "http_request", "executed_on": "https://bait.com", "timestamp": 5,
+                "value": {"method": "GET", "url": "https://evil.com", "data": "", "initator": "https://evil.com/s.js"}})
+        ]);
+        assert_eq!(iocs.len(), 2);
+        assert!(matches!(iocs[0].value, IoCValue::IoCGetCookie(_)));
+        assert!(matches!(iocs[1].value, IoCValue::IoCSetCookie(_)));
+        let indexes: Vec<usize> = invalid.iter().map(|i| i.index).collect();
+        assert_eq!(indexes, vec![2, 3, 4]);
+        assert_eq!(invalid[0].ioc_type, "function_call");
+        assert!(invalid[0].error.contains("calee"));
+        assert!(invalid[2].error.contains("initator"));
+
+        // stored events are read back with their type
+        let stored: Vec<IoC> = serde_json::from_str(&serde_json::to_string(&iocs).unwrap()).unwrap();
+        assert!(matches!(stored[0].value, IoCValue::IoCGetCookie(_)));
+
+        let (iocs, invalid) = parse_events(IOC_SCHEMA_VERSION + 1, vec![
+            json!({"type": "get_cookie", "executed_on": "https://bait.com", "timestamp": 1, "value": {"cookie": "sid=1"}})
+        ]);
+        assert!(iocs.is_empty());
+        assert_eq!(invalid[0].error, format!("unsupported schema version {}", IOC_SCHEMA_VERSION + 1));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::domain_intel::HostInfo;
+
+// the most frequent english bigrams. Human chosen domain names are
+// mostly made of these, algorithmically generated ones are not
+const COMMON_BIGRAMS: [&str; 100] = [
+    "th", "he", "in", "er", "an", "re", "on", "at", "en", "nd",
+    "ti", "es", "or", "te", "of", "ed", "is", "it", "al", "ar",
+    "st", "to", "nt", "ng", "se", "ha", "as", "ou", "io", "le",
+    "ve", "co", "me", "de", "hi", "ri", "ro", "ic", "ne", "ea",
+    "ra", "ce", "li", "ch", "ll", "be", "ma", "si", "om", "ur",
+    "ca", "el", "ta", "la", "ns", "di", "fo", "ho", "pe", "ec",
+    "pr", "no", "ct", "us", "ac", "ot", "il", "tr", "ly", "nc",
+    "et", "ut", "ss", "so", "rs", "un", "lo", "wa", "ge", "ie",
+    "wh", "ee", "wi", "em", "ad", "ol", "rt", "po", "we", "na",
+    "ul", "ni", "ts", "mo", "ow", "pa", "im", "mi", "ai", "sh",
+];
+
+// tlds that are free or very cheap and heavily abused
+const SUSPICIOUS_TLDS: [&str; 24] = [
+    "tk", "ml", "ga", "cf", "gq", "xyz", "top", "buzz", "icu", "cyou",
+    "rest", "zip", "mov", "click", "link", "work", "country", "kim", "loan",
+    "gdn", "bid", "win", "monster", "sbs",
+];
+
+// free dynamic dns providers and tunneling services
+const DYNAMIC_DNS_SUFFIXES: [&str; 20] = [
+    "duckdns.org", "no-ip.org", "no-ip.biz", "ddns.net", "hopto.org",
+    "zapto.org", "sytes.net", "servebeer.com", "serveftp.com", "myftp.org",
+    "dynu.net", "dyndns.org", "afraid.org", "changeip.com", "ngrok.io",
+    "ngrok-free.app", "serveo.net", "trycloudflare.com", "loca.lt", "duckdns.com",
+];
+
+const HIGH_ENTROPY: f32 = 3.5;
+const MIN_LABEL_LEN_FOR_ENTROPY: usize = 8;
+const LOW_BIGRAM_LIKELIHOOD: f32 = 0.35;
+const MIN_LABEL_LEN_FOR_BIGRAMS: usize = 6;
+const HIGH_DIGIT_RATIO: f32 = 0.3;
+const LONG_LABEL_LEN: usize = 20;
+const LONG_CONSONANT_RUN: usize = 5;
+
+// a domain whose score reaches this value is reported as suspicious
+pub const SUSPICIOUS_DOMAIN_SCORE: f32 = 0.6;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DomainScore {
+    pub domain: String,
+    // 0.0 (looks normal) to 1.0 (almost certainly generated/abusive)
+    pub score: f32,
+    pub entropy: f32,
+    pub bigram_likelihood: f32,
+    pub digit_ratio: f32,
+    pub reasons: Vec<String>
+}
+
+impl DomainScore {
+    pub fn is_suspicious(&self) -> bool {
+        self.score >= SUSPICIOUS_DOMAIN_SCORE
+    }
+}
+
+impl fmt::Display for DomainScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} score={:.2} ({})", self.domain, self.score, self.reasons.join(", "))
+    }
+}
+
+// offline scoring of a contacted domain. It does not need any external
+// service so it gives a verdict even for domains registered a few hours ago
+pub fn score_host(host: &HostInfo) -> DomainScore {
+    let label = host.registrable_label().to_lowercase();
+    // the interesting part of a generated domain is the registrable label,
+    // but for dynamic dns the user controlled part is the subdomain
+    let dynamic_dns_suffix = DYNAMIC_DNS_SUFFIXES
+        .iter()
+        .find(|s| host.host == **s || host.host.ends_with(&format!(".{}", s)));
+    let scored_label = match dynamic_dns_suffix {
+        Some(_s) => host.host
+            .trim_end_matches(*_s)
+            .trim_end_matches('.')
+            .rsplit('.')
+            .next()
+            .unwrap_or("")
+            .to_string(),
+        None => label
+    };
+
+    let mut score: f32 = 0.0;
+    let mut reasons: Vec<String> = Vec::new();
+    let label_len = scored_label.chars().count();
+
+    let entropy = shannon_entropy(&scored_label);
+    if label_len >= MIN_LABEL_LEN_FOR_ENTROPY && entropy >= HIGH_ENTROPY {
+        score += 0.3;
+        reasons.push(format!("high entropy {:.2}", entropy));
+    }
+
+    let bigram_likelihood = bigram_likelihood(&scored_label);
+    if label_len >= MIN_LABEL_LEN_FOR_BIGRAMS && bigram_likelihood < LOW_BIGRAM_LIKELIHOOD {
+        score += 0.3;
+        reasons.push(format!("unpronounceable ({:.2} common bigrams)", bigram_likelihood));
+    }
+
+    let digit_ratio = digit_ratio(&scored_label);
+    if digit_ratio >= HIGH_DIGIT_RATIO {
+        score += 0.2;
+        reasons.push(format!("digit ratio {:.2}", digit_ratio));
+    }
+
+    if label_len >= LONG_LABEL_LEN {
+        score += 0.15;
+        reasons.push(format!("long label ({} chars)", label_len));
+    }
+
+    if longest_consonant_run(&scored_label) >= LONG_CONSONANT_RUN {
+        score += 0.15;
+        reasons.push("long consonant sequence".to_string());
+    }
+
+    let tld = host.suffix.rsplit('.').next().unwrap_or("");
+    if SUSPICIOUS_TLDS.contains(&tld) {
+        score += 0.25;
+        reasons.push(format!("suspicious tld .{}", tld));
+    }
+
+    if let Some(_s) = dynamic_dns_suffix {
+        score += 0.4;
+        reasons.push(format!("free dynamic dns {}", _s));
+    }
+
+    DomainScore {
+        domain: host.host.clone(),
+        score: score.min(1.0),
+        entropy,
+        bigram_likelihood,
+        digit_ratio,
+        reasons
+    }
+}
+
+fn shannon_entropy(label: &str) -> f32 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut total: usize = 0;
+    for c in label.chars().filter(|c| *c != '-') {
+        *counts.entry(c).or_insert(0) += 1;
+        total += 1;
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    counts.values().fold(0.0, |acc, n| {
+        let p = *n as f32 / total as f32;
+        acc - p * p.log2()
+    })
+}
+
+// share of the label's letter bigrams that are common in natural language
+fn bigram_likelihood(label: &str) -> f32 {
+    let mut total: usize = 0;
+    let mut common: usize = 0;
+    for part in label.split(|c: char| !c.is_ascii_alphabetic()) {
+        let chars: Vec<char> = part.chars().collect();
+        for w in chars.windows(2) {
+            total += 1;
+            let bigram: String = w.iter().collect();
+            if COMMON_BIGRAMS.contains(&bigram.as_str()) {
+                common += 1;
+            }
+        }
+    }
+    if total == 0 {
+        return 1.0;
+    }
+    common as f32 / total as f32
+}
+
+fn digit_ratio(label: &str) -> f32 {
+    let total = label.chars().filter(|c| *c != '-').count();
+    if total == 0 {
+        return 0.0;
+    }
+    label.chars().filter(|c| c.is_ascii_digit()).count() as f32 / total as f32
+}
+
+fn longest_consonant_run(label: &str) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in label.chars() {
+        if c.is_ascii_alphabetic() && !"aeiouy".contains(c) {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::domain_intel::HostKind;
+
+    fn host(h: &str, registrable: &str, suffix: &str) -> HostInfo {
+        HostInfo {
+            host: h.to_string(),
+            registrable_domain: registrable.to_string(),
+            registrable_domain_unicode: registrable.to_string(),
+            suffix: suffix.to_string(),
+            kind: HostKind::RegistrableDomain
+        }
+    }
+
+    #[test]
+    fn test_regular_domains_are_not_suspicious() {
+        assert!(!score_host(&host("www.google.com", "google.com", "com")).is_suspicious());
+        assert!(!score_host(&host("static.theguardian.co.uk", "theguardian.co.uk", "co.uk")).is_suspicious());
+        assert!(!score_host(&host("cdn.shopify.com", "shopify.com", "com")).is_suspicious());
+    }
+
+    #[test]
+    fn test_generated_domain_is_suspicious() {
+        let s = score_host(&host("xkqjzvtbw7r2mpl9.xyz", "xkqjzvtbw7r2mpl9.xyz", "xyz"));
+        assert!(s.is_suspicious(), "{}", s);
+    }
+
+    #[test]
+    fn test_dynamic_dns_scores_subdomain() {
+        let s = score_host(&host("q8x7zkw2vn.duckdns.org", "q8x7zkw2vn.duckdns.org", "duckdns.org"));
+        assert!(s.is_suspicious(), "{}", s);
+        assert!(s.reasons.iter().any(|r| r.contains("duckdns.org")));
+    }
+}
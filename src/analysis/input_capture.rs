@@ -0,0 +1,197 @@
+use core::fmt;
+
+use crate::analysis::canary::DataClass;
+use crate::analysis::dast_ioc_types::IoC;
+
+// listeners that see every key typed or every value entered in a field
+const KEY_LISTENERS: [&str; 5] = ["keydown", "keypress", "keyup", "input", "change"];
+// elements a keylogger hooks: the whole page or the fields themselves
+const KEY_LISTENER_TARGETS: [&str; 5] = ["document", "window", "input", "textarea", "form"];
+const SUBMIT_LISTENER_TARGETS: [&str; 3] = ["document", "window", "form"];
+
+// a listener registration reported by the sandbox
+#[derive(Debug, Clone)]
+pub struct ListenerRegistration {
+    pub executed_on: String,
+    pub timestamp: u64,
+    pub listener: String,
+    // "document", "window" or the element description, e.g. "input#card[name=cc]".
+    // Empty for sandboxes that only hooked `document.addEventListener`
+    pub target: String
+}
+
+impl fmt::Display for ListenerRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        let target = match self.target.is_empty() {
+            true => "document",
+            false => self.target.as_str()
+        };
+        write!(f, "{}.addEventListener(\"{}\")", target, self.listener)
+    }
+}
+
+// an outgoing request that carried planted data
+#[derive(Debug, Clone)]
+pub struct Leak {
+    pub request: IoC,
+    // "METHOD url"
+    pub description: String,
+    pub destination: String,
+    pub origins: Vec<DataClass>,
+    // cookies and storage entries whose values the request carried
+    pub sources: Vec<(DataClass, String)>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaptureKind {
+    Keylogger,
+    FormJacking
+}
+
+impl CaptureKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            CaptureKind::Keylogger => "keylogger sent captured keystrokes",
+            CaptureKind::FormJacking => "form submission was hijacked to exfiltrate data"
+        }
+    }
+}
+
+// a listener able to capture what the user types, followed by a request
+// carrying what was typed
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub kind: CaptureKind,
+    pub listener: ListenerRegistration,
+    pub leak: Leak
+}
+
+impl fmt::Display for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        let origins: Vec<&str> = self.leak.origins.iter().map(|o| o.as_str()).collect();
+        write!(f, "{} registered at {}, then {} sent {} data to {} {}ms later",
+            self.listener, self.listener.timestamp, self.leak.description, origins.join(", "),
+            self.leak.destination, self.leak.request.timestamp.saturating_sub(self.listener.timestamp))
+    }
+}
+
+// only data typed in the page can be captured by a listener,
+// cookies and storage values are read directly
+fn is_typed_data(origin: &DataClass) -> bool {
+    matches!(origin, DataClass::UserInput | DataClass::CreditCard | DataClass::Password | DataClass::Email)
+}
+
+fn target_tag(target: &str) -> &str {
+    match target.is_empty() {
+        true => "document",
+        false => target.split(['#', '[']).next().unwrap_or(target)
+    }
+}
+
+fn capture_kind(listener: &ListenerRegistration) -> Option<CaptureKind> {
+    let tag = target_tag(&listener.target);
+    let name = listener.listener.to_lowercase();
+    if KEY_LISTENERS.contains(&name.as_str()) && KEY_LISTENER_TARGETS.contains(&tag) {
+        return Some(CaptureKind::Keylogger);
+    }
+    if name == "submit" && SUBMIT_LISTENER_TARGETS.contains(&tag) {
+        return Some(CaptureKind::FormJacking);
+    }
+    None
+}
+
+// pairs every leak of typed data with the first capturing listener registered
+// before it on the same bait website. One capture per leak, key listeners
+// first as they see the data before any submit does
+pub fn detect_captures(listeners: &[ListenerRegistration], leaks: &[Leak]) -> Vec<Capture> {
+    let mut captures: Vec<Capture> = Vec::new();
+    for _l in leaks {
+        if !_l.origins.iter().any(is_typed_data) {
+            continue;
+        }
+        let capture = [CaptureKind::Keylogger, CaptureKind::FormJacking]
+            .into_iter()
+            .find_map(|kind| listeners
+                .iter()
+                .filter(|r| r.executed_on == _l.request.executed_on && r.timestamp <= _l.request.timestamp)
+                .filter(|r| capture_kind(r).as_ref() == Some(&kind))
+                .min_by_key(|r| r.timestamp)
+                .map(|r| (kind, r.clone())));
+        if let Some((kind, listener)) = capture {
+            captures.push(Capture {
+                kind,
+                listener,
+                leak: Leak {
+                    origins: _l.origins.iter().filter(|o| is_typed_data(o)).copied().collect(),
+                    .._l.clone()
+                }
+            });
+        }
+    }
+    captures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::dast_ioc_types::{IoCHttpRequest, IoCType, IoCValue};
+
+    fn listener(listener: &str, target: &str, timestamp: u64) -> ListenerRegistration {
+        ListenerRegistration {
+            executed_on: "https://bait.com".to_string(),
+            timestamp,
+            listener: listener.to_string(),
+            target: target.to_string()
+        }
+    }
+
+    fn leak(timestamp: u64, origins: Vec<DataClass>) -> Leak {
+        Leak {
+            request: IoC {
+                ioc_type: IoCType::HttpRequest,
+                timestamp,
+                executed_on: "https://bait.com".to_string(),
+                value: IoCValue::IoCHttpRequest(IoCHttpRequest {
+                    method: "POST".to_string(),
+                    url: "https://evil.com/k".to_string(),
+                    data: "".to_string(),
+                    headers: Default::default(),
+                    request_id: "".to_string(),
+                    initiator: "".to_string()
+                })
+            },
+            description: "POST https://evil.com/k".to_string(),
+            destination: "evil.com".to_string(),
+            origins,
+            sources: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_key_listener_followed_by_leak_is_keylogger() {
+        let listeners = vec![
+            listener("click", "document", 10),
+            listener("keydown", "input#card[name=cc]", 20),
+            listener("submit", "form#checkout", 30)
+        ];
+        let captures = detect_captures(&listeners, &[leak(100, vec![DataClass::CreditCard, DataClass::Cookie])]);
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0].kind, CaptureKind::Keylogger);
+        assert_eq!(captures[0].listener.target, "input#card[name=cc]");
+        assert_eq!(captures[0].leak.origins, vec![DataClass::CreditCard]);
+        assert!(captures[0].to_string().contains("input#card[name=cc].addEventListener(\"keydown\")"));
+
+        // without a key listener the submit listener captured the data
+        let captures = detect_captures(&listeners[2..], &[leak(100, vec![DataClass::Password])]);
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0].kind, CaptureKind::FormJacking);
+    }
+
+    #[test]
+    fn test_leaks_before_listener_or_of_cookies_are_not_captures() {
+        let listeners = vec![listener("keydown", "", 200)];
+        assert!(detect_captures(&listeners, &[leak(100, vec![DataClass::Password])]).is_empty());
+        assert!(detect_captures(&listeners, &[leak(300, vec![DataClass::Cookie])]).is_empty());
+        assert_eq!(detect_captures(&listeners, &[leak(300, vec![DataClass::Password])]).len(), 1);
+    }
+}
@@ -2,4 +2,24 @@
 pub mod dast;
 pub mod sast;
 pub mod analyzer;
-pub mod dast_ioc_types;
\ No newline at end of file
+pub mod dast_ioc_types;
+pub mod domain_intel;
+pub mod domain_heuristics;
+pub mod correlation;
+pub mod payload_decoder;
+pub mod canary;
+pub mod event_rules;
+pub mod rule_engine;
+pub mod beaconing;
+pub mod input_capture;
+pub mod timeline;
+pub mod scoring;
+pub mod aggregation;
+pub mod hash_intel;
+pub mod second_stage;
+pub mod runtime_decoded;
+pub mod html_injection;
+pub mod storage_access;
+pub mod network_graph;
+pub mod site_differential;
+pub mod baseline;
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::dast_ioc_types::{IoC, IoCValue};
+use crate::analysis::domain_intel::{DomainIntel, HostInfo};
+
+// node of the analysed file, origin of the requests and elements that have
+// no other known initiator
+pub const SAMPLE_NODE: &str = "sample";
+
+// urls kept per edge, the count tells how many there were
+const MAX_EDGE_URLS: usize = 10;
+
+// registrable domains of CDNs, analytics and tag managers legitimate pages load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkGraphConf {
+    pub known_services: Vec<String>
+}
+
+impl Default for NetworkGraphConf {
+    fn default() -> Self {
+        NetworkGraphConf {
+            known_services: [
+                "googleapis.com", "gstatic.com", "google-analytics.com", "googletagmanager.com",
+                "doubleclick.net", "cloudflare.com", "jsdelivr.net", "unpkg.com", "jquery.com",
+                "bootstrapcdn.com", "facebook.net", "hotjar.com", "akamaihd.net", "cloudfront.net"
+            ].iter().map(|s| s.to_string()).collect()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeClass {
+    Sample,
+    // the registrable domain of the bait website the event happened on
+    FirstParty,
+    KnownService,
+    ThirdParty
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GraphNode {
+    // host name, or `sample`
+    pub id: String,
+    pub class: NodeClass,
+    pub registrable_domain: String,
+    // bait websites the host was contacted from
+    pub executed_on: Vec<String>,
+    pub first_seen: u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    // request, download or "<element> element"
+    pub relation: String,
+    pub count: usize,
+    pub methods: Vec<String>,
+    // size of the request bodies, tells where data went
+    pub bytes_sent: usize,
+    // scripts that sent the requests
+    pub initiators: Vec<String>,
+    pub urls: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NetworkGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str, max: usize) {
+    if !value.is_empty() && values.len() < max && !values.iter().any(|v| v == value) {
+        values.push(value.to_string());
+    }
+}
+
+// graphviz ids and labels are double quoted strings
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl NetworkGraph {
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph network {\n    rankdir=LR;\n    node [style=filled];\n");
+        for _n in self.nodes.iter() {
+            let (shape, color, class) = match _n.class {
+                NodeClass::Sample => ("box", "lightblue", "sample"),
+                NodeClass::FirstParty => ("ellipse", "palegreen", "first party"),
+                NodeClass::KnownService => ("ellipse", "lightgray", "known service"),
+                NodeClass::ThirdParty => ("ellipse", "salmon", "third party")
+            };
+            dot.push_str(&format!("    \"{}\" [shape={}, fillcolor={}, label=\"{}\\n{}\"];\n",
+                dot_escape(&_n.id), shape, color, dot_escape(&_n.id), class));
+        }
+        for _e in self.edges.iter() {
+            let mut label = format!("{} x{}", _e.relation, _e.count);
+            if !_e.methods.is_empty() {
+                label.push_str(&format!(" {}", _e.methods.join("/")));
+            }
+            if _e.bytes_sent > 0 {
+                label.push_str(&format!(" {}B sent", _e.bytes_sent));
+            }
+            dot.push_str(&format!("    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                dot_escape(&_e.from), dot_escape(&_e.to), dot_escape(&label)));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// builds the graph of the hosts contacted during a dynamic run
+pub struct NetworkGraphBuilder {
+    domain_intel: Arc<DomainIntel>,
+    known_services: Vec<String>
+}
+
+impl NetworkGraphBuilder {
+    pub fn new(domain_intel: Arc<DomainIntel>, conf: NetworkGraphConf) -> Self {
+        NetworkGraphBuilder {
+            domain_intel,
+            known_services: conf.known_services.iter().map(|s| s.trim().to_lowercase()).collect()
+        }
+    }
+
+    // relative urls are served by the bait website itself
+    fn host_of(&self, url: &str, executed_on: &str) -> Option<HostInfo> {
+        let url = url.trim();
+        let relative = (url.starts_with('/') && !url.starts_with("//")) || url.starts_with('.') || url.starts_with('?');
+        match relative {
+            true => self.domain_intel.classify_url(executed_on),
+            false => self.domain_intel.classify_url(url)
+        }
+    }
+
+    fn class_of(&self, host: &HostInfo, executed_on: &str) -> NodeClass {
+        let bait_domain = self.domain_intel.classify_url(executed_on).map(|h| h.registrable_domain);
+        if bait_domain.as_ref() == Some(&host.registrable_domain) {
+            return NodeClass::FirstParty;
+        }
+        if self.known_services.iter().any(|s| host.host == *s || host.host.ends_with(&format!(".{}", s))) {
+            return NodeClass::KnownService;
+        }
+        NodeClass::ThirdParty
+    }
+
+    fn node(&self, graph: &mut NetworkGraph, host: &HostInfo, ioc: &IoC) -> String {
+        let class = self.class_of(host, &ioc.executed_on);
+        match graph.nodes.iter_mut().find(|n| n.id == host.host) {
+            Some(_n) => {
+                // first party for one of the bait websites wins
+                if class == NodeClass::FirstParty {
+                    _n.class = class;
+                }
+                push_unique(&mut _n.executed_on, &ioc.executed_on, usize::MAX);
+            },
+            None => graph.nodes.push(GraphNode {
+                id: host.host.clone(),
+                class,
+                registrable_domain: host.registrable_domain.clone(),
+                executed_on: vec![ioc.executed_on.clone()],
+                first_seen: ioc.timestamp
+            })
+        }
+        host.host.clone()
+    }
+
+    // expects correlated events, one per complete http request
+    pub fn build(&self, iocs: &[IoC]) -> NetworkGraph {
+        let mut graph = NetworkGraph::default();
+        graph.nodes.push(GraphNode {
+            id: SAMPLE_NODE.to_string(),
+            class: NodeClass::Sample,
+            registrable_domain: String::new(),
+            executed_on: Vec::new(),
+            first_seen: iocs.iter().map(|i| i.timestamp).min().unwrap_or(0)
+        });
+        for ioc in iocs {
+            let (url, relation, method, bytes_sent, initiator) = match &ioc.value {
+                IoCValue::IoCHttpRequest(_v) => (&_v.url, "request".to_string(), _v.method.as_str(), _v.data.len(), _v.initiator.as_str()),
+                IoCValue::IoCNewNetworkHtmlElement(_v) => (&_v.src, format!("{} element", _v.element_type.to_lowercase()), "", 0, ""),
+                IoCValue::IoCSuspiciousFileDownload(_v) => (&_v.url, "download".to_string(), "", 0, ""),
+                _ => continue
+            };
+            let host = match self.host_of(url, &ioc.executed_on) {
+                Some(_h) => _h,
+                None => continue
+            };
+            let to = self.node(&mut graph, &host, ioc);
+            let from = match self.domain_intel.classify_url(initiator) {
+                Some(_h) => self.node(&mut graph, &_h, ioc),
+                None => SAMPLE_NODE.to_string()
+            };
+            let edge = match graph.edges.iter().position(|e| e.from == from && e.to == to && e.relation == relation) {
+                Some(_i) => &mut graph.edges[_i],
+                None => {
+                    graph.edges.push(GraphEdge {
+                        from, to, relation,
+                        count: 0,
+                        methods: Vec::new(),
+                        bytes_sent: 0,
+                        initiators: Vec::new(),
+                        urls: Vec::new()
+                    });
+                    graph.edges.last_mut().unwrap()
+                }
+            };
+            edge.count += 1;
+            edge.bytes_sent += bytes_sent;
+            push_unique(&mut edge.methods, &method.to_uppercase(), usize::MAX);
+            push_unique(&mut edge.initiators, initiator, MAX_EDGE_URLS);
+            push_unique(&mut edge.urls, url, MAX_EDGE_URLS);
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::analysis::dast_ioc_types::{IoCHttpRequest, IoCNewNetworkHtmlElement, IoCType};
+
+    fn ioc(ioc_type: IoCType, timestamp: u64, value: IoCValue) -> IoC {
+        IoC { ioc_type, timestamp, executed_on: "https://shop.com/checkout".to_string(), value }
+    }
+
+    fn request(timestamp: u64, method: &str, url: &str, data: &str, initiator: &str) -> IoC {
+        ioc(IoCType::HttpRequest, timestamp, IoCValue::IoCHttpRequest(IoCHttpRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            data: data.to_string(),
+            headers: Default::default(),
+            request_id: String::new(),
+            initiator: initiator.to_string()
+        }))
+    }
+
+    #[test]
+    fn test_skimmer_loading_chain() {
+        let domain_intel = Arc::new(DomainIntel::new(PathBuf::from("./public_suffix.txt"), Vec::new(), Vec::new()).unwrap());
+        let builder = NetworkGraphBuilder::new(domain_intel, NetworkGraphConf::default());
+        let graph = builder.build(&[
+            ioc(IoCType::NewNetworkHtmlElement, 1, IoCValue::IoCNewNetworkHtmlElement(IoCNewNetworkHtmlElement {
+                element_type: "SCRIPT".to_string(),
+                src: "https://cdn.evil.com/skim.js".to_string()
+            })),
+            request(2, "get", "/api/cart", "", ""),
+            request(3, "GET", "https://www.google-analytics.com/collect", "", ""),
+            request(4, "POST", "https://collect.evil.net/c", "card=4111", "https://cdn.evil.com/skim.js"),
+            request(5, "POST", "https://collect.evil.net/c", "card=4242", "https://cdn.evil.com/skim.js")
+        ]);
+        let class = |id: &str| graph.nodes.iter().find(|n| n.id == id).map(|n| n.class.clone());
+        assert_eq!(class("shop.com"), Some(NodeClass::FirstParty));
+        assert_eq!(class("www.google-analytics.com"), Some(NodeClass::KnownService));
+        assert_eq!(class("cdn.evil.com"), Some(NodeClass::ThirdParty));
+        assert_eq!(graph.nodes.len(), 5);
+
+        let exfil = graph.edges.iter().find(|e| e.to == "collect.evil.net").unwrap();
+        assert_eq!(exfil.from, "cdn.evil.com");
+        assert_eq!((exfil.count, exfil.bytes_sent), (2, 18));
+        assert_eq!(exfil.methods, vec!["POST".to_string()]);
+        assert_eq!(graph.edges[0].relation, "script element");
+        assert_eq!(graph.edges[0].from, SAMPLE_NODE);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("\"cdn.evil.com\" -> \"collect.evil.net\" [label=\"request x2 POST 18B sent\"];"));
+    }
+}
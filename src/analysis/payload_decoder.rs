@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+use core::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+
+// how many transforms are chained at most (e.g. url -> base64 -> reverse)
+const MAX_DECODE_DEPTH: usize = 3;
+// upper bound of decoded variants kept per payload
+const MAX_VARIANTS: usize = 64;
+// encoded tokens shorter than this are left alone
+const MIN_ENCODED_TOKEN_LEN: usize = 8;
+// the xor key search is quadratic, skip it for large payloads
+const MAX_XOR_PAYLOAD_LEN: usize = 64 * 1024;
+const MAX_XOR_KEY_LEN: usize = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Transform {
+    Url,
+    Base64,
+    Hex,
+    JsonUnescape,
+    Reverse,
+    Xor(Vec<u8>)
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Transform::Url => write!(f, "url"),
+            Transform::Base64 => write!(f, "base64"),
+            Transform::Hex => write!(f, "hex"),
+            Transform::JsonUnescape => write!(f, "json-unescape"),
+            Transform::Reverse => write!(f, "reverse"),
+            Transform::Xor(_k) => {
+                let key: Vec<String> = _k.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(f, "xor(0x{})", key.join(""))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PayloadMatch {
+    pub needle: String,
+    // transforms applied to the raw payload, in order, until the needle appeared
+    pub chain: Vec<Transform>
+}
+
+impl PayloadMatch {
+    pub fn chain_description(&self) -> String {
+        if self.chain.is_empty() {
+            return "plain".to_string();
+        }
+        self.chain.iter().map(|t| t.to_string()).collect::<Vec<String>>().join(" -> ")
+    }
+}
+
+struct Variant {
+    bytes: Vec<u8>,
+    chain: Vec<Transform>
+}
+
+// looks for every needle in the payload and in its decoded variants.
+// Variants are explored breadth first, so the reported chain is the
+// shortest one that reveals the needle. The xor key search only runs for
+// the needles no variant revealed
+pub fn find_needles(payload: &str, needles: &[String]) -> Vec<PayloadMatch> {
+    let mut matches: Vec<PayloadMatch> = Vec::new();
+    let needles: Vec<&String> = needles.iter().filter(|n| !n.is_empty()).collect();
+    if needles.is_empty() || payload.is_empty() {
+        return matches;
+    }
+
+    let variants = decode_variants(payload);
+    for variant in variants.iter() {
+        for needle in needles.iter() {
+            if !matches.iter().any(|m| m.needle == **needle) && contains(&variant.bytes, needle.as_bytes()) {
+                matches.push(PayloadMatch { needle: needle.to_string(), chain: variant.chain.clone() });
+            }
+        }
+        if matches.len() == needles.len() {
+            return matches;
+        }
+    }
+    for variant in variants.iter() {
+        for needle in needles.iter() {
+            if matches.iter().any(|m| m.needle == **needle) {
+                continue;
+            }
+            if let Some(_key) = find_xor_key(&variant.bytes, needle.as_bytes()) {
+                let mut chain = variant.chain.clone();
+                chain.push(Transform::Xor(_key));
+                matches.push(PayloadMatch { needle: needle.to_string(), chain });
+            }
+        }
+        if matches.len() == needles.len() {
+            break;
+        }
+    }
+    matches
+}
+
+fn decode_variants(payload: &str) -> Vec<Variant> {
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    let mut variants: Vec<Variant> = vec![Variant { bytes: payload.as_bytes().to_vec(), chain: Vec::new() }];
+    seen.insert(payload.as_bytes().to_vec());
+
+    let mut frontier: usize = 0;
+    while frontier < variants.len() && variants.len() < MAX_VARIANTS {
+        if variants[frontier].chain.len() >= MAX_DECODE_DEPTH {
+            frontier += 1;
+            continue;
+        }
+        let transforms = [
+            Transform::Url,
+            Transform::Base64,
+            Transform::Hex,
+            Transform::JsonUnescape,
+            Transform::Reverse
+        ];
+        for t in transforms {
+            // reversing twice gives back an already seen variant
+            if t == Transform::Reverse && variants[frontier].chain.last() == Some(&Transform::Reverse) {
+                continue;
+            }
+            if let Some(_decoded) = apply(&t, &variants[frontier].bytes)
+                && seen.insert(_decoded.clone()) {
+                let mut chain = variants[frontier].chain.clone();
+                chain.push(t);
+                variants.push(Variant { bytes: _decoded, chain });
+            }
+        }
+        frontier += 1;
+    }
+    variants
+}
+
+fn apply(transform: &Transform, input: &[u8]) -> Option<Vec<u8>> {
+    match transform {
+        Transform::Url => url_decode(input),
+        Transform::Base64 => replace_tokens(input, is_base64_byte, Some(b'='), base64_decode),
+        Transform::Hex => replace_tokens(input, |b| b.is_ascii_hexdigit(), None, hex_decode),
+        Transform::JsonUnescape => json_unescape(input),
+        Transform::Reverse => {
+            let reversed: Vec<u8> = String::from_utf8_lossy(input).chars().rev().collect::<String>().into_bytes();
+            Some(reversed)
+        },
+        Transform::Xor(_key) => Some(input.iter().enumerate().map(|(i, b)| b ^ _key[i % _key.len()]).collect())
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.len() <= haystack.len() && haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn is_base64_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'-' | b'_')
+}
+
+// decodes every maximal run of `in_alphabet` bytes that `decode` accepts,
+// keeping the rest of the payload (e.g. `a=1&d=<base64>`) as is.
+// `padding` bytes are only accepted at the end of a token
+fn replace_tokens(
+    input: &[u8],
+    in_alphabet: fn(u8) -> bool,
+    padding: Option<u8>,
+    decode: fn(&[u8]) -> Option<Vec<u8>>) -> Option<Vec<u8>> {
+    let mut output: Vec<u8> = Vec::with_capacity(input.len());
+    let mut changed = false;
+    let mut i = 0;
+    while i < input.len() {
+        if !in_alphabet(input[i]) {
+            output.push(input[i]);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < input.len() && in_alphabet(input[i]) {
+            i += 1;
+        }
+        while i < input.len() && Some(input[i]) == padding {
+            i += 1;
+        }
+        let token = &input[start..i];
+        match decode(token) {
+            Some(_d) if token.len() >= MIN_ENCODED_TOKEN_LEN => {
+                output.extend_from_slice(&_d);
+                changed = true;
+            },
+            _ => output.extend_from_slice(token)
+        }
+    }
+    if changed { Some(output) } else { None }
+}
+
+fn base64_decode(token: &[u8]) -> Option<Vec<u8>> {
+    let trimmed: Vec<u8> = token.iter().copied().filter(|b| *b != b'=').collect();
+    match STANDARD_NO_PAD.decode(&trimmed) {
+        Ok(_d) => Some(_d),
+        Err(_) => URL_SAFE_NO_PAD.decode(&trimmed).ok()
+    }
+}
+
+fn hex_decode(token: &[u8]) -> Option<Vec<u8>> {
+    if !token.len().is_multiple_of(2) {
+        return None;
+    }
+    token
+        .chunks(2)
+        .map(|c| u8::from_str_radix(str::from_utf8(c).ok()?, 16).ok())
+        .collect()
+}
+
+fn url_decode(input: &[u8]) -> Option<Vec<u8>> {
+    if !input.contains(&b'%') {
+        return None;
+    }
+    let mut output: Vec<u8> = Vec::with_capacity(input.len());
+    let mut changed = false;
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' && i + 2 < input.len()
+            && let Some(_b) = hex_decode(&input[i + 1..i + 3]) {
+            output.push(_b[0]);
+            changed = true;
+            i += 3;
+            continue;
+        }
+        output.push(input[i]);
+        i += 1;
+    }
+    if changed { Some(output) } else { None }
+}
+
+// undoes javascript/json string escaping (\uXXXX, \xXX, \n, \" ...)
+fn json_unescape(input: &[u8]) -> Option<Vec<u8>> {
+    if !input.contains(&b'\\') {
+        return None;
+    }
+    let text = String::from_utf8_lossy(input);
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = String::with_capacity(chars.len());
+    let mut changed = false;
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' && i + 1 < chars.len() {
+            let escaped = match chars[i + 1] {
+                'u' if i + 5 < chars.len() => {
+                    let code: String = chars[i + 2..i + 6].iter().collect();
+                    u32::from_str_radix(&code, 16).ok().and_then(char::from_u32).map(|c| (c, 6))
+                },
+                'x' if i + 3 < chars.len() => {
+                    let code: String = chars[i + 2..i + 4].iter().collect();
+                    u32::from_str_radix(&code, 16).ok().and_then(char::from_u32).map(|c| (c, 4))
+                },
+                'n' => Some(('\n', 2)),
+                'r' => Some(('\r', 2)),
+                't' => Some(('\t', 2)),
+                '"' | '\'' | '\\' | '/' => Some((chars[i + 1], 2)),
+                _ => None
+            };
+            if let Some((_c, _len)) = escaped {
+                output.push(_c);
+                changed = true;
+                i += _len;
+                continue;
+            }
+        }
+        output.push(chars[i]);
+        i += 1;
+    }
+    if changed { Some(output.into_bytes()) } else { None }
+}
+
+// known plaintext attack: if the payload contains the needle xor'd with a
+// short repeating key, the key can be recovered from any aligned position
+fn find_xor_key(payload: &[u8], needle: &[u8]) -> Option<Vec<u8>> {
+    if payload.len() > MAX_XOR_PAYLOAD_LEN || needle.len() < MIN_ENCODED_TOKEN_LEN || needle.len() > payload.len() {
+        return None;
+    }
+    for start in 0..=(payload.len() - needle.len()) {
+        for key_len in 1..=MAX_XOR_KEY_LEN {
+            let key: Vec<u8> = (0..key_len).map(|i| payload[start + i] ^ needle[i]).collect();
+            if key.iter().all(|k| *k == 0) {
+                continue;
+            }
+            let matched = needle
+                .iter()
+                .enumerate()
+                .all(|(i, n)| payload[start + i] ^ key[i % key_len] == *n);
+            if matched {
+                return Some(key);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD;
+
+    const CANARY: &str = "fake_input_from_sandbox_0";
+
+    fn chain_of(payload: &str) -> Option<String> {
+        find_needles(payload, &[CANARY.to_string()]).first().map(|m| m.chain_description())
+    }
+
+    #[test]
+    fn test_plain_and_url_encoded() {
+        assert_eq!(chain_of("u=fake_input_from_sandbox_0").unwrap(), "plain");
+        assert_eq!(chain_of("u=fake%5Finput%5Ffrom%5Fsandbox%5F0").unwrap(), "url");
+        assert_eq!(chain_of("u=fake%255Finput%255Ffrom%255Fsandbox%255F0").unwrap(), "url -> url");
+    }
+
+    #[test]
+    fn test_base64_reverse_and_hex() {
+        let b64 = STANDARD.encode(CANARY);
+        assert_eq!(chain_of(&format!("a=1&d={}", b64)).unwrap(), "base64");
+        let reversed: String = STANDARD.encode(CANARY).chars().rev().collect();
+        assert_eq!(chain_of(&reversed).unwrap(), "reverse -> base64");
+        let hex: String = CANARY.bytes().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(chain_of(&hex).unwrap(), "hex");
+        assert_eq!(chain_of("{\"v\":\"\\u0066ake_input_from_sandbox_0\"}").unwrap(), "json-unescape");
+    }
+
+    #[test]
+    fn test_xor_with_short_key() {
+        let xored: Vec<u8> = CANARY.bytes().enumerate().map(|(i, b)| b ^ [0x13, 0x37][i % 2]).collect();
+        assert_eq!(chain_of(&STANDARD.encode(xored)).unwrap(), "base64 -> xor(0x1337)");
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert!(chain_of("nothing=here&x=aGVsbG8gd29ybGQ=").is_none());
+    }
+}
@@ -0,0 +1,753 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use log::{debug, error, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::analysis::analyzer::{self, Finding, Severity};
+use crate::analysis::dast_ioc_types::{IoC, IoCType};
+use crate::utils;
+
+// outcome of the reputation lookup of the host an event talks to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReputationVerdict {
+    Bad,
+    Unknown,
+    Good
+}
+
+// a condition as written in the rules file. Exactly one matcher must be set
+#[derive(Deserialize, Debug, Clone)]
+pub struct ConditionDef {
+    // dotted path inside the serialized IoC, e.g. `executed_on`,
+    // `value.callee` or `value.arguments.0`
+    pub field: String,
+    pub equals: Option<String>,
+    pub not_equals: Option<String>,
+    pub contains: Option<String>,
+    pub regex: Option<String>,
+    #[serde(rename = "in")]
+    pub one_of: Option<Vec<String>>,
+    pub html_like: Option<bool>,
+    pub exists: Option<bool>,
+    #[serde(default)]
+    pub case_insensitive: bool
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DynamicRuleDef {
+    pub id: String,
+    pub title: String,
+    pub severity: Severity,
+    pub ioc_type: Option<IoCType>,
+    // only match events whose host has the given reputation
+    pub reputation: Option<ReputationVerdict>,
+    #[serde(default)]
+    pub conditions: Vec<ConditionDef>,
+    // template of the finding poc, `{field.path}` is replaced by the field value
+    pub poc: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+fn one() -> usize {
+    1
+}
+
+// one step of a sequence rule: which events it matches and how close in
+// time they must be to the previous step
+#[derive(Deserialize, Debug, Clone)]
+pub struct SequenceStepDef {
+    pub ioc_type: Option<IoCType>,
+    pub reputation: Option<ReputationVerdict>,
+    #[serde(default)]
+    pub conditions: Vec<ConditionDef>,
+    // the event contacts a third party domain for the first time in the analysis
+    pub new_domain: Option<bool>,
+    // the event contacts a domain other than the bait website
+    pub third_party: Option<bool>,
+    // maximum time between an event of this step and the previous matched event
+    pub max_gap_ms: Option<u64>,
+    // number of events the step needs, e.g. repeated requests
+    #[serde(default = "one")]
+    pub min_count: usize,
+    // all the events of the step contact the same host
+    #[serde(default)]
+    pub same_host: bool
+}
+
+// ordered pattern of events that must all happen within `within_ms`
+#[derive(Deserialize, Debug, Clone)]
+pub struct SequenceRuleDef {
+    pub id: String,
+    pub title: String,
+    pub severity: Severity,
+    pub within_ms: u64,
+    pub steps: Vec<SequenceStepDef>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DynamicRulesFile {
+    #[serde(default)]
+    pub rules: Vec<DynamicRuleDef>,
+    #[serde(default)]
+    pub sequences: Vec<SequenceRuleDef>
+}
+
+// an event of the analysis timeline together with the context the
+// analyzer computed for it, used by the sequence rules
+#[derive(Debug, Clone)]
+pub struct TimelineEvent {
+    pub ioc: IoC,
+    pub reputation: Option<ReputationVerdict>,
+    pub host: Option<String>,
+    pub new_domain: bool,
+    // the contacted host is not in the registrable domain of the bait website
+    pub third_party: bool
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Equals(String),
+    NotEquals(String),
+    Contains(String),
+    Regex(Regex),
+    OneOf(Vec<String>),
+    HtmlLike(bool),
+    Exists(bool)
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    path: Vec<String>,
+    matcher: Matcher,
+    case_insensitive: bool
+}
+
+impl Condition {
+    fn compile(rule_id: &str, def: &ConditionDef) -> Result<Self, String> {
+        let mut matchers: Vec<Matcher> = Vec::new();
+        let fold = |s: &String| if def.case_insensitive { s.to_lowercase() } else { s.clone() };
+        if let Some(_v) = &def.equals {
+            matchers.push(Matcher::Equals(fold(_v)));
+        }
+        if let Some(_v) = &def.not_equals {
+            matchers.push(Matcher::NotEquals(fold(_v)));
+        }
+        if let Some(_v) = &def.contains {
+            matchers.push(Matcher::Contains(fold(_v)));
+        }
+        if let Some(_v) = &def.regex {
+            let pattern = if def.case_insensitive { format!("(?i){}", _v) } else { _v.clone() };
+            match Regex::new(&pattern) {
+                Ok(_r) => matchers.push(Matcher::Regex(_r)),
+                Err(e) => return Err(format!("rule {}: invalid regex {:?}: {}", rule_id, _v, e))
+            }
+        }
+        if let Some(_v) = &def.one_of {
+            matchers.push(Matcher::OneOf(_v.iter().map(fold).collect()));
+        }
+        if let Some(_v) = def.html_like {
+            matchers.push(Matcher::HtmlLike(_v));
+        }
+        if let Some(_v) = def.exists {
+            matchers.push(Matcher::Exists(_v));
+        }
+        if matchers.len() != 1 {
+            return Err(format!(
+                "rule {}: condition on {:?} must have exactly one of equals, not_equals, contains, regex, in, html_like, exists",
+                rule_id, def.field));
+        }
+        Ok(Condition {
+            path: def.field.split('.').map(|s| s.to_string()).collect(),
+            matcher: matchers.remove(0),
+            case_insensitive: def.case_insensitive
+        })
+    }
+
+    fn matches(&self, event: &Value) -> bool {
+        let field = resolve_field(event, &self.path);
+        if let Matcher::Exists(_e) = self.matcher {
+            return field.is_some() == _e;
+        }
+        let field = match field {
+            Some(_f) if self.case_insensitive => _f.to_lowercase(),
+            Some(_f) => _f,
+            // conditions on missing fields never match
+            None => return false
+        };
+        match &self.matcher {
+            Matcher::Equals(_v) => field == *_v,
+            Matcher::NotEquals(_v) => field != *_v,
+            Matcher::Contains(_v) => field.contains(_v.as_str()),
+            Matcher::Regex(_r) => _r.is_match(&field),
+            Matcher::OneOf(_v) => _v.contains(&field),
+            Matcher::HtmlLike(_v) => utils::contains_html_like_code(&field) == *_v,
+            Matcher::Exists(_) => true
+        }
+    }
+}
+
+// value of a dotted path inside the serialized event. Numeric segments
+// index arrays, non string leaves are returned as their json text
+fn resolve_field(event: &Value, path: &[String]) -> Option<String> {
+    let mut current = event;
+    for segment in path {
+        current = match current {
+            Value::Object(_o) => _o.get(segment)?,
+            Value::Array(_a) => _a.get(segment.parse::<usize>().ok()?)?,
+            _ => return None
+        };
+    }
+    match current {
+        Value::String(_s) => Some(_s.clone()),
+        Value::Null => None,
+        _v => Some(_v.to_string())
+    }
+}
+
+// the part of a rule (or of a sequence step) that decides whether a single event matches
+#[derive(Debug, Clone)]
+struct EventMatcher {
+    ioc_type: Option<String>,
+    reputation: Option<ReputationVerdict>,
+    conditions: Vec<Condition>
+}
+
+impl EventMatcher {
+    fn compile(rule_id: &str, ioc_type: &Option<IoCType>, reputation: &Option<ReputationVerdict>, conditions: &[ConditionDef]) -> Result<Self, String> {
+        let mut compiled: Vec<Condition> = Vec::new();
+        for _c in conditions.iter() {
+            compiled.push(Condition::compile(rule_id, _c)?);
+        }
+        Ok(EventMatcher {
+            ioc_type: ioc_type.as_ref().map(|t| t.to_string()),
+            reputation: reputation.clone(),
+            conditions: compiled
+        })
+    }
+
+    fn matches(&self, ioc_type: &str, event: &Value, reputation: Option<&ReputationVerdict>) -> bool {
+        if self.ioc_type.as_ref().is_some_and(|t| t != ioc_type) {
+            return false;
+        }
+        if self.reputation.is_some() && reputation != self.reputation.as_ref() {
+            return false;
+        }
+        self.conditions.iter().all(|c| c.matches(event))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DynamicRule {
+    pub id: String,
+    pub title: String,
+    pub severity: Severity,
+    matcher: EventMatcher,
+    poc: Option<String>
+}
+
+impl DynamicRule {
+    pub fn compile(def: &DynamicRuleDef) -> Result<Self, String> {
+        Ok(DynamicRule {
+            id: def.id.clone(),
+            title: def.title.clone(),
+            severity: def.severity.clone(),
+            matcher: EventMatcher::compile(&def.id, &def.ioc_type, &def.reputation, &def.conditions)?,
+            poc: def.poc.clone()
+        })
+    }
+
+    fn render_poc(&self, event: &Value) -> String {
+        let template = match &self.poc {
+            Some(_p) => _p.clone(),
+            // without a template the first condition field is the most telling value
+            None => match self.matcher.conditions.first() {
+                Some(_c) => format!("{{{}}}", _c.path.join(".")),
+                None => "{type}".to_string()
+            }
+        };
+        let placeholder = Regex::new(r"\{([A-Za-z0-9_.]+)\}").unwrap();
+        placeholder
+            .replace_all(&template, |caps: &regex::Captures| {
+                let path: Vec<String> = caps[1].split('.').map(|s| s.to_string()).collect();
+                resolve_field(event, &path).unwrap_or_default()
+            })
+            .to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SequenceStep {
+    matcher: EventMatcher,
+    new_domain: Option<bool>,
+    third_party: Option<bool>,
+    max_gap_ms: Option<u64>,
+    min_count: usize,
+    same_host: bool
+}
+
+impl SequenceStep {
+    fn matches(&self, event: &TimelineEvent, value: &Value) -> bool {
+        if self.new_domain.is_some_and(|n| n != event.new_domain) {
+            return false;
+        }
+        if self.third_party.is_some_and(|t| t != event.third_party) {
+            return false;
+        }
+        self.matcher.matches(&event.ioc.ioc_type.to_string(), value, event.reputation.as_ref())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SequenceRule {
+    pub id: String,
+    pub title: String,
+    pub severity: Severity,
+    within_ms: u64,
+    steps: Vec<SequenceStep>
+}
+
+impl SequenceRule {
+    pub fn compile(def: &SequenceRuleDef) -> Result<Self, String> {
+        if def.steps.is_empty() {
+            return Err(format!("sequence {}: at least one step is needed", def.id));
+        }
+        let mut steps: Vec<SequenceStep> = Vec::new();
+        for _s in def.steps.iter() {
+            if _s.min_count == 0 {
+                return Err(format!("sequence {}: min_count must be at least 1", def.id));
+            }
+            steps.push(SequenceStep {
+                matcher: EventMatcher::compile(&def.id, &_s.ioc_type, &_s.reputation, &_s.conditions)?,
+                new_domain: _s.new_domain,
+                third_party: _s.third_party,
+                max_gap_ms: _s.max_gap_ms,
+                min_count: _s.min_count,
+                same_host: _s.same_host
+            });
+        }
+        Ok(SequenceRule {
+            id: def.id.clone(),
+            title: def.title.clone(),
+            severity: def.severity.clone(),
+            within_ms: def.within_ms,
+            steps
+        })
+    }
+
+    // tries to complete the sequence starting from the event at `anchor`.
+    // Every step takes the earliest events that match it, so the indexes
+    // of the matched events are returned in timeline order. Steps on the
+    // same host collect the events of every host apart and take the first
+    // host that reaches `min_count`
+    fn match_from(&self, events: &[&TimelineEvent], values: &[Value], anchor: usize) -> Option<Vec<usize>> {
+        let anchor_ts = events[anchor].ioc.timestamp;
+        let mut matched: Vec<usize> = Vec::new();
+        let mut previous_ts = anchor_ts;
+        let mut next = anchor;
+        for (step_idx, step) in self.steps.iter().enumerate() {
+            // events of the step per contacted host, a single group when the
+            // step does not need the same host
+            let mut groups: Vec<(Option<&String>, Vec<usize>)> = Vec::new();
+            let mut step_events: Option<Vec<usize>> = None;
+            let mut j = next;
+            while j < events.len() {
+                let ts = events[j].ioc.timestamp;
+                if ts.saturating_sub(anchor_ts) > self.within_ms {
+                    break;
+                }
+                let host = if step.same_host { events[j].host.as_ref() } else { None };
+                let group_idx = match groups.iter().position(|(h, _)| *h == host) {
+                    Some(_g) => _g,
+                    None => {
+                        groups.push((host, Vec::new()));
+                        groups.len() - 1
+                    }
+                };
+                let group = &mut groups[group_idx].1;
+                let last_ts = group.last().map(|i| events[*i].ioc.timestamp).unwrap_or(previous_ts);
+                let within_gap = step.max_gap_ms.is_none_or(|gap| ts.saturating_sub(last_ts) <= gap);
+                if within_gap && step.matches(events[j], &values[j]) {
+                    group.push(j);
+                    if group.len() >= step.min_count {
+                        step_events = Some(group.clone());
+                        break;
+                    }
+                } else if step_idx == 0 && j == anchor {
+                    // the sequence must start at the anchor
+                    return None;
+                }
+                j += 1;
+            }
+            let mut step_events = step_events?;
+            let last = step_events[step_events.len() - 1];
+            previous_ts = events[last].ioc.timestamp;
+            next = last + 1;
+            matched.append(&mut step_events);
+        }
+        Some(matched)
+    }
+
+    fn evaluate(&self, events: &[&TimelineEvent], values: &[Value]) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = Vec::new();
+        let mut anchor = 0;
+        while anchor < events.len() {
+            match self.match_from(events, values, anchor) {
+                Some(_m) => {
+                    debug!("sequence rule {} matched {} events", self.id, _m.len());
+                    let first_ts = events[_m[0]].ioc.timestamp;
+                    let citations: Vec<String> = _m
+                        .iter()
+                        .map(|i| format!("[+{}ms] {}: {}",
+                            events[*i].ioc.timestamp.saturating_sub(first_ts),
+                            events[*i].ioc.ioc_type,
+                            events[*i].ioc.value.summary()))
+                        .collect();
+                    let last = events[_m[_m.len() - 1]];
+                    findings.push(
+                        analyzer::Finding {
+                            r#type: analyzer::AnalysisType::Dynamic,
+                            ioc: last.ioc.value.clone(),
+                            executed_on: last.ioc.executed_on.clone(),
+                            timestamp: last.ioc.timestamp,
+                            severity: self.severity.clone(),
+                            poc: citations.join("; "),
+                            title: self.title.clone(),
+                            tags: Vec::new()
+                        });
+                    // events of a reported sequence do not start a new one
+                    anchor = _m[_m.len() - 1] + 1;
+                },
+                None => anchor += 1
+            }
+        }
+        findings
+    }
+}
+
+// the dynamic rules loaded from the rules file. The file is reloaded when it
+// changes, so analysts can ship new rules without rebuilding the analyzer
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    rules: Vec<DynamicRule>,
+    sequences: Vec<SequenceRule>
+}
+
+impl RuleSet {
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        let rules_file: DynamicRulesFile = match serde_yaml::from_str(yaml) {
+            Ok(r) => r,
+            Err(e) => return Err(format!("could not parse dynamic rules: {}", e))
+        };
+        let mut rules: Vec<DynamicRule> = Vec::new();
+        for _def in rules_file.rules.iter().filter(|d| d.enabled) {
+            if rules.iter().any(|r| r.id == _def.id) {
+                return Err(format!("rule {}: duplicate rule id", _def.id));
+            }
+            rules.push(DynamicRule::compile(_def)?);
+        }
+        let mut sequences: Vec<SequenceRule> = Vec::new();
+        for _def in rules_file.sequences.iter().filter(|d| d.enabled) {
+            if rules.iter().any(|r| r.id == _def.id) || sequences.iter().any(|r| r.id == _def.id) {
+                return Err(format!("rule {}: duplicate rule id", _def.id));
+            }
+            sequences.push(SequenceRule::compile(_def)?);
+        }
+        Ok(RuleSet {
+            path: None,
+            modified: None,
+            rules,
+            sequences
+        })
+    }
+
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let yaml = match fs::read_to_string(&path) {
+            Ok(r) => r,
+            Err(e) => return Err(format!("could not read dynamic rules {:?}: {}", path, e))
+        };
+        let mut rule_set = RuleSet::from_yaml(&yaml)?;
+        rule_set.path = Some(path);
+        rule_set.modified = modified;
+        Ok(rule_set)
+    }
+
+    // reloads the rules file if it was modified. A broken file is reported
+    // and the previously loaded rules are kept
+    pub fn reload_if_changed(&mut self) {
+        let path = match &self.path {
+            Some(_p) => _p.clone(),
+            None => return
+        };
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        match RuleSet::load(path.clone()) {
+            Ok(_r) => {
+                info!("reloaded {} dynamic rules from {:?}", _r.count(), path);
+                *self = _r;
+            },
+            Err(e) => {
+                error!("keeping previous dynamic rules: {}", e);
+                self.modified = modified;
+            }
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.rules.len() + self.sequences.len()
+    }
+
+    pub fn evaluate(&self, ioc: &IoC, reputation: Option<&ReputationVerdict>) -> Vec<Finding> {
+        let event = match serde_json::to_value(ioc) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("could not serialize event for the dynamic rules: {:?}", e);
+                return Vec::new();
+            }
+        };
+        let ioc_type = ioc.ioc_type.to_string();
+        let mut findings: Vec<Finding> = Vec::new();
+        for rule in self.rules.iter().filter(|r| r.matcher.matches(&ioc_type, &event, reputation)) {
+            debug!("dynamic rule {} matched", rule.id);
+            findings.push(
+                analyzer::Finding {
+                    r#type: analyzer::AnalysisType::Dynamic,
+                    ioc: ioc.value.clone(),
+                    executed_on: ioc.executed_on.clone(),
+                    timestamp: ioc.timestamp,
+                    severity: rule.severity.clone(),
+                    poc: rule.render_poc(&event),
+                    title: rule.title.clone(),
+                    tags: Vec::new()
+                });
+        }
+        findings
+    }
+
+    // evaluates the sequence rules over the whole analysis. Every bait
+    // website is a separate timeline ordered by event timestamp
+    pub fn evaluate_sequences(&self, timeline: &[TimelineEvent]) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = Vec::new();
+        if self.sequences.is_empty() {
+            return findings;
+        }
+        let mut bait_websites: Vec<&String> = Vec::new();
+        for _e in timeline.iter() {
+            if !bait_websites.contains(&&_e.ioc.executed_on) {
+                bait_websites.push(&_e.ioc.executed_on);
+            }
+        }
+        for bait_website in bait_websites {
+            let mut events: Vec<&TimelineEvent> = timeline
+                .iter()
+                .filter(|e| e.ioc.executed_on == *bait_website)
+                .collect();
+            events.sort_by_key(|e| e.ioc.timestamp);
+            let values: Vec<Value> = events
+                .iter()
+                .map(|e| serde_json::to_value(&e.ioc).unwrap_or(Value::Null))
+                .collect();
+            for sequence in self.sequences.iter() {
+                findings.append(&mut sequence.evaluate(&events, &values));
+            }
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::dast_ioc_types::{IoCAddEventListener, IoCFunctionCall, IoCGetCookie, IoCHttpRequest, IoCValue};
+
+    fn function_call(callee: &str, arguments: &[&str]) -> IoC {
+        IoC {
+            ioc_type: IoCType::FunctionCall,
+            executed_on: "https://bait.com".to_string(),
+            timestamp: 1,
+            value: IoCValue::IoCFunctionCall(IoCFunctionCall {
+                callee: callee.to_string(),
+                arguments: arguments.iter().map(|a| a.to_string()).collect()
+            })
+        }
+    }
+
+    #[test]
+    fn test_rule_matches_argument_index() {
+        let rules = RuleSet::from_yaml(r#"
+rules:
+  - id: storage-session
+    title: session read
+    severity: High
+    ioc_type: function_call
+    poc: "{value.callee}({value.arguments.0})"
+    conditions:
+      - field: value.callee
+        equals: window.localStorage.getItem
+      - field: value.arguments.0
+        in: [PHPSESSID, SID]
+      - field: executed_on
+        regex: "^https://"
+"#).unwrap();
+        let f = rules.evaluate(&function_call("window.localStorage.getItem", &["SID"]), None);
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].poc, "window.localStorage.getItem(SID)");
+        assert!(rules.evaluate(&function_call("window.localStorage.getItem", &["theme"]), None).is_empty());
+        assert!(rules.evaluate(&function_call("window.localStorage.getItem", &[]), None).is_empty());
+    }
+
+    #[test]
+    fn test_rule_reputation_condition() {
+        let rules = RuleSet::from_yaml(r#"
+rules:
+  - id: bad-eval
+    title: eval
+    severity: VeryHigh
+    reputation: bad
+    conditions:
+      - field: value.callee
+        contains: EVAL
+        case_insensitive: true
+"#).unwrap();
+        let ioc = function_call("window.eval", &["1"]);
+        assert!(rules.evaluate(&ioc, None).is_empty());
+        assert!(rules.evaluate(&ioc, Some(&ReputationVerdict::Good)).is_empty());
+        assert_eq!(rules.evaluate(&ioc, Some(&ReputationVerdict::Bad)).len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let two_matchers = "rules:\n  - {id: a, title: t, severity: Low, conditions: [{field: type, equals: x, contains: y}]}";
+        assert!(RuleSet::from_yaml(two_matchers).is_err());
+        let bad_regex = "rules:\n  - {id: a, title: t, severity: Low, conditions: [{field: type, regex: '('}]}";
+        assert!(RuleSet::from_yaml(bad_regex).is_err());
+        let duplicate = "rules:\n  - {id: a, title: t, severity: Low}\n  - {id: a, title: t, severity: Low}";
+        assert!(RuleSet::from_yaml(duplicate).is_err());
+    }
+
+    fn timeline_event(ts: u64, value: IoCValue, ioc_type: IoCType, host: Option<&str>, new_domain: bool) -> TimelineEvent {
+        TimelineEvent {
+            ioc: IoC { ioc_type, executed_on: "https://bait.com".to_string(), timestamp: ts, value },
+            reputation: None,
+            host: host.map(|h| h.to_string()),
+            new_domain,
+            third_party: host.is_some_and(|h| h != "bait.com" && !h.ends_with(".bait.com"))
+        }
+    }
+
+    fn request(ts: u64, url: &str, host: &str, new_domain: bool) -> TimelineEvent {
+        timeline_event(ts, IoCValue::IoCHttpRequest(IoCHttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            data: String::new(),
+            headers: Default::default(),
+            request_id: String::new(),
+            initiator: String::new()
+        }), IoCType::HttpRequest, Some(host), new_domain)
+    }
+
+    #[test]
+    fn test_sequence_within_time_window() {
+        let rules = RuleSet::from_yaml(r#"
+sequences:
+  - id: cookie-then-new-domain
+    title: cookie then new domain
+    severity: High
+    within_ms: 1000
+    steps:
+      - ioc_type: get_cookie
+      - ioc_type: http_request
+        new_domain: true
+"#).unwrap();
+        let cookie = |ts| timeline_event(ts, IoCValue::IoCGetCookie(IoCGetCookie { cookie: "sid=1".to_string() }), IoCType::GetCookie, None, false);
+        let f = rules.evaluate_sequences(&[
+            request(0, "https://bait.com/a", "bait.com", false),
+            cookie(10),
+            request(200, "https://bait.com/b", "bait.com", false),
+            request(500, "https://evil.com/c", "evil.com", true),
+        ]);
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].poc, "[+0ms] get_cookie: sid=1; [+490ms] http_request: GET https://evil.com/c");
+
+        let f = rules.evaluate_sequences(&[cookie(0), request(1500, "https://evil.com/c", "evil.com", true)]);
+        assert!(f.is_empty());
+    }
+
+    #[test]
+    fn test_sequence_repeated_step_on_same_host() {
+        let rules = RuleSet::from_yaml(r#"
+sequences:
+  - id: listener-then-beacons
+    title: listener then beacons
+    severity: High
+    within_ms: 10000
+    steps:
+      - ioc_type: add_event_listener
+      - ioc_type: http_request
+        min_count: 3
+        same_host: true
+"#).unwrap();
+        let listener = timeline_event(0, IoCValue::IoCAddEventListener(IoCAddEventListener { listener: "keydown".to_string(), target: "document".to_string() }), IoCType::AddEventListener, None, false);
+        let f = rules.evaluate_sequences(&[
+            listener.clone(),
+            request(1000, "https://c2.com/k", "c2.com", true),
+            request(1500, "https://cdn.com/x", "cdn.com", true),
+            request(2000, "https://c2.com/k", "c2.com", false),
+            request(3000, "https://c2.com/k", "c2.com", false),
+        ]);
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].poc.matches("c2.com").count(), 3);
+        // a first party request before the beacons does not lock the step on its host
+        let f = rules.evaluate_sequences(&[
+            listener.clone(),
+            request(500, "https://bait.com/api", "bait.com", false),
+            request(1000, "https://c2.evil.com/k", "c2.evil.com", true),
+            request(2000, "https://c2.evil.com/k", "c2.evil.com", false),
+            request(3000, "https://c2.evil.com/k", "c2.evil.com", false),
+        ]);
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].poc.matches("c2.evil.com").count(), 3);
+        assert!(!f[0].poc.contains("bait.com/api"));
+        let f = rules.evaluate_sequences(&[
+            listener,
+            request(1000, "https://c2.com/k", "c2.com", true),
+            request(1500, "https://cdn.com/x", "cdn.com", true),
+        ]);
+        assert!(f.is_empty());
+    }
+
+    #[test]
+    fn test_shipped_key_listener_rule_skips_first_party_requests() {
+        let path = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/config/dynamic_rules.yaml"));
+        let rules = RuleSet::load(path).unwrap();
+        let listener = timeline_event(0, IoCValue::IoCAddEventListener(IoCAddEventListener { listener: "input".to_string(), target: "document".to_string() }), IoCType::AddEventListener, None, false);
+        let api = |ts| request(ts, "https://api.bait.com/search", "api.bait.com", false);
+        let f = rules.evaluate_sequences(&[listener.clone(), api(1000), api(2000), api(3000)]);
+        assert!(f.is_empty());
+        let beacon = |ts| request(ts, "https://c2.evil.com/k", "c2.evil.com", false);
+        let f = rules.evaluate_sequences(&[listener, api(500), beacon(1000), beacon(2000), beacon(3000)]);
+        assert_eq!(f.len(), 1);
+    }
+
+    #[test]
+    fn test_shipped_rules_file_is_valid() {
+        let path = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/config/dynamic_rules.yaml"));
+        assert!(RuleSet::load(path).unwrap().count() > 0);
+    }
+}
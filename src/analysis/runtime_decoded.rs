@@ -0,0 +1,91 @@
+use log::{debug, warn};
+
+use crate::analysis::analyzer::{Finding, SastAnalyze};
+use crate::analysis::dast_ioc_types::{IoC, IoCFunctionCall};
+use crate::analysis::sast::SastAnalyzer;
+use crate::store::models::FileAnalysisReport;
+use crate::utils;
+
+pub const RUNTIME_DECODED_TAG: &str = "runtime-decoded";
+
+// larger payloads are usually bundled libraries, not worth parsing on every call
+const MAX_RUNTIME_PAYLOAD_LEN: usize = 1024 * 1024;
+
+// the code a call hands to the javascript engine, after every layer of
+// obfuscation has been removed by the sample itself
+pub fn decoded_sources(call: &IoCFunctionCall) -> Vec<String> {
+    let payload = match call.arguments.first() {
+        Some(_p) if !_p.trim().is_empty() && _p.len() <= MAX_RUNTIME_PAYLOAD_LEN => _p,
+        _ => return Vec::new()
+    };
+    match call.callee.as_str() {
+        "window.eval" | "window.execScript" => vec![payload.clone()],
+        // written html runs its inline scripts
+        "document.write" => utils::inline_scripts(payload),
+        _ => Vec::new()
+    }
+}
+
+// static findings of the code decoded at runtime, attributed to the call that ran it
+pub fn runtime_decoded_findings(file_report: &FileAnalysisReport, ioc: &IoC, call: &IoCFunctionCall) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = Vec::new();
+    for _source in decoded_sources(call) {
+        let mut script_report = file_report.clone();
+        script_report.file_extension = "js".to_string();
+        let mut static_analyser = SastAnalyzer::new();
+        match static_analyser.analyze(script_report, _source.into_bytes()) {
+            Ok(_f) => findings.extend(_f.into_iter().map(|mut f| {
+                f.executed_on = ioc.executed_on.clone();
+                f.timestamp = ioc.timestamp;
+                f.ioc = ioc.value.clone();
+                f.tags.push(RUNTIME_DECODED_TAG.to_string());
+                f
+            })),
+            Err(e) => warn!("could not analyse the code passed to {}: {}", call.callee, e)
+        }
+    }
+    debug!("{} findings in the code passed to {}", findings.len(), call.callee);
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyzer::AnalysisType;
+    use crate::analysis::dast_ioc_types::{IoCType, IoCValue};
+
+    fn call(callee: &str, argument: &str) -> IoC {
+        IoC {
+            ioc_type: IoCType::FunctionCall,
+            timestamp: 1750728000120,
+            executed_on: "https://bait.com".to_string(),
+            value: IoCValue::IoCFunctionCall(IoCFunctionCall {
+                callee: callee.to_string(),
+                arguments: vec![argument.to_string()]
+            })
+        }
+    }
+
+    #[test]
+    fn test_runtime_decoded_code_is_analysed_statically() {
+        let report = FileAnalysisReport::empty();
+        let eval = call("window.eval", "window.execScript('x'); document.write('<iframe src=' + u + '>')");
+        let findings = match &eval.value {
+            IoCValue::IoCFunctionCall(_v) => runtime_decoded_findings(&report, &eval, _v),
+            _ => unreachable!()
+        };
+        assert_eq!(findings.len(), 2);
+        assert!(findings.iter().all(|f| f.r#type == AnalysisType::Static
+            && f.tags == vec![RUNTIME_DECODED_TAG.to_string()]
+            && f.executed_on == "https://bait.com"
+            && f.timestamp == 1750728000120));
+
+        let written = IoCFunctionCall {
+            callee: "document.write".to_string(),
+            arguments: vec!["<p>hi</p><script src=a.js></script><script>window.eval(p)</script>".to_string()]
+        };
+        assert_eq!(decoded_sources(&written), vec!["window.eval(p)".to_string()]);
+        let other = IoCFunctionCall { callee: "window.localStorage.getItem".to_string(), arguments: vec!["x".to_string()] };
+        assert!(decoded_sources(&other).is_empty());
+    }
+}
@@ -174,9 +174,11 @@ impl<'a> analyzer::SastAnalyze<'a> for SastAnalyzer {
                 r#type: analyzer::AnalysisType::Static,
                 ioc: super::dast_ioc_types::IoCValue::None,
                 executed_on: "".to_string(),
+                timestamp: 0,
                 poc: _it.poc.clone(),
                 severity: _it.severity.clone(),
-                title: _it.title.to_string()
+                title: _it.title.to_string(),
+                tags: Vec::new()
             }
         }).collect();
         findings.append(&mut _interesting_findings);
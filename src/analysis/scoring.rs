@@ -0,0 +1,347 @@
+use core::fmt;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use log::debug;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::analyzer::{Finding, Severity};
+use crate::analysis::baseline::BASELINE_TAG;
+
+// findings without a scoring rule fall in this category
+const DEFAULT_CATEGORY: &str = "other";
+const MAX_SCORE: f64 = 100.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Clean,
+    Suspicious,
+    Malicious,
+    // nothing was analysed yet
+    #[default]
+    Unknown
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Verdict::Clean => write!(f, "clean"),
+            Verdict::Suspicious => write!(f, "suspicious"),
+            Verdict::Malicious => write!(f, "malicious"),
+            Verdict::Unknown => write!(f, "unknown")
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScoringRuleDef {
+    // regex matched against the whole finding title
+    pub title: String,
+    pub category: String,
+    pub weight: f64,
+    // how often the finding really means malicious behaviour, 0 to 1
+    #[serde(default = "full_confidence")]
+    pub confidence: f64
+}
+
+// points of the findings without a scoring rule, by severity
+#[derive(Deserialize, Debug, Clone)]
+pub struct SeverityWeightsDef {
+    pub low: f64,
+    pub moderate: f64,
+    pub high: f64,
+    pub very_high: f64
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScoringDef {
+    pub suspicious_threshold: f64,
+    pub malicious_threshold: f64,
+    // every repeat of a finding is worth this fraction of the previous one
+    pub repeat_decay: f64,
+    pub default_weights: SeverityWeightsDef,
+    pub default_confidence: f64,
+    // maximum points of a category, categories not listed are only capped by the total
+    #[serde(default)]
+    pub category_caps: HashMap<String, f64>,
+    #[serde(default)]
+    pub rules: Vec<ScoringRuleDef>,
+    // findings the control run of the bait website also raised are worth
+    // this fraction of their points
+    #[serde(default = "default_baseline_factor")]
+    pub baseline_factor: f64
+}
+
+fn full_confidence() -> f64 {
+    1.0
+}
+
+fn default_baseline_factor() -> f64 {
+    0.1
+}
+
+// how much a group of identical findings added to the score
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Contribution {
+    pub title: String,
+    pub category: String,
+    pub occurrences: usize,
+    pub points: f64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct RiskAssessment {
+    // 0 to 100
+    pub score: f64,
+    pub verdict: Verdict,
+    pub explanation: String,
+    pub contributions: Vec<Contribution>
+}
+
+#[derive(Debug, Clone)]
+struct ScoringRule {
+    title: Regex,
+    category: String,
+    weight: f64,
+    confidence: f64
+}
+
+#[derive(Debug, Clone)]
+pub struct ScoringModel {
+    def: ScoringDef,
+    rules: Vec<ScoringRule>
+}
+
+fn round(v: f64) -> f64 {
+    (v * 10.0).round() / 10.0
+}
+
+impl ScoringModel {
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        let def: ScoringDef = match serde_yaml::from_str(yaml) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("invalid scoring file: {}", e))
+        };
+        if !(0.0..=1.0).contains(&def.repeat_decay) {
+            return Err("repeat_decay must be between 0 and 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&def.baseline_factor) {
+            return Err("baseline_factor must be between 0 and 1".to_string());
+        }
+        if def.suspicious_threshold > def.malicious_threshold {
+            return Err("suspicious_threshold must not be above malicious_threshold".to_string());
+        }
+        let mut rules: Vec<ScoringRule> = Vec::new();
+        for _r in def.rules.iter() {
+            let title = match Regex::new(format!("^(?:{})$", _r.title).as_str()) {
+                Ok(t) => t,
+                Err(e) => return Err(format!("invalid title {:?}: {}", _r.title, e))
+            };
+            if !(0.0..=1.0).contains(&_r.confidence) {
+                return Err(format!("confidence of {:?} must be between 0 and 1", _r.title));
+            }
+            rules.push(ScoringRule {
+                title,
+                category: _r.category.clone(),
+                weight: _r.weight,
+                confidence: _r.confidence
+            });
+        }
+        Ok(ScoringModel { def, rules })
+    }
+
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        match fs::read_to_string(&path) {
+            Ok(s) => Self::from_yaml(&s),
+            Err(e) => Err(format!("could not read {:?}: {}", path, e))
+        }
+    }
+
+    // category and points of a single occurrence of the finding
+    fn value_of(&self, finding: &Finding) -> (String, f64) {
+        let (category, value) = self.rule_value_of(finding);
+        match finding.tags.iter().any(|t| t == BASELINE_TAG) {
+            true => (category, value * self.def.baseline_factor),
+            false => (category, value)
+        }
+    }
+
+    fn rule_value_of(&self, finding: &Finding) -> (String, f64) {
+        match self.rules.iter().find(|r| r.title.is_match(&finding.title)) {
+            Some(_r) => (_r.category.clone(), _r.weight * _r.confidence),
+            None => {
+                debug!("no scoring rule for {:?}, scored as {}", finding.title, DEFAULT_CATEGORY);
+                let weight = match finding.severity {
+                    Severity::Low => self.def.default_weights.low,
+                    Severity::Moderate => self.def.default_weights.moderate,
+                    Severity::High => self.def.default_weights.high,
+                    Severity::VeryHigh => self.def.default_weights.very_high
+                };
+                (DEFAULT_CATEGORY.to_string(), weight * self.def.default_confidence)
+            }
+        }
+    }
+
+    // `analysed` is false while no analysis has produced results,
+    // the verdict is unknown instead of clean then
+    pub fn assess(&self, findings: &[Finding], analysed: bool) -> RiskAssessment {
+        // identical findings (same title) are grouped, each repeat is worth less
+        let mut contributions: Vec<Contribution> = Vec::new();
+        for _f in findings {
+            let (category, value) = self.value_of(_f);
+            match contributions.iter_mut().find(|c| c.title == _f.title) {
+                Some(_c) => {
+                    _c.points += value * self.def.repeat_decay.powi(_c.occurrences as i32);
+                    _c.occurrences += 1;
+                },
+                None => contributions.push(Contribution {
+                    title: _f.title.clone(),
+                    category,
+                    occurrences: 1,
+                    points: value
+                })
+            }
+        }
+        contributions.iter_mut().for_each(|c| c.points = round(c.points));
+        contributions.sort_by(|a, b| b.points.total_cmp(&a.points));
+
+        let mut categories: Vec<(String, f64, f64)> = Vec::new();
+        for _c in contributions.iter() {
+            match categories.iter_mut().find(|(name, _, _)| *name == _c.category) {
+                Some(_cat) => _cat.1 += _c.points,
+                None => {
+                    let cap = self.def.category_caps.get(&_c.category).copied().unwrap_or(MAX_SCORE);
+                    categories.push((_c.category.clone(), _c.points, cap));
+                }
+            }
+        }
+        let total: f64 = categories.iter().map(|(_, points, cap)| points.min(*cap)).sum();
+        let score = round(total.min(MAX_SCORE));
+
+        let verdict = if !analysed {
+            Verdict::Unknown
+        } else if score >= self.def.malicious_threshold {
+            Verdict::Malicious
+        } else if score >= self.def.suspicious_threshold {
+            Verdict::Suspicious
+        } else {
+            Verdict::Clean
+        };
+
+        let mut lines: Vec<String> = vec![format!("{} with a score of {}/100", verdict, score)];
+        for (name, points, cap) in categories.iter() {
+            let capped = match points > cap {
+                true => format!(" (capped at {})", cap),
+                false => String::new()
+            };
+            lines.push(format!("{}: {}{}", name, round(*points), capped));
+            for _c in contributions.iter().filter(|c| c.category == *name) {
+                lines.push(format!("  +{} {} (x{})", _c.points, _c.title, _c.occurrences));
+            }
+        }
+
+        RiskAssessment {
+            score,
+            verdict,
+            explanation: lines.join("\n"),
+            contributions
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyzer::AnalysisType;
+    use crate::analysis::dast_ioc_types::IoCValue;
+
+    const SCORING: &str = r#"
+suspicious_threshold: 20
+malicious_threshold: 60
+repeat_decay: 0.5
+default_confidence: 0.5
+default_weights: {low: 2, moderate: 10, high: 20, very_high: 40}
+category_caps: {code_execution: 30}
+rules:
+  - {title: "window.eval was called", category: code_execution, weight: 20}
+  - {title: "http request sent containing .* data", category: exfiltration, weight: 70, confidence: 0.9}
+"#;
+
+    fn finding(title: &str, severity: Severity) -> Finding {
+        Finding {
+            r#type: AnalysisType::Dynamic,
+            executed_on: "https://bait.com".to_string(),
+            timestamp: 0,
+            severity,
+            poc: "".to_string(),
+            ioc: IoCValue::None,
+            title: title.to_string(),
+            tags: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_repeats_have_diminishing_returns_and_categories_are_capped() {
+        let model = ScoringModel::from_yaml(SCORING).unwrap();
+        let evals: Vec<Finding> = (0..20).map(|_| finding("window.eval was called", Severity::VeryHigh)).collect();
+        let a = model.assess(&evals, true);
+        assert_eq!(a.contributions.len(), 1);
+        assert_eq!(a.contributions[0].occurrences, 20);
+        assert_eq!(a.contributions[0].points, 40.0);
+        // 40 points, capped by the category
+        assert_eq!(a.score, 30.0);
+        assert_eq!(a.verdict, Verdict::Suspicious);
+        assert!(a.explanation.contains("capped at 30"));
+    }
+
+    #[test]
+    fn test_verdicts() {
+        let model = ScoringModel::from_yaml(SCORING).unwrap();
+        assert_eq!(model.assess(&[], false).verdict, Verdict::Unknown);
+        assert_eq!(model.assess(&[], true).verdict, Verdict::Clean);
+        let leak = finding("http request sent containing credit card data", Severity::VeryHigh);
+        let a = model.assess(&[leak, finding("something new", Severity::Moderate)], true);
+        assert_eq!(a.score, 68.0);
+        assert_eq!(a.verdict, Verdict::Malicious);
+        assert_eq!(a.contributions[1].category, "other");
+    }
+
+    #[test]
+    fn test_shipped_scoring_file_is_valid() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("config/scoring.yaml");
+        let model = ScoringModel::load(path).unwrap();
+        // every finding the analysers raise has its own rule
+        let titles = [
+            "file matches a known malware hash",
+            "known malware file was downloaded",
+            "malicious file was downloaded",
+            "http request sent containing credit card data",
+            "keylogger sent captured keystrokes",
+            "form submission was hijacked to exfiltrate data",
+            "form action was rewritten to another domain",
+            "lookalike domain of paypal.com was contacted",
+            "requests sent to the same host at regular intervals",
+            "unknown domain looks generated or abusive",
+            "private or reserved ip range was contacted",
+            "ip address was contacted directly",
+            "hidden iframe injected into the page",
+            "form posting to another domain injected into the page",
+            "external script injected into the page",
+            "sensitive cookie was read",
+            "sensitive session storage entry was read",
+            "cookie overwrote a session identifier",
+            "long lived tracking cookie was set",
+            "console output contains planted sensitive data",
+            "execution of known suspicious commands",
+            "html element adhoc write to dom",
+            crate::analysis::site_differential::PARTIAL_ACTIVITY_TITLE,
+            crate::analysis::site_differential::TARGETED_BEHAVIOUR_TITLE
+        ];
+        for _t in titles {
+            assert!(model.rules.iter().any(|r| r.title.is_match(_t)), "no scoring rule for {:?}", _t);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::analysis::dast_ioc_types::{IoC, IoCValue};
+use crate::analysis::domain_intel::DomainIntel;
+use crate::utils;
+
+const JAVASCRIPT_CONTENT_TYPES: [&str; 4] = [
+    "text/javascript",
+    "application/javascript",
+    "application/x-javascript",
+    "application/ecmascript"
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadKind {
+    Script,
+    Html
+}
+
+impl PayloadKind {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PayloadKind::Script => "js",
+            PayloadKind::Html => "html"
+        }
+    }
+}
+
+// a payload fetched by the sample that is worth its own analysis
+#[derive(Debug, Clone)]
+pub struct SecondStage {
+    pub source_url: String,
+    // how it got into the page: script element, http response or download
+    pub origin: String,
+    pub kind: PayloadKind,
+    pub content: Vec<u8>,
+    pub sha256: String
+}
+
+impl SecondStage {
+    // file name of the child report, the last path segment of the url
+    pub fn file_name(&self) -> String {
+        let path = self.source_url.split(['?', '#']).next().unwrap_or("");
+        let name = path.rsplit('/').next().unwrap_or("");
+        let extension = format!(".{}", self.kind.extension());
+        match name.is_empty() {
+            true => format!("{}{}", self.sha256, extension),
+            false if name.ends_with(&extension) => name.to_string(),
+            false => format!("{}{}", name, extension)
+        }
+    }
+
+    // the javascript to analyse statically: the payload itself or the inline scripts of a page
+    pub fn script_source(&self) -> Option<String> {
+        let text = String::from_utf8(self.content.clone()).ok()?;
+        match self.kind {
+            PayloadKind::Script => Some(text),
+            PayloadKind::Html => {
+                let scripts = utils::inline_scripts(&text);
+                match scripts.is_empty() {
+                    true => None,
+                    false => Some(scripts.join(";\n"))
+                }
+            }
+        }
+    }
+}
+
+fn path_ends_with(url: &str, extensions: &[&str]) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or("").to_lowercase();
+    extensions.iter().any(|e| path.ends_with(e))
+}
+
+fn same_page(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+// responses served by the bait website's own domain are its first party
+// resources, not payloads of the sample
+fn is_first_party(domain_intel: &DomainIntel, url: &str, executed_on: &str) -> bool {
+    let bait_domain = domain_intel.classify_url(executed_on).map(|h| h.registrable_domain);
+    match domain_intel.classify_url(url) {
+        Some(_h) => bait_domain.as_ref() == Some(&_h.registrable_domain),
+        // relative urls
+        None => true
+    }
+}
+
+// scripts and pages the sample loaded or downloaded. The bait websites and
+// their first party resources are skipped, every payload is returned once
+pub fn extract_second_stages(domain_intel: &DomainIntel, iocs: &[IoC]) -> Vec<SecondStage> {
+    let script_elements: Vec<&str> = iocs
+        .iter()
+        .filter_map(|i| match &i.value {
+            IoCValue::IoCNewNetworkHtmlElement(_v) if _v.element_type.eq_ignore_ascii_case("script") => Some(_v.src.as_str()),
+            _ => None
+        })
+        .collect();
+
+    let mut stages: Vec<SecondStage> = Vec::new();
+    for _i in iocs {
+        let (source_url, origin, kind, content) = match &_i.value {
+            IoCValue::IoCHttpResponse(_v) if !_v.data.is_empty() && _v.status.starts_with('2')
+                && !is_first_party(domain_intel, &_v.url, &_i.executed_on) => {
+                let kind = if JAVASCRIPT_CONTENT_TYPES.contains(&_v.content_type.as_str())
+                    || (_v.content_type != "text/html" && path_ends_with(&_v.url, &[".js", ".mjs"])) {
+                    PayloadKind::Script
+                } else if _v.content_type == "text/html" && !same_page(&_v.url, &_i.executed_on) {
+                    PayloadKind::Html
+                } else {
+                    continue;
+                };
+                let origin = match script_elements.iter().any(|s| same_page(s, &_v.url)) {
+                    true => "script element",
+                    false => "http response"
+                };
+                (_v.url.clone(), origin, kind, _v.data.as_bytes().to_vec())
+            },
+            IoCValue::IoCSuspiciousFileDownload(_v) if !_v.data.is_empty() => {
+                let extension = _v.extension.to_lowercase();
+                let kind = if extension.contains("javascript") || extension == "ecmascript" || path_ends_with(&_v.url, &[".js", ".mjs"]) {
+                    PayloadKind::Script
+                } else if extension == "html" || path_ends_with(&_v.url, &[".html", ".htm"]) {
+                    PayloadKind::Html
+                } else {
+                    continue;
+                };
+                (_v.url.clone(), "download", kind, _v.data.clone())
+            },
+            _ => continue
+        };
+        let sha256 = sha256::digest(&content).to_string();
+        if stages.iter().any(|s| s.sha256 == sha256) {
+            continue;
+        }
+        stages.push(SecondStage {
+            source_url,
+            origin: origin.to_string(),
+            kind,
+            content,
+            sha256
+        });
+    }
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::analysis::dast_ioc_types::{IoCHttpResponse, IoCNewNetworkHtmlElement, IoCSuspiciousFileDownload, IoCType};
+
+    fn ioc(ioc_type: IoCType, value: IoCValue) -> IoC {
+        IoC {
+            ioc_type,
+            timestamp: 0,
+            executed_on: "https://bait.com/".to_string(),
+            value
+        }
+    }
+
+    fn response(url: &str, content_type: &str, data: &str) -> IoC {
+        ioc(IoCType::HttpResponse, IoCValue::IoCHttpResponse(IoCHttpResponse {
+            status: "200".to_string(),
+            url: url.to_string(),
+            data: data.to_string(),
+            content_type: content_type.to_string()
+        }))
+    }
+
+    #[test]
+    fn test_second_stages_are_extracted_once() {
+        let iocs = vec![
+            ioc(IoCType::NewNetworkHtmlElement, IoCValue::IoCNewNetworkHtmlElement(IoCNewNetworkHtmlElement {
+                element_type: "SCRIPT".to_string(),
+                src: "https://cdn.evil.com/stage2.js".to_string()
+            })),
+            response("https://bait.com", "text/html", "<html>bait</html>"),
+            // first party resources of the bait website
+            response("https://static.bait.com/app.js", "application/javascript", "init()"),
+            response("https://bait.com/account", "text/html", "<html>account</html>"),
+            response("https://cdn.evil.com/stage2.js", "application/javascript", "eval(atob('x'))"),
+            // the same payload again
+            response("https://cdn.evil.com/stage2.js?v=2", "application/javascript", "eval(atob('x'))"),
+            response("https://evil.com/api", "application/json", "{}"),
+            response("https://evil.com/sample.js", "text/plain", "sample"),
+            ioc(IoCType::SuspiciousFileDownload, IoCValue::IoCSuspiciousFileDownload(IoCSuspiciousFileDownload {
+                url: "https://evil.com/drop".to_string(),
+                extension: "html".to_string(),
+                data: b"<script src=x></script><script>document.write(1)</script>".to_vec()
+            }))
+        ];
+        let domain_intel = DomainIntel::new(PathBuf::from("./public_suffix.txt"), Vec::new(), Vec::new()).unwrap();
+        let stages = extract_second_stages(&domain_intel, &iocs);
+        assert_eq!(stages.len(), 3);
+        assert_eq!(stages[0].origin, "script element");
+        assert_eq!(stages[0].kind, PayloadKind::Script);
+        assert_eq!(stages[0].file_name(), "stage2.js");
+        // scripts are recognized by their path when served as text
+        assert_eq!(stages[1].file_name(), "sample.js");
+        assert_eq!(stages[2].origin, "download");
+        assert_eq!(stages[2].file_name(), "drop.html");
+        assert_eq!(stages[2].script_source().unwrap(), "document.write(1)");
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::analyzer::{AnalysisType, Finding, Severity};
+use crate::analysis::baseline::BASELINE_TAG;
+use crate::analysis::dast_ioc_types::IoCValue;
+
+pub const TARGETED_BEHAVIOUR_TITLE: &str = "behaviour only triggered on some bait websites";
+pub const PARTIAL_ACTIVITY_TITLE: &str = "sample was only active on some bait websites";
+
+// behaviours (titles of the findings raised from its events) of one bait website
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SiteBehaviour {
+    pub executed_on: String,
+    pub behaviours: Vec<String>,
+    pub findings: usize
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TargetedBehaviour {
+    pub title: String,
+    pub severity: Severity,
+    pub triggered_on: Vec<String>,
+    pub not_triggered_on: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SiteComparison {
+    pub sites: Vec<SiteBehaviour>,
+    // behaviours seen on some of the sites only
+    pub targeted_behaviours: Vec<TargetedBehaviour>,
+    pub active_on: Vec<String>,
+    // sites where the sample did nothing worth a finding
+    pub dormant_on: Vec<String>
+}
+
+fn same_site(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/').eq_ignore_ascii_case(b.trim_end_matches('/'))
+}
+
+pub fn is_differential(finding: &Finding) -> bool {
+    finding.title == TARGETED_BEHAVIOUR_TITLE || finding.title == PARTIAL_ACTIVITY_TITLE
+}
+
+fn site_list(sites: &[String]) -> String {
+    match sites.is_empty() {
+        true => "-".to_string(),
+        false => sites.join(", ")
+    }
+}
+
+// what every site triggered. `sites` are the bait websites the sample ran on,
+// sites only known from the findings are added
+pub fn compare_sites(sites: &[String], findings: &[Finding]) -> SiteComparison {
+    let behaviours: Vec<&Finding> = findings
+        .iter()
+        .filter(|f| f.is_from_events() && !is_differential(f) && !f.executed_on.is_empty())
+        // the bait website does the same without the sample
+        .filter(|f| !f.tags.iter().any(|t| t == BASELINE_TAG))
+        .collect();
+    let mut comparison = SiteComparison::default();
+    for _s in sites.iter().chain(behaviours.iter().map(|f| &f.executed_on)) {
+        if comparison.sites.iter().any(|s| same_site(&s.executed_on, _s)) {
+            continue;
+        }
+        let site_findings: Vec<&&Finding> = behaviours.iter().filter(|f| same_site(&f.executed_on, _s)).collect();
+        let mut titles: Vec<String> = Vec::new();
+        for _f in site_findings.iter() {
+            if !titles.contains(&_f.title) {
+                titles.push(_f.title.clone());
+            }
+        }
+        match titles.is_empty() {
+            true => comparison.dormant_on.push(_s.clone()),
+            false => comparison.active_on.push(_s.clone())
+        }
+        comparison.sites.push(SiteBehaviour {
+            executed_on: _s.clone(),
+            behaviours: titles,
+            findings: site_findings.len()
+        });
+    }
+    if comparison.sites.len() < 2 {
+        return comparison;
+    }
+
+    let mut titles: Vec<&String> = Vec::new();
+    for _f in behaviours.iter() {
+        if !titles.contains(&&_f.title) {
+            titles.push(&_f.title);
+        }
+    }
+    for _t in titles {
+        let (triggered_on, not_triggered_on): (Vec<&SiteBehaviour>, Vec<&SiteBehaviour>) = comparison.sites
+            .iter()
+            .partition(|s| s.behaviours.contains(_t));
+        if not_triggered_on.is_empty() {
+            continue;
+        }
+        comparison.targeted_behaviours.push(TargetedBehaviour {
+            title: _t.clone(),
+            severity: behaviours
+                .iter()
+                .filter(|f| &f.title == _t)
+                .map(|f| f.severity.clone())
+                .fold(Severity::Low, |a, b| if b > a { b } else { a }),
+            triggered_on: triggered_on.iter().map(|s| s.executed_on.clone()).collect(),
+            not_triggered_on: not_triggered_on.iter().map(|s| s.executed_on.clone()).collect()
+        });
+    }
+    comparison
+}
+
+fn differential_finding(title: &str, severity: Severity, poc: String, executed_on: &str, findings: &[Finding]) -> Finding {
+    Finding {
+        r#type: AnalysisType::Dynamic,
+        executed_on: executed_on.to_string(),
+        timestamp: findings
+            .iter()
+            .filter(|f| f.is_from_events() && same_site(&f.executed_on, executed_on))
+            .map(|f| f.timestamp)
+            .min()
+            .unwrap_or(0),
+        severity,
+        poc,
+        ioc: IoCValue::None,
+        title: title.to_string(),
+        tags: Vec::new()
+    }
+}
+
+// a sample dormant on most sites, or doing more on some of them, checks where it runs
+pub fn differential_findings(comparison: &SiteComparison, findings: &[Finding]) -> Vec<Finding> {
+    let mut differential: Vec<Finding> = Vec::new();
+    if comparison.active_on.is_empty() || comparison.sites.len() < 2 {
+        return differential;
+    }
+    // sites legitimately differ (login walls, consent banners, geo blocks), so
+    // this alone is a weak signal
+    if !comparison.dormant_on.is_empty() {
+        differential.push(differential_finding(
+            PARTIAL_ACTIVITY_TITLE,
+            Severity::Moderate,
+            format!("active on {}, dormant on {}", site_list(&comparison.active_on), site_list(&comparison.dormant_on)),
+            &comparison.active_on[0],
+            findings));
+    }
+    // dormant sites are covered above, behaviours missing on active sites are reported one by one
+    for _t in comparison.targeted_behaviours.iter() {
+        let missing_on: Vec<String> = _t.not_triggered_on
+            .iter()
+            .filter(|s| comparison.active_on.contains(s))
+            .cloned()
+            .collect();
+        if missing_on.is_empty() {
+            continue;
+        }
+        differential.push(differential_finding(
+            TARGETED_BEHAVIOUR_TITLE,
+            if _t.severity >= Severity::High { Severity::High } else { Severity::Moderate },
+            format!("{} on {}, not on {}", _t.title, site_list(&_t.triggered_on), site_list(&missing_on)),
+            &_t.triggered_on[0],
+            findings));
+    }
+    differential
+}
+
+// replaces the differential findings of a report after new events were analysed
+pub fn apply(sites: &[String], findings: &mut Vec<Finding>) -> SiteComparison {
+    findings.retain(|f| !is_differential(f));
+    let comparison = compare_sites(sites, findings);
+    let mut differential = differential_findings(&comparison, findings);
+    findings.append(&mut differential);
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(executed_on: &str, title: &str, severity: Severity) -> Finding {
+        Finding {
+            r#type: AnalysisType::Dynamic,
+            executed_on: executed_on.to_string(),
+            timestamp: 10,
+            severity,
+            poc: String::new(),
+            ioc: IoCValue::None,
+            title: title.to_string(),
+            tags: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_sample_targeting_one_site() {
+        let sites = vec!["https://bank.com".to_string(), "https://shop.com".to_string(), "https://news.com".to_string()];
+        let mut findings = vec![
+            finding("https://bank.com/", "keylogger captured typed input", Severity::VeryHigh),
+            finding("https://bank.com", "cookies were read", Severity::Moderate),
+            finding("https://shop.com", "cookies were read", Severity::Moderate),
+            // static findings are the same everywhere
+            Finding { r#type: AnalysisType::Static, ..finding("", "eval", Severity::High) }
+        ];
+        let comparison = apply(&sites, &mut findings);
+        assert_eq!(comparison.active_on, vec!["https://bank.com".to_string(), "https://shop.com".to_string()]);
+        assert_eq!(comparison.dormant_on, vec!["https://news.com".to_string()]);
+        assert_eq!(comparison.sites[0].behaviours.len(), 2);
+        assert_eq!(comparison.targeted_behaviours.len(), 2);
+
+        let differential: Vec<&Finding> = findings.iter().filter(|f| is_differential(f)).collect();
+        assert_eq!(differential.len(), 2);
+        assert_eq!(differential[0].poc, "active on https://bank.com, https://shop.com, dormant on https://news.com");
+        assert_eq!(differential[0].severity, Severity::Moderate);
+        assert_eq!(differential[1].poc, "keylogger captured typed input on https://bank.com, not on https://shop.com");
+        assert_eq!(differential[1].severity, Severity::High);
+
+        // applied again after the next batch of events, nothing is duplicated
+        apply(&sites, &mut findings);
+        assert_eq!(findings.iter().filter(|f| is_differential(f)).count(), 2);
+        assert!(differential_findings(&compare_sites(&sites[..1], &findings[..2]), &findings).is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::analysis::analyzer::{Finding, Severity};
+use crate::analysis::dast_ioc_types::IoC;
+
+// a finding as shown next to the event that triggered it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelineFinding {
+    pub title: String,
+    pub severity: Severity,
+    pub poc: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelineEntry {
+    // time since the first event on the same bait website
+    pub offset_ms: u64,
+    pub ioc: IoC,
+    pub findings: Vec<TimelineFinding>
+}
+
+// everything the sample did on one bait website, in chronological order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SiteTimeline {
+    pub executed_on: String,
+    pub started_at: u64,
+    pub events: Vec<TimelineEntry>
+}
+
+// orders the events of an analysis per bait website and attaches every dynamic
+// finding to the event it was raised for. Findings do not reference events
+// directly, so an event matches when it has the same bait website and value.
+// Repeated identical events get one finding each instead of all of them on every event
+pub fn build_timeline(mut iocs: Vec<IoC>, findings: &[Finding]) -> Vec<SiteTimeline> {
+    iocs.sort_by_key(|i| i.timestamp);
+
+    let mut sites: Vec<SiteTimeline> = Vec::new();
+    let mut values: Vec<Vec<Value>> = Vec::new();
+    for ioc in iocs {
+        let index = match sites.iter().position(|s| s.executed_on == ioc.executed_on) {
+            Some(_i) => _i,
+            None => {
+                sites.push(SiteTimeline {
+                    executed_on: ioc.executed_on.clone(),
+                    started_at: ioc.timestamp,
+                    events: Vec::new()
+                });
+                values.push(Vec::new());
+                sites.len() - 1
+            }
+        };
+        values[index].push(serde_json::to_value(&ioc.value).unwrap_or(Value::Null));
+        let offset_ms = ioc.timestamp - sites[index].started_at;
+        sites[index].events.push(TimelineEntry {
+            offset_ms,
+            ioc,
+            findings: Vec::new()
+        });
+    }
+
+    for finding in findings.iter().filter(|f| f.is_from_events()) {
+        let index = match sites.iter().position(|s| s.executed_on == finding.executed_on) {
+            Some(_i) => _i,
+            None => continue
+        };
+        let value = serde_json::to_value(&finding.ioc).unwrap_or(Value::Null);
+        let entry = sites[index].events
+            .iter_mut()
+            .zip(values[index].iter())
+            .find(|(e, v)| **v == value
+                && !e.findings.iter().any(|f| f.title == finding.title && f.poc == finding.poc));
+        if let Some((_e, _)) = entry {
+            _e.findings.push(TimelineFinding {
+                title: finding.title.clone(),
+                severity: finding.severity.clone(),
+                poc: finding.poc.clone()
+            });
+        }
+    }
+    sites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyzer::AnalysisType;
+    use crate::analysis::dast_ioc_types::{IoCFunctionCall, IoCType, IoCValue};
+
+    fn call(executed_on: &str, timestamp: u64, callee: &str) -> IoC {
+        IoC {
+            ioc_type: IoCType::FunctionCall,
+            executed_on: executed_on.to_string(),
+            timestamp,
+            value: IoCValue::IoCFunctionCall(IoCFunctionCall {
+                callee: callee.to_string(),
+                arguments: vec![]
+            })
+        }
+    }
+
+    fn finding(executed_on: &str, callee: &str) -> Finding {
+        Finding {
+            r#type: AnalysisType::Dynamic,
+            executed_on: executed_on.to_string(),
+            timestamp: 0,
+            severity: Severity::VeryHigh,
+            poc: callee.to_string(),
+            ioc: call(executed_on, 0, callee).value,
+            title: format!("{} was called", callee),
+            tags: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_events_are_grouped_per_site_and_sorted() {
+        let iocs = vec![
+            call("https://b.com", 300, "window.eval"),
+            call("https://a.com", 200, "document.write"),
+            call("https://a.com", 100, "window.eval")
+        ];
+        let sites = build_timeline(iocs, &[]);
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].executed_on, "https://a.com");
+        assert_eq!(sites[0].events.len(), 2);
+        assert_eq!(sites[0].events[1].offset_ms, 100);
+        assert_eq!(sites[1].started_at, 300);
+    }
+
+    #[test]
+    fn test_findings_are_attached_once_per_matching_event() {
+        let iocs = vec![
+            call("https://a.com", 100, "window.eval"),
+            call("https://a.com", 200, "document.write"),
+            call("https://a.com", 300, "window.eval")
+        ];
+        let findings = vec![
+            finding("https://a.com", "window.eval"),
+            finding("https://a.com", "window.eval"),
+            finding("https://b.com", "document.write")
+        ];
+        let sites = build_timeline(iocs, &findings);
+        let counts: Vec<usize> = sites[0].events.iter().map(|e| e.findings.len()).collect();
+        assert_eq!(counts, vec![1, 0, 1]);
+    }
+}
@@ -1,11 +1,11 @@
 
-use std::{fs::create_dir_all, os, path::PathBuf, sync::Arc, time::{self}};
+use std::{collections::HashMap, fs::create_dir_all, os, path::PathBuf, sync::Arc, time::{self}};
 use async_std::{stream::StreamExt};
 use lapin::options::BasicAckOptions;
-use tokio::task;
+use tokio::{sync::Mutex, task};
 use axum::{
-    extract::{Multipart, Path},
-    http::StatusCode,
+    extract::{DefaultBodyLimit, Multipart, Path, Query},
+    http::{header, StatusCode},
     response::IntoResponse,
     routing::{delete, get, post},
     Extension,
@@ -25,17 +25,41 @@ pub mod types;
 
 use crate::{
     analysis::{
-        analyzer::{self, DastAnalyze, Finding, SastAnalyze, Severity},dast::DastAnalyzer, sast::{self}},
-    app::types::{EventsFromAnalysis, Response},
-    store::{self, models::FileAnalysisReport, StoreError},
+        aggregation, analyzer::{self, DastAnalyze, Finding, SastAnalyze, Severity}, baseline::{self, Baseline, BaselineConf}, canary::CanaryPlan, hash_intel, correlation, timeline, dast::DastAnalyzer, domain_intel::DomainIntel, rule_engine::RuleSet, sast::{self}, scoring::ScoringModel, second_stage::{self, PayloadKind, SecondStage}, site_differential, storage_access::SensitiveStorage, network_graph::NetworkGraphBuilder},
+    analysis::dast_ioc_types::{InvalidEvent, IoC, IoCType, IoCValue},
+    app::types::{EventsFromAnalysis, RawEventsFromAnalysis, Response},
+    bootstrap::analysis_conf::RecursiveAnalysisExtConf,
+    integrations::virus_total::VTClient,
+    store::{self, artifacts::{self, ArtifactFiles}, models::{AnalysisEvents, Artifact, BaselineRun, ConsoleLogEntry, FileAnalysisReport, RejectedEvent}, StoreError},
     utils
 };
 use store::Store;
 
+const DEFAULT_CONSOLE_LOGS_SEARCH_LIMIT: i64 = 50;
+const MAX_CONSOLE_LOGS_SEARCH_LIMIT: i64 = 500;
+// full MalwareBazaar dumps are a few hundred MB
+const MAX_KNOWN_HASHES_IMPORT_SIZE: usize = 1024 * 1024 * 1024;
+// password of the downloaded artifact zips unless ARTIFACTS_ZIP_PASSWORD is set,
+// the one malware repositories commonly use
+const DEFAULT_ARTIFACTS_ZIP_PASSWORD: &str = "infected";
+const ARTIFACT_RELATION_SAMPLE: &str = "sample";
+const ARTIFACT_RELATION_DOWNLOAD: &str = "download";
+// bounds the walk over parent reports in case of corrupted links
+const MAX_INFECTION_CHAIN_DEPTH: usize = 32;
+// file name of the control runs, they have no sample and no file hash
+const BASELINE_FILE_NAME: &str = "baseline";
+
 #[derive(Clone)]
 struct ApiContext {
     store: Arc<Store>,
-    queue: Arc<dyn 'static + Send + rabbitclient::RBMQ>
+    queue: Arc<dyn 'static + Send + rabbitclient::RBMQ>,
+    // used to re-analyse stored events, separate from the one of the queue consumer
+    dynamic_analyser: Arc<Mutex<DastAnalyzer>>,
+    scoring: Arc<ScoringModel>,
+    artifacts: Arc<ArtifactFiles>,
+    artifacts_zip_password: String,
+    network_graph: Arc<NetworkGraphBuilder>,
+    baseline: BaselineConf
 }
 
 pub trait AppMethods {
@@ -46,11 +70,32 @@ pub struct App {
     bindhost: String,
     store: Store,
     queue: Arc<dyn rabbitclient::RBMQ + Send + Sync>,
-    malsmug_dir: PathBuf
+    malsmug_dir: PathBuf,
+    domain_intel: Arc<DomainIntel>,
+    dynamic_rules: RuleSet,
+    scoring: Arc<ScoringModel>,
+    virus_total: Option<Arc<VTClient>>,
+    artifacts: Arc<ArtifactFiles>,
+    recursive_analysis: RecursiveAnalysisExtConf,
+    sensitive_storage: SensitiveStorage,
+    network_graph: Arc<NetworkGraphBuilder>,
+    baseline: BaselineConf
+}
+
+// everything the analyzers are built from, loaded from `config/analysis.yaml` on startup
+pub struct AnalysisComponents {
+    pub domain_intel: Arc<DomainIntel>,
+    pub dynamic_rules: RuleSet,
+    pub scoring: Arc<ScoringModel>,
+    pub virus_total: Option<Arc<VTClient>>,
+    pub recursive_analysis: RecursiveAnalysisExtConf,
+    pub sensitive_storage: SensitiveStorage,
+    pub network_graph: Arc<NetworkGraphBuilder>,
+    pub baseline: BaselineConf
 }
 
 impl App {
-    pub async fn new(h: String, q: Box<dyn rabbitclient::RBMQ + Send + Sync>) -> Self {
+    pub async fn new(h: String, q: Box<dyn rabbitclient::RBMQ + Send + Sync>, a: AnalysisComponents) -> Self {
         let home_dir = match home::home_dir() {
             Some(path) => {
                 path
@@ -66,13 +111,367 @@ impl App {
                 panic!("could not create home dir");
             }
         };
+        // samples and downloaded payloads, encrypted at rest when a key is set
+        let artifacts = match ArtifactFiles::new(
+            app_home_dir.join(PathBuf::from("artifacts")),
+            utils::get_env_var("ARTIFACTS_ENCRYPTION_KEY").filter(|k| !k.is_empty())) {
+                Ok(a) => a,
+                Err(e) => panic!("could not initialize the artifact store: {}", e)
+            };
         Self {
             bindhost: h.clone(),
             store,
             queue: Arc::from(q),
-            malsmug_dir: app_home_dir
+            malsmug_dir: app_home_dir,
+            domain_intel: a.domain_intel,
+            dynamic_rules: a.dynamic_rules,
+            scoring: a.scoring,
+            virus_total: a.virus_total,
+            artifacts: Arc::new(artifacts),
+            recursive_analysis: a.recursive_analysis,
+            sensitive_storage: a.sensitive_storage,
+            network_graph: a.network_graph,
+            baseline: a.baseline
+        }
+    }
+}
+
+// weighted score and verdict of the report findings. Reports nothing was
+// analysed or found for yet get an unknown verdict
+fn assess_file_report(scoring: &ScoringModel, file_report: &mut FileAnalysisReport) {
+    let analysed = file_report.has_started_analysis || file_report.static_analysis || !file_report.findings.is_empty();
+    file_report.assessment = scoring.assess(&file_report.findings, analysed);
+}
+
+// bait websites that published events for the analysis. Runs publish their
+// events one site at a time, sites still running are left out of the comparison
+fn analysed_sites(iocs: &[IoC]) -> Vec<String> {
+    let mut sites: Vec<String> = Vec::new();
+    for _i in iocs.iter() {
+        if !_i.executed_on.is_empty() && !sites.contains(&_i.executed_on) {
+            sites.push(_i.executed_on.clone());
+        }
+    }
+    sites
+}
+
+// compares what the sample did on every bait website and raises the site
+// differential findings. The severity of the report follows the other findings
+fn compare_bait_websites(file_report: &mut FileAnalysisReport, sites: &[String]) {
+    let comparison = site_differential::apply(sites, &mut file_report.findings);
+    if !comparison.targeted_behaviours.is_empty() {
+        info!("{:?} behaves differently on {} bait websites, dormant on {:?}",
+            file_report.file_name, comparison.sites.len(), comparison.dormant_on);
+    }
+    // the differential findings restate findings already counted, they do not
+    // raise the severity of the report on their own
+    let mut max_severity = Severity::Low;
+    for f in file_report.findings.iter().filter(|f| !site_differential::is_differential(f)) {
+        if f.severity > max_severity {
+            max_severity = f.severity.clone();
+        }
+    }
+    file_report.severity = max_severity as i64;
+}
+
+// identical findings are returned once with their occurrences, the individual
+// ones only when the evidence is requested
+fn file_report_response(mut file_report: FileAnalysisReport, evidence: bool) -> FileAnalysisReport {
+    file_report.aggregated_findings = aggregation::aggregate(&file_report.findings);
+    if !evidence {
+        file_report.findings = Vec::new();
+    }
+    file_report
+}
+
+// known malware gets a verdict before any analysis, nothing leaves the host
+async fn known_hash_findings(store: &Store, file_bytes: &[u8], file_name: &str) -> Vec<Finding> {
+    match store.db.hash_intel.find_known_hash(&hash_intel::digests(file_bytes)).await {
+        Ok(_k) => vec![Finding {
+            r#type: analyzer::AnalysisType::Static,
+            executed_on: "".to_string(),
+            timestamp: 0,
+            severity: Severity::VeryHigh,
+            poc: format!("{} {} is {} (source: {}, first seen: {})", _k.hash_type, _k.hash, _k.family, _k.source, _k.first_seen),
+            ioc: IoCValue::None,
+            title: "file matches a known malware hash".to_string(),
+            tags: Vec::new()
+        }],
+        Err(StoreError::NotFoundError) => Vec::new(),
+        Err(e) => {
+            error!("could not look up the hash of {:?}: {:?}", file_name, e);
+            Vec::new()
+        }
+    }
+}
+
+// sends the file to the sandbox through the core files queue
+async fn send_for_dynamic_analysis(queue: &dyn rabbitclient::RBMQ, file_for_analysis: types::FileForAnalysis) {
+    let mut file_for_analysis_buf: Vec<u8> = Vec::new();
+
+    // Serialize the FileForAnalysis to byte Vec
+    file_for_analysis.serialize(&mut Serializer::new(&mut file_for_analysis_buf)).unwrap();
+
+    // pulish the data
+    let _ = queue.publish(
+        queue.get_core_files_queue(),
+        queue.get_main_exchange(),
+        file_for_analysis_buf).await;
+
+    info!("file {:?} sent to queue for analysis", file_for_analysis.file_name);
+}
+
+// events of a batch that did not validate are kept with the reason, so a
+// broken hook in the sandbox shows up instead of silently losing events
+async fn reject_events(store: &Store, events: &EventsFromAnalysis, invalid: Vec<InvalidEvent>) {
+    warn!("{} events of analysis {:?} (schema version {}) are invalid", invalid.len(), events.analysis_id, events.schema_version);
+    let received_at = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(_) => 0
+    };
+    let rejected: Vec<RejectedEvent> = invalid
+        .into_iter()
+        .map(|i| RejectedEvent {
+            analysis_id: events.analysis_id.clone(),
+            file_hash: events.file_hash.clone(),
+            received_at,
+            schema_version: events.schema_version as i64,
+            event_index: i.index as i64,
+            ioc_type: i.ioc_type,
+            error: i.error,
+            event: i.event
+        })
+        .collect();
+    if let Err(e) = store.db.analysis_event.store_rejected_events(rejected).await {
+        error!("could not store the invalid events of {:?}: {:?}", events.analysis_id, e);
+    }
+}
+
+// control runs of the bait websites without a sample, requested when a website
+// has none or its last one is older than `max_age_hours`. The sandbox only
+// visits the website and reports what the site does on its own
+async fn request_baseline_runs(store: &Store, queue: &dyn rabbitclient::RBMQ, conf: &BaselineConf, bait_websites: &[String]) {
+    if !conf.enabled {
+        return;
+    }
+    let now = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(_) => 0
+    };
+    for _w in bait_websites {
+        match store.db.baseline.get_latest_baseline_run(_w, false).await {
+            // a pending run is not requested again before it gets as old
+            Ok(_r) if now - _r.requested_at < conf.max_age_hours as i64 * 3600 => continue,
+            Ok(_) | Err(StoreError::NotFoundError) => (),
+            Err(e) => {
+                error!("could not get the control runs of {:?}: {:?}", _w, e);
+                continue;
+            }
+        }
+        let analysis_id = Uuid::new_v4().to_string();
+        let run = BaselineRun {
+            analysis_id: analysis_id.clone(),
+            bait_website: _w.clone(),
+            requested_at: now,
+            completed_at: None
+        };
+        if let Err(e) = store.db.baseline.create_baseline_run(run).await {
+            error!("could not create control run of {:?}: {:?}", _w, e);
+            continue;
+        }
+        info!("requesting control run {:?} of {:?}", analysis_id, _w);
+        send_for_dynamic_analysis(queue, types::FileForAnalysis {
+            file_name: BASELINE_FILE_NAME.to_string(),
+            file_hash: String::new(),
+            analysis_id,
+            bait_websites: vec![_w.clone()],
+            file_bytes: Vec::new(),
+            canary_plan: CanaryPlan::generate()
+        }).await;
+    }
+}
+
+// events of the latest completed control run of every site
+async fn load_baselines(store: &Store, sites: &[String]) -> HashMap<String, Baseline> {
+    let mut baselines: HashMap<String, Baseline> = HashMap::new();
+    for _s in sites {
+        let run = match store.db.baseline.get_latest_baseline_run(_s, true).await {
+            Ok(r) => r,
+            Err(StoreError::NotFoundError) => {
+                debug!("no control run of {:?} yet", _s);
+                continue;
+            },
+            Err(e) => {
+                error!("could not get the control runs of {:?}: {:?}", _s, e);
+                continue;
+            }
+        };
+        match store.db.analysis_event.get_analysis_events(&run.analysis_id).await {
+            Ok(_e) => {
+                baselines.insert(_s.clone(), Baseline::from_iocs(&_e.iocs));
+            },
+            Err(e) => error!("could not get the events of control run {:?}: {:?}", run.analysis_id, e)
+        }
+    }
+    baselines
+}
+
+// lowers or drops the findings the bait websites raise without the sample,
+// the severity of the report is recomputed
+async fn subtract_baselines(store: &Store, conf: &BaselineConf, file_report: &mut FileAnalysisReport, sites: &[String]) {
+    if !conf.enabled {
+        return;
+    }
+    let baselines = load_baselines(store, sites).await;
+    if baselines.is_empty() {
+        return;
+    }
+    let canaries: Vec<String> = file_report.canary_plan.tokens().into_iter().map(|(t, _)| t).collect();
+    let matched = baseline::apply_baselines(&mut file_report.findings, &baselines, conf.suppress, &canaries);
+    if matched > 0 {
+        info!("{} findings of {:?} also happened in the control runs", matched, file_report.file_name);
+    }
+    let mut max_severity = Severity::Low;
+    for f in file_report.findings.iter() {
+        if f.severity > max_severity {
+            max_severity = f.severity.clone();
+        }
+    }
+    file_report.severity = max_severity as i64;
+}
+
+// a child report for a second stage payload, analysed statically right away
+async fn create_child_report(store: &Store, queue: &dyn rabbitclient::RBMQ, scoring: &ScoringModel, artifact_files: &ArtifactFiles,
+    conf: &RecursiveAnalysisExtConf, parent: &FileAnalysisReport, payload: SecondStage) {
+    let parent_uid = parent.uid.clone().unwrap_or_default();
+    let file_name = payload.file_name();
+    let analysis_uuid = Uuid::new_v4();
+    let canary_plan = CanaryPlan::generate();
+    // pages have no file the sandbox could inject, only scripts are analysed dynamically
+    let dynamic_analysis = conf.dynamic_analysis && payload.kind == PayloadKind::Script;
+    let mut child = FileAnalysisReport::new(
+        file_name.clone(),
+        payload.sha256.clone(),
+        file_name.clone(),
+        payload.kind.extension().to_string(),
+        analysis_uuid.to_string(),
+        false,
+        dynamic_analysis, true,
+        0, parent.bait_websites.clone(), known_hash_findings(store, &payload.content, &file_name).await);
+    child.canary_plan = canary_plan.clone();
+    child.parent_uid = Some(parent_uid.clone());
+    child.source_url = payload.source_url.clone();
+    child.depth = parent.depth + 1;
+
+    if let Some(_source) = payload.script_source() {
+        let mut static_analyser = sast::SastAnalyzer::new();
+        // the inline scripts of pages are parsed as javascript
+        let mut script_report = child.clone();
+        script_report.file_extension = PayloadKind::Script.extension().to_string();
+        match static_analyser.analyze(script_report, _source.into_bytes()) {
+            Ok(mut f) => child.findings.append(&mut f),
+            Err(e) => warn!("could not analyse second stage {:?} statically: {:?}", payload.source_url, e)
         }
     }
+    child.severity = child.findings.iter().map(|f| f.severity.clone() as i64).max().unwrap_or(0);
+    assess_file_report(scoring, &mut child);
+
+    let child = match store.db.file_analysis_report.create_file_report(child).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("child report of {:?} for {:?} was NOT saved. Error: {:?}", parent_uid, payload.source_url, e);
+            return;
+        }
+    };
+    let child_uid = child.uid.clone().unwrap_or_default();
+    info!("{} {:?} fetched by {:?} has child report {:?}", payload.origin, payload.source_url, parent_uid, child_uid);
+    store_artifact(store, artifact_files, &payload.content, &child_uid, ARTIFACT_RELATION_SAMPLE, &file_name).await;
+
+    if dynamic_analysis {
+        send_for_dynamic_analysis(queue, types::FileForAnalysis {
+            file_name,
+            file_hash: payload.sha256,
+            analysis_id: analysis_uuid.to_string(),
+            bait_websites: child.bait_websites,
+            file_bytes: payload.content,
+            canary_plan
+        }).await;
+    }
+}
+
+// scripts and pages fetched during the analysis become child reports of the
+// analysed one, up to the configured depth
+async fn create_child_reports(store: &Store, queue: &dyn rabbitclient::RBMQ, scoring: &ScoringModel, artifact_files: &ArtifactFiles,
+    conf: &RecursiveAnalysisExtConf, parent: &FileAnalysisReport, stages: Vec<SecondStage>) {
+    let parent_uid = match parent.uid.as_ref() {
+        Some(_uid) if conf.enabled && parent.depth < conf.max_depth => _uid,
+        _ => return
+    };
+    // events arrive in batches, payloads of earlier batches already have a child
+    let children = store.db.file_analysis_report.get_child_file_reports(parent_uid).await.unwrap_or_default();
+    let mut excluded_hashes: Vec<String> = children.iter().map(|c| c.file_hash.clone()).collect();
+    // a payload fetching one of its ancestors does not start the chain again
+    excluded_hashes.push(parent.file_hash.clone());
+    let mut ancestor_uid = parent.parent_uid.clone();
+    for _ in 0..parent.depth {
+        let ancestor = match ancestor_uid {
+            Some(_uid) => match store.db.file_analysis_report.get_file_report(&_uid).await {
+                Ok(a) => a,
+                Err(_) => break
+            },
+            None => break
+        };
+        excluded_hashes.push(ancestor.file_hash);
+        ancestor_uid = ancestor.parent_uid;
+    }
+
+    let room = conf.max_children.saturating_sub(children.len());
+    let payloads = stages.into_iter().filter(|s| !excluded_hashes.contains(&s.sha256));
+    for _payload in payloads.take(room) {
+        create_child_report(store, queue, scoring, artifact_files, conf, parent, _payload).await;
+    }
+}
+
+// keeps the content of a sample or downloaded payload and links it to the report
+async fn store_artifact(store: &Store, artifact_files: &ArtifactFiles, data: &[u8], file_report_uid: &str, relation: &str, name: &str) {
+    let blob = match artifact_files.put(data) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("could not store {} artifact of {:?}: {}", relation, file_report_uid, e);
+            return;
+        }
+    };
+    let created_at = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(_) => 0
+    };
+    let artifact = Artifact {
+        sha256: blob.sha256,
+        size: blob.size,
+        stored_size: blob.stored_size,
+        compression: artifacts::ARTIFACT_COMPRESSION.to_string(),
+        encryption: blob.encryption,
+        ref_count: 0,
+        created_at
+    };
+    match store.db.artifact.link_artifact(artifact, file_report_uid, relation, name).await {
+        Ok(a) => debug!("artifact {} linked to {:?}, {} references", a.sha256, file_report_uid, a.ref_count),
+        Err(e) => error!("could not link artifact to {:?}: {:?}", file_report_uid, e)
+    }
+}
+
+// artifacts no other report references are deleted with the report
+async fn unlink_artifacts(store: &Store, artifact_files: &ArtifactFiles, file_report_uid: &str) {
+    match store.db.artifact.unlink_file_report(file_report_uid).await {
+        Ok(unreferenced) => {
+            for _sha256 in unreferenced {
+                if let Err(e) = artifact_files.delete(&_sha256) {
+                    error!("{}", e);
+                }
+            }
+        },
+        Err(e) => error!("could not unlink the artifacts of {:?}: {:?}", file_report_uid, e)
+    }
 }
 
 fn error_response(e: StoreError) -> (StatusCode, axum::Json<Response>) {
@@ -101,13 +500,28 @@ fn error_response(e: StoreError) -> (StatusCode, axum::Json<Response>) {
 }
 
 async fn delete_file_reports_by_hash(Extension(ctx): Extension<ApiContext>, Path(file_hash): Path<String>) -> impl IntoResponse {
+    if let Err(e) = ctx.store.db.console_log.delete_console_logs_by_file_hash(&file_hash).await {
+        error!("could not delete console logs of {:?}: {:?}", file_hash, e);
+    }
+    if let Err(e) = ctx.store.db.analysis_event.delete_analysis_events_by_file_hash(&file_hash).await {
+        error!("could not delete analysis events of {:?}: {:?}", file_hash, e);
+    }
+    let mut children_deleted = 0;
+    if let Ok(_reports) = ctx.store.db.file_analysis_report.get_file_reports_by_file_hash(&file_hash).await {
+        for _report in _reports {
+            if let Some(_uid) = _report.uid {
+                unlink_artifacts(&ctx.store, &ctx.artifacts, &_uid).await;
+                children_deleted += delete_child_reports(&ctx.store, &ctx.artifacts, &_uid).await;
+            }
+        }
+    }
     let r = match ctx.store.db.file_analysis_report.delete_file_reports_by_hash(&file_hash).await {
         Ok(r) => {
             (StatusCode::OK, Json(
                     types::Response{
                             r:  types::Responses::DeleteFileReport(
                                     types::DeleteFileReport {
-                                        file_reports_deleted: r
+                                        file_reports_deleted: r + children_deleted
                                     }
                                 )
                             }
@@ -120,14 +534,54 @@ async fn delete_file_reports_by_hash(Extension(ctx): Extension<ApiContext>, Path
 }
 
 
+// removes the console logs, events and artifact links of a single report
+async fn delete_file_report_data(store: &Store, artifact_files: &ArtifactFiles, file_report: &FileAnalysisReport) {
+    let file_report_uid = file_report.uid.clone().unwrap_or_default();
+    if let Err(e) = store.db.console_log.delete_console_logs_by_file_report(&file_report.file_hash, &file_report_uid).await {
+        error!("could not delete console logs of {:?}: {:?}", file_report_uid, e);
+    }
+    if let Err(e) = store.db.analysis_event.delete_analysis_events_by_analysis_id(&file_report.last_analysis_id).await {
+        error!("could not delete analysis events of {:?}: {:?}", file_report_uid, e);
+    }
+    unlink_artifacts(store, artifact_files, &file_report_uid).await;
+}
+
+// child reports are deleted together with the report whose analysis fetched
+// them, so no report points to a missing parent. Returns how many were deleted
+async fn delete_child_reports(store: &Store, artifact_files: &ArtifactFiles, parent_uid: &str) -> u64 {
+    let mut deleted = 0;
+    let mut parents = vec![parent_uid.to_string()];
+    while let Some(_parent_uid) = parents.pop() {
+        let children = store.db.file_analysis_report.get_child_file_reports(&_parent_uid).await.unwrap_or_default();
+        for _child in children {
+            let child_uid = match _child.uid.clone() {
+                Some(_u) => _u,
+                None => continue
+            };
+            delete_file_report_data(store, artifact_files, &_child).await;
+            match store.db.file_analysis_report.delete_file_report(&child_uid).await {
+                Ok(r) => deleted += r,
+                Err(e) => error!("could not delete child report {:?} of {:?}: {:?}", child_uid, _parent_uid, e)
+            }
+            parents.push(child_uid);
+        }
+    }
+    deleted
+}
+
 async fn delete_file_report(Extension(ctx): Extension<ApiContext>, Path(file_report_uid): Path<String>) -> impl IntoResponse {
+    match ctx.store.db.file_analysis_report.get_file_report(&file_report_uid).await {
+        Ok(_report) => delete_file_report_data(&ctx.store, &ctx.artifacts, &_report).await,
+        Err(_) => unlink_artifacts(&ctx.store, &ctx.artifacts, &file_report_uid).await
+    }
+    let children_deleted = delete_child_reports(&ctx.store, &ctx.artifacts, &file_report_uid).await;
     let r = match ctx.store.db.file_analysis_report.delete_file_report(&file_report_uid).await {
         Ok(r) => {
             (StatusCode::OK, Json(
                     types::Response{
                             r:  types::Responses::DeleteFileReport(
                                     types::DeleteFileReport {
-                                        file_reports_deleted: r
+                                        file_reports_deleted: r + children_deleted
                                     }
                                 )
                             }
@@ -139,7 +593,7 @@ async fn delete_file_report(Extension(ctx): Extension<ApiContext>, Path(file_rep
     r
 }
 
-async fn get_file_reports(Extension(ctx): Extension<ApiContext>, Path(file_hash): Path<String>) -> impl IntoResponse {
+async fn get_file_reports(Extension(ctx): Extension<ApiContext>, Path(file_hash): Path<String>, Query(params): Query<types::GetFileReportsParams>) -> impl IntoResponse {
     let r = match ctx.store.db.file_analysis_report.get_file_reports_by_file_hash(file_hash.as_str()).await {
             Ok(r) => {
                 debug!("{} file analysis reports found", r.len());
@@ -148,6 +602,9 @@ async fn get_file_reports(Extension(ctx): Extension<ApiContext>, Path(file_hash)
                             r:  types::Responses::GetFileReports(
                                     types::GetFileReports {
                                         file_reports: r
+                                            .into_iter()
+                                            .map(|far| file_report_response(far, params.evidence))
+                                            .collect()
                                     }
                                 )
                             }
@@ -159,6 +616,467 @@ async fn get_file_reports(Extension(ctx): Extension<ApiContext>, Path(file_hash)
     r
 }
 
+async fn search_console_logs(Extension(ctx): Extension<ApiContext>, Query(params): Query<types::SearchConsoleLogsParams>) -> impl IntoResponse {
+    if params.q.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(types::Response{
+            r:  types::Responses::GenericErrorResponse (
+                    types::GenericErrorResponse { msg: "Empty search query".to_string() }
+                )
+        }))
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_CONSOLE_LOGS_SEARCH_LIMIT).clamp(1, MAX_CONSOLE_LOGS_SEARCH_LIMIT);
+    match ctx.store.db.console_log.search_console_logs(params.q.as_str(), params.file_hash.as_deref(), limit).await {
+            Ok(r) => {
+                debug!("{} console logs found for {:?}", r.len(), params.q);
+                (StatusCode::OK, Json(
+                    types::Response{
+                            r:  types::Responses::SearchConsoleLogs(
+                                    types::SearchConsoleLogs {
+                                        console_logs: r
+                                    }
+                                )
+                            }
+                        )
+                    )
+            }
+            Err(e) => error_response(e)
+    }
+}
+
+// reruns the dynamic analysis over the stored events of the last analysis of a
+// report and replaces its dynamic findings. Static findings are kept
+async fn reanalyse_file_report(store: &Store, dynamic_analyser: &Mutex<DastAnalyzer>, scoring: &ScoringModel, baseline: &BaselineConf, mut file_report: FileAnalysisReport) -> Result<FileAnalysisReport, StoreError> {
+    let file_report_uid = match file_report.uid.clone() {
+        Some(_u) => _u,
+        None => return Err(StoreError::NotFoundError)
+    };
+    if !file_report.dynamic_analysis {
+        return Err(StoreError::GenericError("dynamic analysis was not requested for this report".to_string()));
+    }
+    let events = store.db.analysis_event.get_analysis_events(file_report.last_analysis_id.as_str()).await?;
+    let sites = analysed_sites(&events.iocs);
+
+    let mut dynamic_findings = match dynamic_analyser.lock().await.analyze(file_report.clone(), events.iocs).await {
+        Ok(f) => f,
+        Err(e) => return Err(StoreError::GenericError(format!("could not analyse stored events: {:?}", e)))
+    };
+    info!("re-analysis found {} findings for {:?}", dynamic_findings.len(), file_report.file_name);
+
+    let mut findings: Vec<Finding> = file_report.findings
+        .into_iter()
+        .filter(|f| !f.is_from_events())
+        .collect();
+    findings.append(&mut dynamic_findings);
+    let mut max_severity = Severity::Low;
+    for f in findings.iter() {
+        if f.severity > max_severity {
+            max_severity = f.severity.clone();
+        }
+    }
+    file_report.severity = max_severity as i64;
+    file_report.findings = findings;
+    subtract_baselines(store, baseline, &mut file_report, &sites).await;
+    compare_bait_websites(&mut file_report, &sites);
+    assess_file_report(scoring, &mut file_report);
+    store.db.file_analysis_report.update_file_report(file_report_uid.as_str(), file_report).await
+}
+
+async fn reanalyse_file_report_by_uid(Extension(ctx): Extension<ApiContext>, Path(file_report_uid): Path<String>, Query(params): Query<types::GetFileReportsParams>) -> impl IntoResponse {
+    let file_report = match ctx.store.db.file_analysis_report.get_file_report(file_report_uid.as_str()).await {
+        Ok(r) => r,
+        Err(e) => return error_response(e)
+    };
+    match reanalyse_file_report(&ctx.store, &ctx.dynamic_analyser, &ctx.scoring, &ctx.baseline, file_report).await {
+        Ok(r) => {
+            (StatusCode::OK, Json(
+                types::Response{
+                        r:  types::Responses::GetFileReport(
+                                types::GetFileReport {
+                                    file_report: file_report_response(r, params.evidence)
+                                }
+                            )
+                        }
+                    )
+                )
+        },
+        Err(e) => error_response(e)
+    }
+}
+
+async fn reanalyse_file_reports_by_hash(Extension(ctx): Extension<ApiContext>, Path(file_hash): Path<String>, Query(params): Query<types::GetFileReportsParams>) -> impl IntoResponse {
+    let file_reports = match ctx.store.db.file_analysis_report.get_file_reports_by_file_hash(file_hash.as_str()).await {
+        Ok(r) => r,
+        Err(e) => return error_response(e)
+    };
+    let mut reanalysed: Vec<FileAnalysisReport> = Vec::new();
+    for far in file_reports.into_iter().filter(|far| far.dynamic_analysis) {
+        match reanalyse_file_report(&ctx.store, &ctx.dynamic_analyser, &ctx.scoring, &ctx.baseline, far.clone()).await {
+            Ok(r) => reanalysed.push(file_report_response(r, params.evidence)),
+            Err(e) => warn!("could not re-analyse file report {:?}: {:?}", far.uid, e)
+        }
+    }
+    (StatusCode::OK, Json(
+        types::Response{
+                r:  types::Responses::GetFileReports(
+                        types::GetFileReports {
+                            file_reports: reanalysed
+                        }
+                    )
+                }
+            )
+        )
+}
+
+// re-analyses every report with stored events in the background,
+// past runs can be many so the request does not wait for them
+async fn reanalyse_all_file_reports(Extension(ctx): Extension<ApiContext>) -> impl IntoResponse {
+    let file_hashes = match ctx.store.db.analysis_event.get_analysed_file_hashes().await {
+        Ok(r) => r,
+        Err(e) => return error_response(e)
+    };
+    let scheduled = file_hashes.len();
+    let inner_ctx = ctx.clone();
+    task::spawn(async move {
+        let mut reanalysed = 0;
+        for file_hash in file_hashes {
+            let file_reports = match inner_ctx.store.db.file_analysis_report.get_file_reports_by_file_hash(file_hash.as_str()).await {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!("could not get file reports of {:?}: {:?}", file_hash, e);
+                    continue;
+                }
+            };
+            for far in file_reports.into_iter().filter(|far| far.dynamic_analysis) {
+                match reanalyse_file_report(&inner_ctx.store, &inner_ctx.dynamic_analyser, &inner_ctx.scoring, &inner_ctx.baseline, far.clone()).await {
+                    Ok(_) => reanalysed += 1,
+                    Err(e) => warn!("could not re-analyse file report {:?}: {:?}", far.uid, e)
+                }
+            }
+        }
+        info!("{} file reports re-analysed", reanalysed);
+    });
+    (StatusCode::ACCEPTED, Json(
+        types::Response{
+                r:  types::Responses::GenericSuccessResponse(
+                        types::GenericSuccessResponse {
+                            msg: format!("re-analysis of {} files scheduled", scheduled)
+                        }
+                    )
+                }
+            )
+        )
+}
+
+async fn get_analysis_timeline(Extension(ctx): Extension<ApiContext>, Path(analysis_id): Path<String>) -> impl IntoResponse {
+    let events = match ctx.store.db.analysis_event.get_analysis_events(analysis_id.as_str()).await {
+        Ok(r) => r,
+        Err(e) => return error_response(e)
+    };
+    // findings are only kept for the last analysis of a report, older runs
+    // are returned without them
+    let findings: Vec<Finding> = match ctx.store.db.file_analysis_report.get_file_reports_by_file_hash(events.file_hash.as_str()).await {
+        Ok(r) => r
+            .into_iter()
+            .find(|far| far.last_analysis_id == analysis_id)
+            .map(|far| far.findings)
+            .unwrap_or_default(),
+        Err(e) => {
+            debug!("no file report found for analysis {:?}: {:?}", analysis_id, e);
+            Vec::new()
+        }
+    };
+    // the findings were raised on the correlated events, so the timeline shows those
+    let correlated = correlation::correlate(events.iocs);
+    (StatusCode::OK, Json(
+        types::Response{
+                r:  types::Responses::GetAnalysisTimeline(
+                        types::GetAnalysisTimeline {
+                            analysis_id: events.analysis_id,
+                            file_hash: events.file_hash,
+                            timeline: timeline::build_timeline(correlated.iocs, &findings)
+                        }
+                    )
+                }
+            )
+        )
+}
+
+// hosts contacted during an analysis run and what made the sample contact them,
+// as json or, with `?format=dot`, as a graphviz digraph
+async fn get_network_graph(Extension(ctx): Extension<ApiContext>, Path(analysis_id): Path<String>, Query(params): Query<types::GetNetworkGraphParams>) -> impl IntoResponse {
+    let events = match ctx.store.db.analysis_event.get_analysis_events(analysis_id.as_str()).await {
+        Ok(r) => r,
+        Err(e) => return error_response(e).into_response()
+    };
+    let correlated = correlation::correlate(events.iocs);
+    let graph = ctx.network_graph.build(&correlated.iocs);
+    debug!("network graph of {:?}: {} hosts, {} edges", analysis_id, graph.nodes.len(), graph.edges.len());
+    match params.format.as_deref() {
+        Some("dot") => {
+            (StatusCode::OK, [
+                (header::CONTENT_TYPE, "text/vnd.graphviz".to_string())
+            ], graph.to_dot()).into_response()
+        },
+        _ => {
+            (StatusCode::OK, Json(
+                types::Response{
+                    r:  types::Responses::GetNetworkGraph(
+                            types::GetNetworkGraph {
+                                analysis_id: events.analysis_id,
+                                file_hash: events.file_hash,
+                                network_graph: graph
+                            }
+                        )
+                    }
+                )
+            ).into_response()
+        }
+    }
+}
+
+// behaviours the last analysis of a report triggered on each bait website,
+// and those only some of the sites triggered
+async fn get_site_comparison(Extension(ctx): Extension<ApiContext>, Path(file_report_uid): Path<String>) -> impl IntoResponse {
+    let file_report = match ctx.store.db.file_analysis_report.get_file_report(&file_report_uid).await {
+        Ok(r) => r,
+        Err(e) => return error_response(e)
+    };
+    let sites = match ctx.store.db.analysis_event.get_analysis_events(file_report.last_analysis_id.as_str()).await {
+        Ok(_e) => analysed_sites(&_e.iocs),
+        // no events yet, the sites the sample was sent to are compared
+        Err(StoreError::NotFoundError) => file_report.bait_websites.clone(),
+        Err(e) => return error_response(e)
+    };
+    (StatusCode::OK, Json(
+        types::Response{
+                r:  types::Responses::GetSiteComparison(
+                        types::GetSiteComparison {
+                            file_report_uid,
+                            analysis_id: file_report.last_analysis_id,
+                            site_comparison: site_differential::compare_sites(&sites, &file_report.findings)
+                        }
+                    )
+                }
+            )
+        )
+}
+
+// events of an analysis the sandbox sent but that did not validate
+async fn get_rejected_events(Extension(ctx): Extension<ApiContext>, Path(analysis_id): Path<String>) -> impl IntoResponse {
+    let rejected_events = match ctx.store.db.analysis_event.get_rejected_events(analysis_id.as_str()).await {
+        Ok(r) => r,
+        Err(e) => return error_response(e)
+    };
+    (StatusCode::OK, Json(
+        types::Response{
+                r:  types::Responses::GetRejectedEvents(
+                        types::GetRejectedEvents {
+                            analysis_id,
+                            count: rejected_events.len(),
+                            rejected_events
+                        }
+                    )
+                }
+            )
+        )
+}
+
+async fn import_known_hashes(Extension(ctx): Extension<ApiContext>, mut multipart: Multipart) -> impl IntoResponse {
+    let mut export = String::new();
+    let mut source = String::new();
+    let mut family = hash_intel::UNKNOWN_FAMILY.to_string();
+
+    while let Some(field) = match multipart.next_field().await {
+        Ok(f) => f,
+        Err(err) => {
+            return (StatusCode::BAD_REQUEST, Json(types::Response{
+                r:  types::Responses::GenericErrorResponse (
+                        types::GenericErrorResponse { msg: format!("Error reading multipart field: {:?}", err) }
+                    )
+            } ))
+        }
+    } {
+        let field_name = &field.name().unwrap_or_default().to_string();
+        match field_name.as_str() {
+            "hashes" => {
+                if source.is_empty() {
+                    source = field.file_name().unwrap_or_default().to_string();
+                }
+                export = match field.text().await {
+                    Ok(t) => t,
+                    Err(err) => {
+                        return (StatusCode::BAD_REQUEST, Json(types::Response{
+                            r:  types::Responses::GenericErrorResponse (
+                                    types::GenericErrorResponse { msg: format!("Error reading hashes: {:?}", err) }
+                                )
+                        } ))
+                    }
+                };
+            },
+            "source" => {
+                source = field.text().await.unwrap_or_default();
+            },
+            "family" => {
+                family = field.text().await.unwrap_or_default();
+            },
+            _ => {}
+        }
+    }
+
+    let known_hashes = hash_intel::parse_export(&export, &source, &family);
+    if known_hashes.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(types::Response{
+            r:  types::Responses::GenericErrorResponse (
+                    types::GenericErrorResponse { msg: "No hashes found".to_string() }
+                )
+        }))
+    }
+    match ctx.store.db.hash_intel.import_known_hashes(known_hashes).await {
+        Ok(r) => {
+            info!("{} known hashes imported from {:?}", r, source);
+            (StatusCode::CREATED, Json(
+                types::Response{
+                        r:  types::Responses::ImportKnownHashes(
+                                types::ImportKnownHashes {
+                                    hashes_imported: r
+                                }
+                            )
+                        }
+                    )
+                )
+        },
+        Err(e) => error_response(e)
+    }
+}
+
+async fn get_known_hash(Extension(ctx): Extension<ApiContext>, Path(hash): Path<String>) -> impl IntoResponse {
+    match ctx.store.db.hash_intel.find_known_hash(&[hash]).await {
+        Ok(r) => {
+            (StatusCode::OK, Json(
+                types::Response{
+                        r:  types::Responses::GetKnownHash(
+                                types::GetKnownHash {
+                                    known_hash: r
+                                }
+                            )
+                        }
+                    )
+                )
+        },
+        Err(e) => error_response(e)
+    }
+}
+
+fn infection_chain_node(file_report: FileAnalysisReport, reports: &mut Vec<FileAnalysisReport>, evidence: bool) -> types::InfectionChainNode {
+    let uid = file_report.uid.clone();
+    let (children, rest): (Vec<FileAnalysisReport>, Vec<FileAnalysisReport>) = std::mem::take(reports)
+        .into_iter()
+        .partition(|r| r.parent_uid.is_some() && r.parent_uid == uid);
+    *reports = rest;
+    types::InfectionChainNode {
+        file_report: file_report_response(file_report, evidence),
+        children: children
+            .into_iter()
+            .map(|c| infection_chain_node(c, reports, evidence))
+            .collect()
+    }
+}
+
+// the whole chain the report belongs to: from the submitted file down to every
+// payload fetched by it or by its payloads
+async fn get_infection_chain(Extension(ctx): Extension<ApiContext>, Path(file_report_uid): Path<String>, Query(params): Query<types::GetFileReportsParams>) -> impl IntoResponse {
+    let mut root = match ctx.store.db.file_analysis_report.get_file_report(&file_report_uid).await {
+        Ok(r) => r,
+        Err(e) => return error_response(e)
+    };
+    for _ in 0..MAX_INFECTION_CHAIN_DEPTH {
+        let parent_uid = match root.parent_uid.as_ref() {
+            Some(_uid) => _uid.clone(),
+            None => break
+        };
+        match ctx.store.db.file_analysis_report.get_file_report(&parent_uid).await {
+            Ok(p) => root = p,
+            // the parent was deleted, the chain starts here
+            Err(StoreError::NotFoundError) => break,
+            Err(e) => return error_response(e)
+        }
+    }
+
+    let mut descendants: Vec<FileAnalysisReport> = Vec::new();
+    let mut pending: Vec<String> = root.uid.clone().into_iter().collect();
+    for _ in 0..MAX_INFECTION_CHAIN_DEPTH {
+        if pending.is_empty() {
+            break;
+        }
+        let mut next: Vec<String> = Vec::new();
+        for _uid in pending {
+            match ctx.store.db.file_analysis_report.get_child_file_reports(&_uid).await {
+                Ok(children) => {
+                    next.extend(children.iter().filter_map(|c| c.uid.clone()));
+                    descendants.extend(children);
+                },
+                Err(e) => return error_response(e)
+            }
+        }
+        pending = next;
+    }
+
+    (StatusCode::OK, Json(
+        types::Response{
+                r:  types::Responses::GetInfectionChain(
+                        types::GetInfectionChain {
+                            infection_chain: infection_chain_node(root, &mut descendants, params.evidence)
+                        }
+                    )
+                }
+            )
+        )
+}
+
+async fn get_artifacts(Extension(ctx): Extension<ApiContext>, Path(file_report_uid): Path<String>) -> impl IntoResponse {
+    match ctx.store.db.artifact.get_report_artifacts(&file_report_uid).await {
+        Ok(r) => {
+            (StatusCode::OK, Json(
+                types::Response{
+                        r:  types::Responses::GetArtifacts(
+                                types::GetArtifacts {
+                                    artifacts: r
+                                }
+                            )
+                        }
+                    )
+                )
+        },
+        Err(e) => error_response(e)
+    }
+}
+
+// the artifact is served inside a password protected zip, never as is
+async fn download_artifact(Extension(ctx): Extension<ApiContext>, Path(sha256): Path<String>) -> impl IntoResponse {
+    let artifact = match ctx.store.db.artifact.get_artifact(&sha256.to_lowercase()).await {
+        Ok(a) => a,
+        Err(e) => return error_response(e).into_response()
+    };
+    let zipped = ctx.artifacts.get(&artifact.sha256)
+        .and_then(|data| artifacts::password_protected_zip(&artifact.sha256, &data, &ctx.artifacts_zip_password));
+    match zipped {
+        Ok(z) => {
+            (StatusCode::OK, [
+                (header::CONTENT_TYPE, "application/zip".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.zip\"", artifact.sha256))
+            ], z).into_response()
+        },
+        Err(e) => {
+            error!("could not serve artifact {}: {}", artifact.sha256, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(types::Response{
+                r:  types::Responses::GenericErrorResponse (
+                        types::GenericErrorResponse { msg: "Artifact could not be read".to_string() }
+                    )
+            })).into_response()
+        }
+    }
+}
+
 async fn analyse_file(Extension(ctx): Extension<ApiContext>, mut multipart: Multipart) -> impl IntoResponse {
     // let mut total_chunks = 0;
     let mut file_name: String = String::new();
@@ -228,9 +1146,15 @@ async fn analyse_file(Extension(ctx): Extension<ApiContext>, mut multipart: Mult
     // calculation of the hash of the file content
     let file_hash_from_bytes = sha256::digest(&total_file_bytes).to_string();
     let analysis_uuid = Uuid::new_v4();
+    // tokens planted by the sandbox in the bait websites, one per kind of data
+    let canary_plan = CanaryPlan::generate();
 
-    // save report reference to database
-    let file_analysis_report  = match ctx.store.db.file_analysis_report.create_file_report(FileAnalysisReport::new(
+    let known_hash_findings: Vec<Finding> = known_hash_findings(&ctx.store, &total_file_bytes, &file_name).await;
+    let initial_severity = match known_hash_findings.is_empty() {
+        true => 0,
+        false => Severity::VeryHigh as i64
+    };
+    let mut new_file_report = FileAnalysisReport::new(
         file_name.clone(),
         file_hash_from_bytes.clone(),
         file_name.clone(),
@@ -238,7 +1162,12 @@ async fn analyse_file(Extension(ctx): Extension<ApiContext>, mut multipart: Mult
         analysis_uuid.to_string(),
         false,
         dynamic_analysis,static_analysis,
-        0, bait_websites.to_owned(), Vec::new())).await {
+        initial_severity, bait_websites.to_owned(), known_hash_findings.clone());
+    new_file_report.canary_plan = canary_plan.clone();
+    assess_file_report(&ctx.scoring, &mut new_file_report);
+
+    // save report reference to database
+    let file_analysis_report  = match ctx.store.db.file_analysis_report.create_file_report(new_file_report).await {
             Ok(f) => {
                 info!("file {:?} report saved", file_name.clone());
                 f
@@ -253,28 +1182,24 @@ async fn analyse_file(Extension(ctx): Extension<ApiContext>, mut multipart: Mult
             }
         };
 
+    // the uploaded sample is kept, pages for analysis only have a dummy file
+    if page_for_analysis.is_empty()
+        && let Some(_uid) = file_analysis_report.uid.as_ref() {
+        store_artifact(&ctx.store, &ctx.artifacts, &total_file_bytes, _uid, ARTIFACT_RELATION_SAMPLE, &file_name).await;
+    }
+
     if dynamic_analysis {
+        request_baseline_runs(&ctx.store, ctx.queue.as_ref(), &ctx.baseline, &bait_websites).await;
         // prepare the FileForAnalysis details to be sent as byte stream to RBMQ
         let file_for_analysis = types::FileForAnalysis {
             file_name: file_name.clone(),
             file_hash: file_hash_from_bytes.clone(),
             analysis_id: analysis_uuid.to_string(),
             bait_websites: bait_websites,
-            file_bytes: total_file_bytes.clone()
+            file_bytes: total_file_bytes.clone(),
+            canary_plan
         };
-
-        let mut file_for_analysis_buf: Vec<u8> = Vec::new();
-
-        // Serialize the FileForAnalysis to byte Vec
-        file_for_analysis.serialize(&mut Serializer::new(&mut file_for_analysis_buf)).unwrap();
-
-        // pulish the data
-        let _ = ctx.queue.publish(
-            ctx.queue.get_core_files_queue(),
-            ctx.queue.get_main_exchange(),
-            file_for_analysis_buf).await;
-
-        info!("file {:?} sent to queue for analysis", file_name);
+        send_for_dynamic_analysis(ctx.queue.as_ref(), file_for_analysis).await;
     }
 
     if static_analysis {
@@ -283,6 +1208,8 @@ async fn analyse_file(Extension(ctx): Extension<ApiContext>, mut multipart: Mult
             Ok(mut r) => {
                 match static_analyser.analyze(r.to_owned(), total_file_bytes) {
                     Ok(mut f) => {
+                        // the known hash finding is static too, keep it with the new ones
+                        f.append(&mut known_hash_findings.clone());
                         info!("found {} findings for {:?}", f.len(), r.clone().file_name);
                         // r.has_started_analysis = true; TODO: set a separate column to check if is analysed dynamically has_started_analysis_dynamically
                         let mut tmp_findings: Vec<Finding> = Vec::new();
@@ -296,7 +1223,7 @@ async fn analyse_file(Extension(ctx): Extension<ApiContext>, mut multipart: Mult
                         }
 
                         for rf in r.clone().findings {
-                            if rf.r#type != analyzer::AnalysisType::Static {
+                            if rf.is_from_events() {
                                 tmp_findings.push(rf.to_owned());
                                 if rf.severity > max_severity {
                                     max_severity = rf.severity;
@@ -309,6 +1236,7 @@ async fn analyse_file(Extension(ctx): Extension<ApiContext>, mut multipart: Mult
                         r.severity = max_severity as i64;
                         r.findings = tmp_findings;
                         r.findings.append(&mut f);
+                        assess_file_report(&ctx.scoring, &mut r);
                         match ctx.store.db.file_analysis_report.update_file_report(
                             file_report_uid.as_str(), 
                             r).await {
@@ -375,16 +1303,46 @@ impl AppMethods for App {
         let inner_queue = self.queue.clone();
         let inner_store = self.store.clone();
         let inner_malsmug_dir = self.malsmug_dir.clone();
+        let inner_domain_intel = self.domain_intel.clone();
+        let inner_dynamic_rules = self.dynamic_rules.clone();
+        let inner_scoring = self.scoring.clone();
+        let inner_virus_total = self.virus_total.clone();
+        let inner_artifacts = self.artifacts.clone();
+        let inner_recursive_analysis = self.recursive_analysis.clone();
+        let inner_sensitive_storage = self.sensitive_storage.clone();
+        let inner_baseline = self.baseline.clone();
 
         let app = Router::new()
             .route("/analyse-file", post(analyse_file))
             .route("/delete-file-report/{file_report_uid}", delete(delete_file_report))
             .route("/delete-file-reports/{file_hash}", delete(delete_file_reports_by_hash))
             .route("/get-file-reports/{file_hash}", get(get_file_reports))
+            .route("/search-console-logs", get(search_console_logs))
+            .route("/get-analysis-timeline/{analysis_id}", get(get_analysis_timeline))
+            .route("/get-network-graph/{analysis_id}", get(get_network_graph))
+            .route("/get-site-comparison/{file_report_uid}", get(get_site_comparison))
+            .route("/get-rejected-events/{analysis_id}", get(get_rejected_events))
+            .route("/reanalyse-file-report/{file_report_uid}", post(reanalyse_file_report_by_uid))
+            .route("/reanalyse-file-reports/{file_hash}", post(reanalyse_file_reports_by_hash))
+            .route("/reanalyse-all", post(reanalyse_all_file_reports))
+            .route("/import-known-hashes", post(import_known_hashes).layer(DefaultBodyLimit::max(MAX_KNOWN_HASHES_IMPORT_SIZE)))
+            .route("/get-known-hash/{hash}", get(get_known_hash))
+            .route("/get-artifacts/{file_report_uid}", get(get_artifacts))
+            .route("/get-infection-chain/{file_report_uid}", get(get_infection_chain))
+            .route("/download-artifact/{sha256}", get(download_artifact))
             .route_layer(
                 Extension(ApiContext {
                     store: Arc::from(self.store.clone()),
                     queue: inner_queue.clone(),
+                    dynamic_analyser: Arc::new(Mutex::new(DastAnalyzer::new(
+                        self.malsmug_dir.clone(), self.domain_intel.clone(), self.dynamic_rules.clone(),
+                        self.store.db.hash_intel.clone(), self.virus_total.clone(), self.sensitive_storage.clone()))),
+                    scoring: self.scoring.clone(),
+                    artifacts: self.artifacts.clone(),
+                    artifacts_zip_password: utils::get_env_var("ARTIFACTS_ZIP_PASSWORD")
+                        .unwrap_or(DEFAULT_ARTIFACTS_ZIP_PASSWORD.to_string()),
+                    network_graph: self.network_graph.clone(),
+                    baseline: self.baseline.clone()
                 })
             );
 
@@ -393,7 +1351,9 @@ impl AppMethods for App {
                     inner_queue.get_sandbox_iocs_queue()).await {
                         Ok(mut c) => {
                             // initialize the dynamic analyzer ready to process incoming events
-                            let mut dynamic_analyser = DastAnalyzer::new(inner_malsmug_dir);
+                            let mut dynamic_analyser = DastAnalyzer::new(
+                                inner_malsmug_dir, inner_domain_intel.clone(), inner_dynamic_rules, inner_store.db.hash_intel.clone(),
+                                inner_virus_total, inner_sensitive_storage);
 
                             while let Some(delivery) = c.next().await {
                                 match delivery {
@@ -417,13 +1377,65 @@ impl AppMethods for App {
                                         };
 
                                         // from json string to struct
-                                        let events_for_analysis: EventsFromAnalysis = match serde_json::from_str(data_string) {
+                                        let raw_events: RawEventsFromAnalysis = match serde_json::from_str(data_string) {
                                             Ok(r) => r,
                                             Err(e) => {
                                                 error!("could not parse json string from queue: {:?}", e);
                                                 continue;
                                             }
                                         };
+                                        let (events_for_analysis, invalid_events) = raw_events.validate();
+                                        if !invalid_events.is_empty() {
+                                            reject_events(&inner_store, &events_for_analysis, invalid_events).await;
+                                        }
+
+                                        // keep the raw events, the analysis below only keeps its findings
+                                        match inner_store.db.analysis_event.store_analysis_events(AnalysisEvents {
+                                            analysis_id: events_for_analysis.analysis_id.clone(),
+                                            file_hash: events_for_analysis.file_hash.clone(),
+                                            iocs: events_for_analysis.iocs.clone()
+                                        }).await {
+                                            Ok(_) => debug!("{} events stored", events_for_analysis.iocs.len()),
+                                            Err(e) => error!("could not store analysis events: {:?}", e)
+                                        }
+
+                                        // events of a control run are the baseline of its bait website, there is no report to update
+                                        if events_for_analysis.file_hash.is_empty() {
+                                            let completed_at = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+                                                Ok(d) => d.as_secs() as i64,
+                                                Err(_) => 0
+                                            };
+                                            match inner_store.db.baseline.complete_baseline_run(events_for_analysis.analysis_id.as_str(), completed_at).await {
+                                                Ok(_r) => {
+                                                    info!("control run of {:?} completed with {} events", _r.bait_website, events_for_analysis.iocs.len());
+                                                    continue;
+                                                },
+                                                Err(StoreError::NotFoundError) => (),
+                                                Err(e) => error!("could not complete control run {:?}: {:?}", events_for_analysis.analysis_id, e)
+                                            }
+                                        }
+
+                                        // index the console output of the sample so that it can be searched later
+                                        let console_logs: Vec<ConsoleLogEntry> = events_for_analysis.iocs
+                                            .iter()
+                                            .filter(|i| matches!(i.ioc_type, IoCType::ConsoleLog))
+                                            .filter_map(|i| match &i.value {
+                                                IoCValue::IoCConsoleLog(_v) => Some(ConsoleLogEntry {
+                                                    file_hash: events_for_analysis.file_hash.clone(),
+                                                    analysis_id: events_for_analysis.analysis_id.clone(),
+                                                    executed_on: i.executed_on.clone(),
+                                                    timestamp: i.timestamp as i64,
+                                                    text: _v.text.clone()
+                                                }),
+                                                _ => None
+                                            })
+                                            .collect();
+                                        if !console_logs.is_empty() {
+                                            match inner_store.db.console_log.index_console_logs(console_logs).await {
+                                                Ok(n) => debug!("{} console logs indexed", n),
+                                                Err(e) => error!("could not index console logs: {:?}", e)
+                                            }
+                                        }
 
                                         // get file report stored in database on file upload
                                         let file_reports_with_the_same_hash = match inner_store.db
@@ -452,6 +1464,19 @@ impl AppMethods for App {
                                             }
                                         }
 
+                                        // keep the payloads the sample downloaded
+                                        if let Some(_uid) = file_report.uid.clone() {
+                                            for _i in events_for_analysis.iocs.iter() {
+                                                if let IoCValue::IoCSuspiciousFileDownload(_v) = &_i.value {
+                                                    store_artifact(&inner_store, &inner_artifacts, &_v.data, &_uid,
+                                                        ARTIFACT_RELATION_DOWNLOAD, &_v.url).await;
+                                                }
+                                            }
+                                        }
+                                        create_child_reports(&inner_store, inner_queue.as_ref(), &inner_scoring, &inner_artifacts,
+                                            &inner_recursive_analysis, &file_report,
+                                            second_stage::extract_second_stages(&inner_domain_intel, &events_for_analysis.iocs)).await;
+
                                         // this is used to decide whether we must to append to existing findings or
                                         // to initialize the dynamic analysis findings from the start.
                                         // If the previous retreived findings have the same analysis_id, means that
@@ -477,7 +1502,7 @@ impl AppMethods for App {
                                                     }
 
                                                     for rf in file_report.findings {
-                                                        if rf.r#type != analyzer::AnalysisType::Dynamic || append_to_findings {
+                                                        if !rf.is_from_events() || append_to_findings {
                                                             tmp_findings.push(rf.to_owned());
                                                             if rf.severity > max_severity {
                                                                 max_severity = rf.severity;
@@ -490,6 +1515,16 @@ impl AppMethods for App {
                                                     file_report.severity = max_severity as i64;
                                                     file_report.findings = tmp_findings;
                                                     file_report.findings.append(&mut f);
+                                                    // the other sites of the analysis published their events earlier
+                                                    match inner_store.db.analysis_event.get_analysis_events(events_for_analysis.analysis_id.as_str()).await {
+                                                        Ok(_e) => {
+                                                            let sites = analysed_sites(&_e.iocs);
+                                                            subtract_baselines(&inner_store, &inner_baseline, &mut file_report, &sites).await;
+                                                            compare_bait_websites(&mut file_report, &sites);
+                                                        },
+                                                        Err(e) => error!("could not get the events of analysis {:?}: {:?}", events_for_analysis.analysis_id, e)
+                                                    }
+                                                    assess_file_report(&inner_scoring, &mut file_report);
                                                     match inner_store.db.file_analysis_report.update_file_report(
                                                         file_report_uid.as_str(), 
                                                         file_report).await {
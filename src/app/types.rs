@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use crate::{analysis::dast_ioc_types::{self}, store::models::FileAnalysisReport};
+use crate::{analysis::{canary::CanaryPlan, dast_ioc_types::{self}, hash_intel::KnownHash, network_graph::NetworkGraph, site_differential::SiteComparison, timeline::SiteTimeline}, store::models::{ConsoleLogEntry, FileAnalysisReport, RejectedEvent, ReportArtifact}};
 
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct FileForAnalysis {
@@ -7,11 +7,40 @@ pub struct FileForAnalysis {
     pub file_hash: String,
     pub analysis_id: String,
     pub bait_websites: Vec<String>,
-    pub file_bytes: Vec<u8>
+    pub file_bytes: Vec<u8>,
+    pub canary_plan: CanaryPlan
+}
+
+// a batch of events as published by the sandbox, the events are validated
+// one by one so that an invalid event does not cost the whole batch
+#[derive(Debug, Deserialize)]
+pub struct RawEventsFromAnalysis {
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
+    pub file_hash: String,
+    pub analysis_id: String,
+    pub iocs: Vec<serde_json::Value>
+}
+
+fn legacy_schema_version() -> u32 {
+    dast_ioc_types::LEGACY_IOC_SCHEMA_VERSION
+}
+
+impl RawEventsFromAnalysis {
+    pub fn validate(self) -> (EventsFromAnalysis, Vec<dast_ioc_types::InvalidEvent>) {
+        let (iocs, invalid) = dast_ioc_types::parse_events(self.schema_version, self.iocs);
+        (EventsFromAnalysis {
+            schema_version: self.schema_version,
+            file_hash: self.file_hash,
+            analysis_id: self.analysis_id,
+            iocs
+        }, invalid)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct EventsFromAnalysis {
+    pub schema_version: u32,
     pub file_hash: String,
     pub analysis_id: String,
     pub iocs: Vec<dast_ioc_types::IoC>
@@ -50,6 +79,87 @@ pub struct DeleteFileReport {
     pub file_reports_deleted: u64,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct SearchConsoleLogs {
+    pub console_logs: Vec<ConsoleLogEntry>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetAnalysisTimeline {
+    pub analysis_id: String,
+    pub file_hash: String,
+    pub timeline: Vec<SiteTimeline>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportKnownHashes {
+    pub hashes_imported: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetKnownHash {
+    pub known_hash: KnownHash,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetArtifacts {
+    pub artifacts: Vec<ReportArtifact>,
+}
+
+// a report with the reports of the payloads it fetched
+#[derive(Deserialize, Serialize)]
+pub struct InfectionChainNode {
+    pub file_report: FileAnalysisReport,
+    pub children: Vec<InfectionChainNode>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetInfectionChain {
+    // report of the submitted file the chain starts from
+    pub infection_chain: InfectionChainNode,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetNetworkGraph {
+    pub analysis_id: String,
+    pub file_hash: String,
+    pub network_graph: NetworkGraph
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetRejectedEvents {
+    pub analysis_id: String,
+    pub count: usize,
+    pub rejected_events: Vec<RejectedEvent>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetSiteComparison {
+    pub file_report_uid: String,
+    pub analysis_id: String,
+    pub site_comparison: SiteComparison
+}
+
+#[derive(Deserialize)]
+pub struct GetNetworkGraphParams {
+    // `json` (default) or `dot`
+    pub format: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct GetFileReportsParams {
+    // also return every individual finding, not only the aggregated ones
+    #[serde(default)]
+    pub evidence: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SearchConsoleLogsParams {
+    pub q: String,
+    pub file_hash: Option<String>,
+    pub limit: Option<i64>,
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Responses {
@@ -58,7 +168,16 @@ pub enum Responses {
     FileUploadResponse(FileUploadResponse),
     GetFileReport(GetFileReport),
     GetFileReports(GetFileReports),
-    DeleteFileReport(DeleteFileReport)
+    DeleteFileReport(DeleteFileReport),
+    SearchConsoleLogs(SearchConsoleLogs),
+    GetAnalysisTimeline(GetAnalysisTimeline),
+    ImportKnownHashes(ImportKnownHashes),
+    GetKnownHash(GetKnownHash),
+    GetArtifacts(GetArtifacts),
+    GetInfectionChain(GetInfectionChain),
+    GetNetworkGraph(GetNetworkGraph),
+    GetSiteComparison(GetSiteComparison),
+    GetRejectedEvents(GetRejectedEvents)
 }
 
 #[derive(Deserialize, Serialize)]
@@ -1,6 +1,13 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
-use crate::{app::{self, rabbitclient, App}, bootstrap::rabbitmq_conf::RabbitMQExtConf, utils, Args};
+use crate::{
+    analysis::{domain_intel::DomainIntel, rule_engine::RuleSet, scoring::ScoringModel, storage_access::SensitiveStorage, network_graph::NetworkGraphBuilder},
+    app::{self, rabbitclient, App},
+    integrations::virus_total::VTClient,
+    bootstrap::{analysis_conf::AnalysisExtConf, rabbitmq_conf::RabbitMQExtConf},
+    utils,
+    Args
+};
 
 pub mod rabbitmq_conf {
     use serde::{Serialize, Deserialize};
@@ -44,12 +51,132 @@ pub mod rabbitmq_conf {
     }
 }
 
+pub mod analysis_conf {
+    use serde::{Serialize, Deserialize};
+
+    use crate::analysis::baseline::BaselineConf;
+    use crate::analysis::network_graph::NetworkGraphConf;
+    use crate::analysis::storage_access::SensitiveStorageConf;
+    use crate::integrations::virus_total::VTConfig;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct DomainIntelExtConf {
+        pub public_suffix_list: String,
+        #[serde(default)]
+        pub brands: Vec<String>,
+        // registrable domains owned by the brands, never reported as imitations
+        #[serde(default)]
+        pub allowed_domains: Vec<String>
+    }
+
+    // second stage scripts and pages fetched by a sample are analysed in child reports
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct RecursiveAnalysisExtConf {
+        pub enabled: bool,
+        // children are analysed statically, this also sends them to the sandbox
+        pub dynamic_analysis: bool,
+        // depth of the deepest child, the submitted file is at depth 0
+        pub max_depth: i64,
+        // children created per analysis of a report
+        pub max_children: usize
+    }
+
+    impl Default for RecursiveAnalysisExtConf {
+        fn default() -> Self {
+            RecursiveAnalysisExtConf {
+                enabled: true,
+                dynamic_analysis: false,
+                max_depth: 3,
+                max_children: 20
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct AnalysisExtConf {
+        pub domain_intel: DomainIntelExtConf,
+        #[serde(default = "default_dynamic_rules")]
+        pub dynamic_rules: String,
+        #[serde(default = "default_scoring")]
+        pub scoring: String,
+        #[serde(default)]
+        pub virus_total: VTConfig,
+        #[serde(default)]
+        pub recursive_analysis: RecursiveAnalysisExtConf,
+        #[serde(default)]
+        pub sensitive_storage: SensitiveStorageConf,
+        #[serde(default)]
+        pub network_graph: NetworkGraphConf,
+        #[serde(default)]
+        pub baseline: BaselineConf
+    }
+
+    fn default_dynamic_rules() -> String {
+        "./config/dynamic_rules.yaml".to_string()
+    }
+
+    fn default_scoring() -> String {
+        "./config/scoring.yaml".to_string()
+    }
+}
+
 pub async fn bootstrap(args: Args) -> App {
     let server_address: String = args.bindhost + ":" + args.bindport.to_string().as_str();
 
     let rbmq_conf_from_file = utils::parse_yaml::
         <RabbitMQExtConf>(PathBuf::from("./config/rabbitmq.yaml")).unwrap(); 
 
+    let analysis_conf_from_file = utils::parse_yaml::
+        <AnalysisExtConf>(PathBuf::from("./config/analysis.yaml")).unwrap();
+
+    // the public suffix list is loaded once and shared by all analyzers.
+    // Known services embed brand names too (google-analytics.com) and are
+    // never imitations
+    let allowed_domains: Vec<String> = analysis_conf_from_file.domain_intel.allowed_domains.iter()
+        .chain(analysis_conf_from_file.network_graph.known_services.iter())
+        .cloned()
+        .collect();
+    let domain_intel = match DomainIntel::new(
+        PathBuf::from(analysis_conf_from_file.domain_intel.public_suffix_list),
+        analysis_conf_from_file.domain_intel.brands,
+        allowed_domains) {
+            Ok(d) => d,
+            Err(e) => panic!("could not initialize domain intelligence: {}", e)
+        };
+
+    // the rules file is reloaded by the analyzer when it changes,
+    // but it must be valid on startup
+    let dynamic_rules = match RuleSet::load(PathBuf::from(analysis_conf_from_file.dynamic_rules)) {
+        Ok(r) => {
+            println!("{} dynamic rules loaded", r.count());
+            r
+        },
+        Err(e) => panic!("could not load dynamic rules: {}", e)
+    };
+
+    let scoring = match ScoringModel::load(PathBuf::from(analysis_conf_from_file.scoring)) {
+        Ok(s) => s,
+        Err(e) => panic!("could not load scoring model: {}", e)
+    };
+
+    let sensitive_storage = match SensitiveStorage::new(&analysis_conf_from_file.sensitive_storage) {
+        Ok(s) => s,
+        Err(e) => panic!("could not load sensitive storage patterns: {}", e)
+    };
+
+    // the api key stays in the environment, the rest of the integration in the config
+    let virus_total = match utils::get_env_var("VIRUS_TOTAL_API_KEY") {
+        Some(k) => {
+            println!("VirusTotal uploads: {:?}", analysis_conf_from_file.virus_total.upload_policy);
+            Some(Arc::new(VTClient::new(&k, analysis_conf_from_file.virus_total)))
+        },
+        None => {
+            println!("VIRUS_TOTAL_API_KEY is not set, downloaded files are not checked on VirusTotal");
+            None
+        }
+    };
+
     println!("running server on {}", server_address.clone());
 
     let rbmqc = rabbitclient::RabbitMQ::new(
@@ -60,5 +187,17 @@ pub async fn bootstrap(args: Args) -> App {
         rabbitclient::RabbitMQConfig::new(rbmq_conf_from_file)
     ).await;
 
-    app::App::new(server_address, Box::new(rbmqc)).await
+    let domain_intel = Arc::new(domain_intel);
+    let network_graph = NetworkGraphBuilder::new(domain_intel.clone(), analysis_conf_from_file.network_graph);
+
+    app::App::new(server_address, Box::new(rbmqc), app::AnalysisComponents {
+        domain_intel,
+        dynamic_rules,
+        scoring: Arc::new(scoring),
+        virus_total,
+        recursive_analysis: analysis_conf_from_file.recursive_analysis,
+        sensitive_storage,
+        network_graph: Arc::new(network_graph),
+        baseline: analysis_conf_from_file.baseline
+    }).await
 }
\ No newline at end of file
@@ -1 +1 @@
-pub mod virus_total;
\ No newline at end of file
+pub mod virus_total;
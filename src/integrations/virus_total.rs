@@ -1,56 +1,418 @@
-use std::{thread, time};
-use log::{info, warn};
-use virustotal3::VtClient;
+use std::collections::VecDeque;
+use std::time::Duration;
 
-const MAX_TRIES_ON_FILE_INFO: u8 = 5;
+use log::{debug, info, warn};
+use reqwest::{multipart, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
 
-pub struct VTClient<'a> {
-    vt_client: VtClient<'a>
+const DEFAULT_BASE_URL: &str = "https://www.virustotal.com/api/v3";
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const MINUTE: Duration = Duration::from_secs(60);
+
+// which downloaded files may leave the deployment. Hashes are always looked up
+// first, files VirusTotal already knows are never uploaded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadPolicy {
+    // hash lookups only, nothing the samples download is shared
+    #[default]
+    Never,
+    // files VirusTotal does not know yet are uploaded and analysed
+    Unknown
 }
 
-impl<'a> VTClient<'a> {
-    pub fn new(api_key: &str) -> VTClient {
-        VTClient {
-            vt_client: VtClient::new(&api_key)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VTConfig {
+    pub base_url: String,
+    pub upload_policy: UploadPolicy,
+    // larger files are never uploaded
+    pub max_upload_size: usize,
+    // the free tier allows 4 requests per minute and 500 per day
+    pub requests_per_minute: usize,
+    pub daily_quota: usize,
+    pub poll_interval_secs: u64,
+    pub max_polls: u32,
+    // engines that must flag a file before it is reported as malicious
+    pub min_detections: u64
+}
+
+impl Default for VTConfig {
+    fn default() -> Self {
+        VTConfig {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            upload_policy: UploadPolicy::Never,
+            max_upload_size: 32 * 1024 * 1024,
+            requests_per_minute: 4,
+            daily_quota: 500,
+            poll_interval_secs: 15,
+            max_polls: 20,
+            min_detections: 7
         }
     }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VTError {
+    #[error("daily VirusTotal quota exhausted")]
+    QuotaExhausted,
+    #[error("VirusTotal rate limit exceeded")]
+    RateLimited,
+    #[error("analysis {0} not completed after {1} polls")]
+    AnalysisTimeout(String, u32),
+    #[error("http error: {0}")]
+    Http(String),
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct VTStats {
+    pub malicious: u64,
+    pub suspicious: u64,
+    pub undetected: u64,
+    pub harmless: u64,
+    pub timeout: u64,
+    #[serde(rename = "type-unsupported")]
+    pub type_unsupported: u64,
+    pub failure: u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VTEngineResult {
+    pub engine_name: String,
+    // malicious, suspicious, undetected, harmless, timeout, type-unsupported or failure
+    pub category: String,
+    #[serde(default)]
+    pub result: Option<String>,
+    #[serde(default)]
+    pub engine_version: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VTReport {
+    pub sha256: String,
+    pub stats: VTStats,
+    // sorted by engine name
+    pub engines: Vec<VTEngineResult>
+}
+
+impl VTReport {
+    fn from_values(sha256: &str, stats: &Value, results: &Value) -> Result<Self, VTError> {
+        let stats: VTStats = match serde_json::from_value(stats.clone()) {
+            Ok(s) => s,
+            Err(e) => return Err(VTError::InvalidResponse(format!("analysis stats: {}", e)))
+        };
+        let mut engines: Vec<VTEngineResult> = Vec::new();
+        if let Some(_r) = results.as_object() {
+            for (_, _v) in _r.iter() {
+                match serde_json::from_value::<VTEngineResult>(_v.clone()) {
+                    Ok(_e) => engines.push(_e),
+                    Err(e) => debug!("skipping engine result: {}", e)
+                }
+            }
+        }
+        engines.sort_by(|a, b| a.engine_name.cmp(&b.engine_name));
+        Ok(VTReport { sha256: sha256.to_string(), stats, engines })
+    }
+
+    pub fn detections(&self) -> Vec<&VTEngineResult> {
+        self.engines.iter().filter(|e| e.category == "malicious").collect()
+    }
+
+    // "12/70 engines: Kaspersky: HEUR:Trojan.Script.Generic, ..."
+    pub fn summary(&self) -> String {
+        let detections: Vec<String> = self.detections()
+            .iter()
+            .map(|e| format!("{}: {}", e.engine_name, e.result.clone().unwrap_or_default()))
+            .collect();
+        format!("{}/{} engines: {}", self.stats.malicious, self.engines.len(), detections.join(", "))
+    }
+}
+
+struct QuotaState {
+    recent: VecDeque<Instant>,
+    day_started: Instant,
+    used_today: usize
+}
+
+// spaces the requests to stay under the per minute limit and refuses them
+// once the daily quota is used up. Waiting does not block the runtime
+pub struct QuotaScheduler {
+    max_requests: usize,
+    window: Duration,
+    daily_quota: usize,
+    state: Mutex<QuotaState>
+}
 
-    pub fn submit_file(&self, file: &str) -> bool{
-        match self.vt_client.scan_file(file) {
-            Ok(_) => true,
-            Err(_) => false
+impl QuotaScheduler {
+    pub fn new(max_requests: usize, window: Duration, daily_quota: usize) -> Self {
+        QuotaScheduler {
+            max_requests: max_requests.max(1),
+            window,
+            daily_quota,
+            state: Mutex::new(QuotaState {
+                recent: VecDeque::new(),
+                day_started: Instant::now(),
+                used_today: 0
+            })
         }
     }
 
-    pub async fn get_file_report(&self, file_hash: &str) -> Result<i64, String> {
-        let mut tries = 0;
+    pub async fn acquire(&self) -> Result<(), VTError> {
         loop {
-            info!("Polling Virus Total for results");
-            thread::sleep(time::Duration::from_secs(3));
-            match self.vt_client.get_report_file(file_hash).await {
-                Ok(r) => {
-                    match r["data"]["attributes"]["last_analysis_stats"]["malicious"].as_number() {
-                        Some(n) => {
-                            return Ok(n.as_i64().unwrap())
-                        },
-                        None => {
-                            warn!("error getting file score");
-                            return Err("error getting file score".to_string());
-                        }
-                    }
-                },
-                Err(e) => {
-                    if tries >= MAX_TRIES_ON_FILE_INFO {
-                        warn!("error scanning file: {:?}", &file_hash);
-                        return Err(e.to_string());
+            let wait = {
+                let mut s = self.state.lock().await;
+                let now = Instant::now();
+                if now.duration_since(s.day_started) >= DAY {
+                    s.day_started = now;
+                    s.used_today = 0;
+                }
+                if s.used_today >= self.daily_quota {
+                    return Err(VTError::QuotaExhausted);
+                }
+                while let Some(_first) = s.recent.front() {
+                    if now.duration_since(*_first) >= self.window {
+                        s.recent.pop_front();
                     } else {
-                        warn!("file analysis not available yet: {:?}", &file_hash);
+                        break;
+                    }
+                }
+                match s.recent.front() {
+                    Some(_first) if s.recent.len() >= self.max_requests => {
+                        self.window - now.duration_since(*_first)
+                    },
+                    _ => {
+                        s.recent.push_back(now);
+                        s.used_today += 1;
+                        return Ok(());
                     }
-                    tries = tries + 1;
                 }
+            };
+            debug!("VirusTotal request rate limited, waiting {:?}", wait);
+            sleep(wait).await;
+        }
+    }
+}
+
+pub struct VTClient {
+    http: reqwest::Client,
+    api_key: String,
+    conf: VTConfig,
+    scheduler: QuotaScheduler
+}
+
+impl VTClient {
+    pub fn new(api_key: &str, conf: VTConfig) -> VTClient {
+        VTClient {
+            http: reqwest::Client::new(),
+            api_key: api_key.to_string(),
+            scheduler: QuotaScheduler::new(conf.requests_per_minute, MINUTE, conf.daily_quota),
+            conf
+        }
+    }
+
+    pub fn min_detections(&self) -> u64 {
+        self.conf.min_detections
+    }
+
+    // every request goes through the scheduler. Not found is returned as None
+    async fn _request(&self, request: reqwest::RequestBuilder) -> Result<Option<Value>, VTError> {
+        self.scheduler.acquire().await?;
+        let response = match request.header("x-apikey", &self.api_key).send().await {
+            Ok(r) => r,
+            Err(e) => return Err(VTError::Http(e.to_string()))
+        };
+        match response.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            StatusCode::TOO_MANY_REQUESTS => return Err(VTError::RateLimited),
+            _s if !_s.is_success() => return Err(VTError::Http(format!("status {}", _s))),
+            _ => ()
+        }
+        match response.json::<Value>().await {
+            Ok(v) => Ok(Some(v)),
+            Err(e) => Err(VTError::InvalidResponse(e.to_string()))
+        }
+    }
+
+    // last analysis of a file VirusTotal already knows, nothing is uploaded
+    pub async fn lookup_hash(&self, file_hash: &str) -> Result<Option<VTReport>, VTError> {
+        let url = format!("{}/files/{}", self.conf.base_url, file_hash);
+        match self._request(self.http.get(url)).await? {
+            Some(_v) => {
+                let attributes = &_v["data"]["attributes"];
+                VTReport::from_values(file_hash, &attributes["last_analysis_stats"], &attributes["last_analysis_results"])
+                    .map(Some)
+            },
+            None => Ok(None)
+        }
+    }
+
+    // uploads the file and returns the id of its analysis
+    async fn _upload(&self, file_hash: &str, data: &[u8]) -> Result<String, VTError> {
+        let url = format!("{}/files", self.conf.base_url);
+        let form = multipart::Form::new()
+            .part("file", multipart::Part::bytes(data.to_vec()).file_name(file_hash.to_string()));
+        match self._request(self.http.post(url).multipart(form)).await? {
+            Some(_v) => match _v["data"]["id"].as_str() {
+                Some(_id) => Ok(_id.to_string()),
+                None => Err(VTError::InvalidResponse("upload without analysis id".to_string()))
+            },
+            None => Err(VTError::InvalidResponse("upload endpoint not found".to_string()))
+        }
+    }
+
+    async fn _wait_for_analysis(&self, file_hash: &str, analysis_id: &str) -> Result<VTReport, VTError> {
+        let url = format!("{}/analyses/{}", self.conf.base_url, analysis_id);
+        for _ in 0..self.conf.max_polls {
+            sleep(Duration::from_secs(self.conf.poll_interval_secs)).await;
+            info!("polling VirusTotal for analysis {}", analysis_id);
+            if let Some(_v) = self._request(self.http.get(url.clone())).await? {
+                let attributes = &_v["data"]["attributes"];
+                if attributes["status"] == "completed" {
+                    return VTReport::from_values(file_hash, &attributes["stats"], &attributes["results"]);
+                }
+            }
+        }
+        Err(VTError::AnalysisTimeout(analysis_id.to_string(), self.conf.max_polls))
+    }
+
+    // looks the hash up and uploads the file only when the upload policy allows it.
+    // None when VirusTotal has no report and the file was not uploaded
+    pub async fn scan(&self, file_hash: &str, data: &[u8]) -> Result<Option<VTReport>, VTError> {
+        if let Some(_r) = self.lookup_hash(file_hash).await? {
+            return Ok(Some(_r));
+        }
+        match self.conf.upload_policy {
+            UploadPolicy::Never => {
+                info!("file {} is not known to VirusTotal and uploads are disabled", file_hash);
+                Ok(None)
+            },
+            UploadPolicy::Unknown if data.len() > self.conf.max_upload_size => {
+                warn!("file {} is too large to be uploaded to VirusTotal", file_hash);
+                Ok(None)
+            },
+            UploadPolicy::Unknown => {
+                let analysis_id = self._upload(file_hash, data).await?;
+                self._wait_for_analysis(file_hash, &analysis_id).await.map(Some)
             }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use axum::{extract::Path, http::StatusCode, routing::{get, post}, Extension, Json, Router};
+    use serde_json::json;
+
+    const KNOWN_HASH: &str = "known";
+
+    #[derive(Clone, Default)]
+    struct MockState {
+        uploads: Arc<AtomicUsize>,
+        polls: Arc<AtomicUsize>
+    }
+
+    fn engines() -> Value {
+        json!({
+            "Kaspersky": {"engine_name": "Kaspersky", "category": "malicious", "result": "HEUR:Trojan.Script.Generic"},
+            "Avast": {"engine_name": "Avast", "category": "undetected", "result": null}
+        })
+    }
+
+    async fn get_file(Path(hash): Path<String>) -> (StatusCode, Json<Value>) {
+        if hash != KNOWN_HASH {
+            return (StatusCode::NOT_FOUND, Json(json!({"error": {"code": "NotFoundError"}})));
+        }
+        (StatusCode::OK, Json(json!({"data": {"attributes": {
+            "last_analysis_stats": {"malicious": 1, "undetected": 1},
+            "last_analysis_results": engines()
+        }}})))
+    }
 
+    async fn upload_file(Extension(state): Extension<MockState>) -> Json<Value> {
+        state.uploads.fetch_add(1, Ordering::SeqCst);
+        Json(json!({"data": {"type": "analysis", "id": "analysis-1"}}))
+    }
 
-}
\ No newline at end of file
+    async fn get_analysis(Extension(state): Extension<MockState>) -> Json<Value> {
+        let status = match state.polls.fetch_add(1, Ordering::SeqCst) {
+            0 => "queued",
+            _ => "completed"
+        };
+        Json(json!({"data": {"attributes": {
+            "status": status,
+            "stats": {"malicious": 1, "undetected": 1},
+            "results": engines()
+        }}}))
+    }
+
+    async fn mock_server(state: MockState) -> String {
+        let app = Router::new()
+            .route("/files/{hash}", get(get_file))
+            .route("/files", post(upload_file))
+            .route("/analyses/{id}", get(get_analysis))
+            .layer(Extension(state));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", address)
+    }
+
+    fn client(base_url: String, upload_policy: UploadPolicy) -> VTClient {
+        VTClient::new("key", VTConfig {
+            base_url,
+            upload_policy,
+            requests_per_minute: 100,
+            poll_interval_secs: 0,
+            ..VTConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_known_hash_is_not_uploaded() {
+        let state = MockState::default();
+        let vt = client(mock_server(state.clone()).await, UploadPolicy::Unknown);
+        let report = vt.scan(KNOWN_HASH, b"payload").await.unwrap().unwrap();
+        assert_eq!(report.stats.malicious, 1);
+        assert_eq!(report.engines.len(), 2);
+        assert_eq!(report.engines[0].engine_name, "Avast");
+        assert_eq!(report.summary(), "1/2 engines: Kaspersky: HEUR:Trojan.Script.Generic");
+        assert_eq!(state.uploads.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_files_are_uploaded_only_when_allowed() {
+        let state = MockState::default();
+        let base_url = mock_server(state.clone()).await;
+        let vt = client(base_url.clone(), UploadPolicy::Never);
+        assert!(vt.scan("unknown", b"payload").await.unwrap().is_none());
+        assert_eq!(state.uploads.load(Ordering::SeqCst), 0);
+
+        let vt = client(base_url, UploadPolicy::Unknown);
+        let report = vt.scan("unknown", b"payload").await.unwrap().unwrap();
+        assert_eq!(report.detections().len(), 1);
+        assert_eq!(state.uploads.load(Ordering::SeqCst), 1);
+        // queued on the first poll, completed on the second
+        assert_eq!(state.polls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_waits_for_the_window_and_stops_at_the_daily_quota() {
+        let scheduler = QuotaScheduler::new(2, Duration::from_millis(200), 3);
+        let started = Instant::now();
+        scheduler.acquire().await.unwrap();
+        scheduler.acquire().await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(200));
+        scheduler.acquire().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(matches!(scheduler.acquire().await, Err(VTError::QuotaExhausted)));
+    }
+}
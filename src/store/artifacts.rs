@@ -0,0 +1,229 @@
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+// every artifact file starts with the magic, the format version and whether
+// the rest is encrypted, so files stay readable when the key is set or removed later
+const ARTIFACT_MAGIC: &[u8; 4] = b"MSMA";
+const ARTIFACT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 6;
+const NONCE_LEN: usize = 12;
+const ARTIFACT_COMPRESSION_LEVEL: i32 = 3;
+
+pub const ARTIFACT_COMPRESSION: &str = "zstd";
+pub const ARTIFACT_ENCRYPTION: &str = "chacha20poly1305";
+pub const NO_ENCRYPTION: &str = "none";
+
+// what `put` wrote, stored in the artifacts table
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredBlob {
+    pub sha256: String,
+    pub size: i64,
+    pub stored_size: i64,
+    pub encryption: String
+}
+
+// content addressed files of samples and downloaded payloads, zstd compressed
+// and encrypted when a key is configured. The database keeps their metadata,
+// links to reports and reference counts
+#[derive(Clone)]
+pub struct ArtifactFiles {
+    dir: PathBuf,
+    cipher: Option<ChaCha20Poly1305>
+}
+
+fn decode_hex_key(key: &str) -> Result<Vec<u8>, String> {
+    let key = key.trim();
+    if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("the artifacts key must be 64 hex characters (32 bytes)".to_string());
+    }
+    Ok((0..key.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&key[i..i + 2], 16).unwrap_or_default())
+        .collect())
+}
+
+impl ArtifactFiles {
+    // `key` is the hex encoded 32 bytes key used to encrypt new artifacts
+    pub fn new(dir: PathBuf, key: Option<String>) -> Result<Self, String> {
+        let cipher = match key {
+            Some(_k) => {
+                let bytes = decode_hex_key(&_k)?;
+                Some(ChaCha20Poly1305::new(Key::from_slice(&bytes)))
+            },
+            None => None
+        };
+        if let Err(e) = fs::create_dir_all(&dir) {
+            return Err(format!("could not create artifacts dir {:?}: {}", dir, e));
+        }
+        Ok(ArtifactFiles { dir, cipher })
+    }
+
+    // artifacts are spread over 256 folders by the first byte of their hash
+    fn path_of(&self, sha256: &str) -> PathBuf {
+        let prefix = sha256.get(0..2).unwrap_or("00");
+        self.dir.join(prefix).join(sha256)
+    }
+
+    // files of artifacts already stored are not written again
+    pub fn put(&self, data: &[u8]) -> Result<StoredBlob, String> {
+        let sha256 = sha256::digest(data).to_string();
+        let path = self.path_of(&sha256);
+        if let Ok(_existing) = fs::read(&path) {
+            return Ok(StoredBlob {
+                size: data.len() as i64,
+                stored_size: _existing.len() as i64,
+                encryption: match _existing.get(5) {
+                    Some(1) => ARTIFACT_ENCRYPTION.to_string(),
+                    _ => NO_ENCRYPTION.to_string()
+                },
+                sha256
+            });
+        }
+
+        let compressed = match zstd::encode_all(data, ARTIFACT_COMPRESSION_LEVEL) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("could not compress artifact: {}", e))
+        };
+        let mut content: Vec<u8> = ARTIFACT_MAGIC.to_vec();
+        content.push(ARTIFACT_VERSION);
+        let encryption = match &self.cipher {
+            Some(_c) => {
+                let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+                let encrypted = match _c.encrypt(&nonce, compressed.as_slice()) {
+                    Ok(e) => e,
+                    Err(e) => return Err(format!("could not encrypt artifact: {}", e))
+                };
+                content.push(1);
+                content.extend_from_slice(nonce.as_slice());
+                content.extend(encrypted);
+                ARTIFACT_ENCRYPTION
+            },
+            None => {
+                content.push(0);
+                content.extend(compressed);
+                NO_ENCRYPTION
+            }
+        };
+
+        if let Some(_parent) = path.parent()
+            && let Err(e) = fs::create_dir_all(_parent) {
+            return Err(format!("could not create artifact dir: {}", e));
+        }
+        // written next to the final path first, so a crash never leaves a truncated artifact
+        let tmp_path = path.with_extension("tmp");
+        if let Err(e) = fs::write(&tmp_path, &content).and_then(|_| fs::rename(&tmp_path, &path)) {
+            return Err(format!("could not write artifact {}: {}", sha256, e));
+        }
+        Ok(StoredBlob {
+            sha256,
+            size: data.len() as i64,
+            stored_size: content.len() as i64,
+            encryption: encryption.to_string()
+        })
+    }
+
+    pub fn get(&self, sha256: &str) -> Result<Vec<u8>, String> {
+        let content = match fs::read(self.path_of(sha256)) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("could not read artifact {}: {}", sha256, e))
+        };
+        if content.len() < HEADER_LEN || &content[0..4] != ARTIFACT_MAGIC || content[4] != ARTIFACT_VERSION {
+            return Err(format!("artifact {} has an unknown format", sha256));
+        }
+        let compressed = match content[5] {
+            0 => content[HEADER_LEN..].to_vec(),
+            _ => {
+                let cipher = match &self.cipher {
+                    Some(_c) => _c,
+                    None => return Err(format!("artifact {} is encrypted and no key is configured", sha256))
+                };
+                if content.len() < HEADER_LEN + NONCE_LEN {
+                    return Err(format!("artifact {} is truncated", sha256));
+                }
+                let nonce = Nonce::from_slice(&content[HEADER_LEN..HEADER_LEN + NONCE_LEN]);
+                match cipher.decrypt(nonce, &content[HEADER_LEN + NONCE_LEN..]) {
+                    Ok(d) => d,
+                    Err(_) => return Err(format!("could not decrypt artifact {}, wrong key?", sha256))
+                }
+            }
+        };
+        match zstd::decode_all(compressed.as_slice()) {
+            Ok(d) => Ok(d),
+            Err(e) => Err(format!("could not decompress artifact {}: {}", sha256, e))
+        }
+    }
+
+    pub fn delete(&self, sha256: &str) -> Result<(), String> {
+        match fs::remove_file(self.path_of(sha256)) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("could not delete artifact {}: {}", sha256, e))
+        }
+    }
+}
+
+// the artifact inside an AES encrypted zip, so that it cannot be opened or
+// executed by accident once downloaded
+pub fn password_protected_zip(file_name: &str, data: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .with_aes_encryption(AesMode::Aes256, password);
+    let written = zip.start_file(file_name, options)
+        .and_then(|_| zip.write_all(data).map_err(|e| e.into()))
+        .and_then(|_| zip.finish());
+    match written {
+        Ok(c) => Ok(c.into_inner()),
+        Err(e) => Err(format!("could not create zip: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn tmp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("malsmug-artifacts-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_artifacts_are_content_addressed_and_encrypted() {
+        let dir = tmp_dir();
+        let key = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff".to_string();
+        let files = ArtifactFiles::new(dir.clone(), Some(key)).unwrap();
+        let payload = "eval(atob('YWxlcnQoMSk='))".repeat(100);
+        let blob = files.put(payload.as_bytes()).unwrap();
+        assert_eq!(blob.sha256, sha256::digest(payload.as_bytes()));
+        assert_eq!(blob.encryption, ARTIFACT_ENCRYPTION);
+        assert!(blob.stored_size < blob.size);
+        // the same content is stored once
+        assert_eq!(files.put(payload.as_bytes()).unwrap(), blob);
+        assert_eq!(files.get(&blob.sha256).unwrap(), payload.as_bytes());
+        let raw = fs::read(files.path_of(&blob.sha256)).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("eval"));
+
+        // encrypted artifacts cannot be read without the key
+        let without_key = ArtifactFiles::new(dir.clone(), None).unwrap();
+        assert!(without_key.get(&blob.sha256).is_err());
+        files.delete(&blob.sha256).unwrap();
+        assert!(files.get(&blob.sha256).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_password_protected_zip() {
+        let zip = password_protected_zip("sample.js", b"alert(1)", "infected").unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(zip)).unwrap();
+        assert!(archive.by_index_decrypt(0, b"wrong").is_err());
+        let mut content = String::new();
+        archive.by_index_decrypt(0, b"infected").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "alert(1)");
+        assert!(ArtifactFiles::new(tmp_dir(), Some("short".to_string())).is_err());
+    }
+}
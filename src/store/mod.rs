@@ -1,8 +1,11 @@
 pub mod sqlite;
 pub mod models;
+pub mod artifacts;
 use async_trait::async_trait;
 use log::info;
-use models::FileAnalysisReport;
+use models::{AnalysisEvents, Artifact, BaselineRun, ConsoleLogEntry, FileAnalysisReport, RejectedEvent, ReportArtifact};
+
+use crate::analysis::hash_intel::KnownHash;
 use sqlx::{migrate::MigrateDatabase, Sqlite};
 
 use crate::utils;
@@ -28,6 +31,101 @@ impl Clone for Box<dyn FileAnalysisReportStoreTrait> {
     }
 }
 
+pub trait ConsoleLogStoreTraitClone {
+    fn clone_box(&self) -> Box<dyn ConsoleLogStoreTrait>;
+}
+
+impl<T> ConsoleLogStoreTraitClone for T
+where
+    T: 'static + ConsoleLogStoreTrait + Clone,
+{
+    fn clone_box(&self) -> Box<dyn ConsoleLogStoreTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn ConsoleLogStoreTrait> {
+    fn clone(&self) -> Box<dyn ConsoleLogStoreTrait> {
+        self.clone_box()
+    }
+}
+
+pub trait AnalysisEventStoreTraitClone {
+    fn clone_box(&self) -> Box<dyn AnalysisEventStoreTrait>;
+}
+
+impl<T> AnalysisEventStoreTraitClone for T
+where
+    T: 'static + AnalysisEventStoreTrait + Clone,
+{
+    fn clone_box(&self) -> Box<dyn AnalysisEventStoreTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn AnalysisEventStoreTrait> {
+    fn clone(&self) -> Box<dyn AnalysisEventStoreTrait> {
+        self.clone_box()
+    }
+}
+
+pub trait HashIntelStoreTraitClone {
+    fn clone_box(&self) -> Box<dyn HashIntelStoreTrait>;
+}
+
+impl<T> HashIntelStoreTraitClone for T
+where
+    T: 'static + HashIntelStoreTrait + Clone,
+{
+    fn clone_box(&self) -> Box<dyn HashIntelStoreTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn HashIntelStoreTrait> {
+    fn clone(&self) -> Box<dyn HashIntelStoreTrait> {
+        self.clone_box()
+    }
+}
+
+pub trait ArtifactStoreTraitClone {
+    fn clone_box(&self) -> Box<dyn ArtifactStoreTrait>;
+}
+
+impl<T> ArtifactStoreTraitClone for T
+where
+    T: 'static + ArtifactStoreTrait + Clone,
+{
+    fn clone_box(&self) -> Box<dyn ArtifactStoreTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn ArtifactStoreTrait> {
+    fn clone(&self) -> Box<dyn ArtifactStoreTrait> {
+        self.clone_box()
+    }
+}
+
+pub trait BaselineStoreTraitClone {
+    fn clone_box(&self) -> Box<dyn BaselineStoreTrait>;
+}
+
+impl<T> BaselineStoreTraitClone for T
+where
+    T: 'static + BaselineStoreTrait + Clone,
+{
+    fn clone_box(&self) -> Box<dyn BaselineStoreTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn BaselineStoreTrait> {
+    fn clone(&self) -> Box<dyn BaselineStoreTrait> {
+        self.clone_box()
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum StoreError {
     #[error("record not found")]
@@ -43,13 +141,71 @@ pub trait FileAnalysisReportStoreTrait: Send + Sync + FileAnalysisReportStoreTra
     async fn update_file_report(&self, uid: &str, updated_file_analysis_report: FileAnalysisReport) -> StoreResult<FileAnalysisReport>;
     async fn get_file_reports_by_file_hash(&self, hash: &str) -> StoreResult<Vec<FileAnalysisReport>>;
     async fn get_file_report(&self, uid: &str) -> StoreResult<FileAnalysisReport>;
+    // reports of the second stage payloads fetched during the analysis of the parent
+    async fn get_child_file_reports(&self, parent_uid: &str) -> StoreResult<Vec<FileAnalysisReport>>;
     async fn delete_file_reports_by_hash(&self, hash: &str) -> StoreResult<u64>;
     async fn delete_file_report(&self, uid: &str) -> StoreResult<u64>;
 }
 
+#[async_trait]
+pub trait ConsoleLogStoreTrait: Send + Sync + ConsoleLogStoreTraitClone {
+    async fn index_console_logs(&self, entries: Vec<ConsoleLogEntry>) -> StoreResult<u64>;
+    async fn search_console_logs(&self, query: &str, file_hash: Option<&str>, limit: i64) -> StoreResult<Vec<ConsoleLogEntry>>;
+    async fn delete_console_logs_by_file_hash(&self, file_hash: &str) -> StoreResult<u64>;
+    async fn delete_console_logs_by_file_report(&self, file_hash: &str, file_report_uid: &str) -> StoreResult<u64>;
+}
+
+#[async_trait]
+pub trait AnalysisEventStoreTrait: Send + Sync + AnalysisEventStoreTraitClone {
+    async fn store_analysis_events(&self, events: AnalysisEvents) -> StoreResult<u64>;
+    // every event received for the analysis, in the order the batches arrived
+    async fn get_analysis_events(&self, analysis_id: &str) -> StoreResult<AnalysisEvents>;
+    // hashes of the files with stored events, i.e. the ones that can be re-analysed
+    async fn get_analysed_file_hashes(&self) -> StoreResult<Vec<String>>;
+    async fn delete_analysis_events_by_file_hash(&self, file_hash: &str) -> StoreResult<u64>;
+    async fn delete_analysis_events_by_analysis_id(&self, analysis_id: &str) -> StoreResult<u64>;
+    async fn store_rejected_events(&self, events: Vec<RejectedEvent>) -> StoreResult<u64>;
+    async fn get_rejected_events(&self, analysis_id: &str) -> StoreResult<Vec<RejectedEvent>>;
+}
+
+#[async_trait]
+pub trait HashIntelStoreTrait: Send + Sync + HashIntelStoreTraitClone {
+    // hashes already known are replaced, the latest export wins
+    async fn import_known_hashes(&self, hashes: Vec<KnownHash>) -> StoreResult<u64>;
+    // the first known hash among the given ones
+    async fn find_known_hash(&self, hashes: &[String]) -> StoreResult<KnownHash>;
+}
+
+#[async_trait]
+pub trait ArtifactStoreTrait: Send + Sync + ArtifactStoreTraitClone {
+    // adds the artifact if it is new and links it to the report,
+    // a new link increases its reference count
+    async fn link_artifact(&self, artifact: Artifact, file_report_uid: &str, relation: &str, name: &str) -> StoreResult<Artifact>;
+    async fn get_artifact(&self, sha256: &str) -> StoreResult<Artifact>;
+    async fn get_report_artifacts(&self, file_report_uid: &str) -> StoreResult<Vec<ReportArtifact>>;
+    // removes the links of the report and returns the artifacts left
+    // without references, which are deleted too
+    async fn unlink_file_report(&self, file_report_uid: &str) -> StoreResult<Vec<String>>;
+}
+
+#[async_trait]
+pub trait BaselineStoreTrait: Send + Sync + BaselineStoreTraitClone {
+    async fn create_baseline_run(&self, run: BaselineRun) -> StoreResult<u64>;
+    // marks the run completed when its events arrive, NotFoundError when the
+    // analysis is not a control run
+    async fn complete_baseline_run(&self, analysis_id: &str, completed_at: i64) -> StoreResult<BaselineRun>;
+    // the latest run requested for the bait website, or the latest completed one
+    async fn get_latest_baseline_run(&self, bait_website: &str, completed: bool) -> StoreResult<BaselineRun>;
+}
+
 #[derive(Clone)]
 pub struct DB {
-    pub file_analysis_report: Box<dyn FileAnalysisReportStoreTrait>
+    pub file_analysis_report: Box<dyn FileAnalysisReportStoreTrait>,
+    pub console_log: Box<dyn ConsoleLogStoreTrait>,
+    pub analysis_event: Box<dyn AnalysisEventStoreTrait>,
+    pub hash_intel: Box<dyn HashIntelStoreTrait>,
+    pub artifact: Box<dyn ArtifactStoreTrait>,
+    pub baseline: Box<dyn BaselineStoreTrait>
 }
 
 #[allow(dead_code)]
@@ -78,7 +234,12 @@ impl Store {
                         Store {
                             driver: "sqlite".to_string(),
                             db: DB {
-                                file_analysis_report: Box::new(sqlite::FileAnalysisReportStore::new(pool))
+                                file_analysis_report: Box::new(sqlite::FileAnalysisReportStore::new(pool.clone())),
+                                console_log: Box::new(sqlite::ConsoleLogStore::new(pool.clone())),
+                                analysis_event: Box::new(sqlite::AnalysisEventStore::new(pool.clone())),
+                                hash_intel: Box::new(sqlite::HashIntelStore::new(pool.clone())),
+                                artifact: Box::new(sqlite::ArtifactStore::new(pool.clone())),
+                                baseline: Box::new(sqlite::BaselineStore::new(pool))
                             }
                         }
                     },
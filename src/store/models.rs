@@ -1,6 +1,6 @@
 use log::debug;
 use serde::{Deserialize, Serialize};
-use crate::{analysis::analyzer::Finding, store::sqlite::FileAnalysisReportRaw};
+use crate::{analysis::{aggregation::AggregatedFinding, analyzer::Finding, canary::CanaryPlan, dast_ioc_types::IoC, scoring::RiskAssessment}, store::sqlite::FileAnalysisReportRaw};
 
 #[derive(Clone, Debug)]
 #[derive(Deserialize, Serialize)]
@@ -16,7 +16,24 @@ pub struct FileAnalysisReport {
     pub static_analysis: bool,
     pub severity: i64,
     pub bait_websites: Vec<String>,
+    // every finding raised, the individual evidence of `aggregated_findings`
     pub findings: Vec<Finding>,
+    // computed from `findings` when the report is returned by the api, not stored
+    #[serde(default)]
+    pub aggregated_findings: Vec<AggregatedFinding>,
+    #[serde(default)]
+    pub canary_plan: CanaryPlan,
+    #[serde(default)]
+    pub assessment: RiskAssessment,
+    // report of the analysis that fetched this payload, none for submitted files
+    #[serde(default)]
+    pub parent_uid: Option<String>,
+    // where the payload was fetched from
+    #[serde(default)]
+    pub source_url: String,
+    // 0 for submitted files, 1 for the payloads they fetched and so on
+    #[serde(default)]
+    pub depth: i64,
 }
 
 impl FileAnalysisReport {
@@ -41,6 +58,12 @@ impl FileAnalysisReport {
             severity,
             findings,
             bait_websites,
+            canary_plan: CanaryPlan::default(),
+            assessment: RiskAssessment::default(),
+            aggregated_findings: Vec::new(),
+            parent_uid: None,
+            source_url: "".to_string(),
+            depth: 0,
             uid: None
           }  
     }
@@ -58,6 +81,12 @@ impl FileAnalysisReport {
         severity: 0,
         findings: Vec::new(),
         bait_websites: Vec::new(),
+        canary_plan: CanaryPlan::default(),
+        assessment: RiskAssessment::default(),
+        aggregated_findings: Vec::new(),
+        parent_uid: None,
+        source_url: "".to_string(),
+        depth: 0,
         uid: None
       }  
     }
@@ -75,7 +104,13 @@ impl FileAnalysisReport {
           static_analysis: self.static_analysis.clone(),
           severity: self.severity.clone(),
           bait_websites: self.bait_websites.clone(),
-          findings: self.findings.clone() 
+          findings: self.findings.clone(),
+          canary_plan: self.canary_plan.clone(),
+          assessment: self.assessment.clone(),
+          aggregated_findings: self.aggregated_findings.clone(),
+          parent_uid: self.parent_uid.clone(),
+          source_url: self.source_url.clone(),
+          depth: self.depth
       }
     }
 }
@@ -90,6 +125,10 @@ impl From<FileAnalysisReportRaw> for FileAnalysisReport {
               Vec::new()
             }
         };
+        // reports created before the canary plan existed have an empty column
+        let canary_plan_from_raw: CanaryPlan = serde_json::from_str(raw.canary_plan.as_str()).unwrap_or_default();
+        // same for reports not scored yet
+        let assessment_from_raw: RiskAssessment = serde_json::from_str(raw.assessment.as_str()).unwrap_or_default();
         FileAnalysisReport {
             uid: raw.uid,
             name: raw.name,
@@ -103,6 +142,85 @@ impl From<FileAnalysisReportRaw> for FileAnalysisReport {
             severity: raw.severity,
             bait_websites: bait_websites_from_raw,
             findings: findings_from_raw,
+            canary_plan: canary_plan_from_raw,
+            assessment: assessment_from_raw,
+            aggregated_findings: Vec::new(),
+            parent_uid: raw.parent_uid,
+            source_url: raw.source_url,
+            depth: raw.depth,
         }
     }
-}
\ No newline at end of file
+}
+// one line of console output of an analysed sample, stored in the full text index
+#[derive(Clone, Debug)]
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+pub struct ConsoleLogEntry {
+    pub file_hash: String,
+    pub analysis_id: String,
+    pub executed_on: String,
+    pub timestamp: i64,
+    pub text: String,
+}
+
+// raw events reported by the sandbox during an analysis
+#[derive(Clone, Debug)]
+#[derive(Deserialize, Serialize)]
+pub struct AnalysisEvents {
+    pub analysis_id: String,
+    pub file_hash: String,
+    pub iocs: Vec<IoC>,
+}
+
+// an event the sandbox sent that did not validate, with the reason
+#[derive(Clone, Debug)]
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+pub struct RejectedEvent {
+    pub analysis_id: String,
+    pub file_hash: String,
+    pub received_at: i64,
+    pub schema_version: i64,
+    // position of the event in its batch
+    pub event_index: i64,
+    pub ioc_type: String,
+    pub error: String,
+    pub event: String,
+}
+
+// control run of a bait website without a sample, pending until its events arrive
+#[derive(Clone, Debug)]
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+pub struct BaselineRun {
+    pub analysis_id: String,
+    pub bait_website: String,
+    pub requested_at: i64,
+    pub completed_at: Option<i64>,
+}
+
+// a sample or downloaded payload, its content is in the artifact files
+#[derive(Clone, Debug)]
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+pub struct Artifact {
+    pub sha256: String,
+    pub size: i64,
+    pub stored_size: i64,
+    pub compression: String,
+    pub encryption: String,
+    // number of report links
+    pub ref_count: i64,
+    pub created_at: i64,
+}
+
+// an artifact as linked to a report
+#[derive(Clone, Debug)]
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+pub struct ReportArtifact {
+    pub file_report_uid: String,
+    pub sha256: String,
+    // sample or download
+    pub relation: String,
+    // file name of the sample or url of the download
+    pub name: String,
+    pub size: i64,
+    pub ref_count: i64,
+    pub created_at: i64,
+}
@@ -1,7 +1,7 @@
 
-use crate::{analysis::analyzer::Finding, store::StoreResult, store::StoreError};
+use crate::{analysis::{analyzer::Finding, canary::CanaryPlan, dast_ioc_types::IoC, hash_intel::KnownHash, scoring::RiskAssessment}, store::StoreResult, store::StoreError};
 
-use super::{models::FileAnalysisReport, FileAnalysisReportStoreTrait};
+use super::{models::{AnalysisEvents, Artifact, BaselineRun, ConsoleLogEntry, FileAnalysisReport, RejectedEvent, ReportArtifact}, AnalysisEventStoreTrait, ArtifactStoreTrait, BaselineStoreTrait, ConsoleLogStoreTrait, FileAnalysisReportStoreTrait, HashIntelStoreTrait};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Sqlite};
@@ -34,6 +34,11 @@ pub struct FileAnalysisReportRaw {
     pub severity: i64,
     pub bait_websites: String,
     pub findings: String,
+    pub canary_plan: String,
+    pub assessment: String,
+    pub parent_uid: Option<String>,
+    pub source_url: String,
+    pub depth: i64,
 }
 
 // impl From<FileAnalysisReport> for FileAnalysisReportRaw {
@@ -69,7 +74,12 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
                 static_analysis,
                 severity,
                 bait_websites,
-                findings
+                findings,
+                canary_plan,
+                assessment,
+                parent_uid,
+                source_url,
+                depth
                 FROM file_analysis_reports WHERE uid = ?"#, uid)
             .fetch_one(&self.pool)
             .await;
@@ -103,7 +113,12 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
                 static_analysis,
                 severity,
                 bait_websites,
-                findings
+                findings,
+                canary_plan,
+                assessment,
+                parent_uid,
+                source_url,
+                depth
                 FROM file_analysis_reports WHERE file_hash = ?"#, hash)
             .fetch_all(&self.pool)
             .await;
@@ -130,6 +145,34 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
         
     }
 
+    async fn get_child_file_reports(&self, parent_uid: &str) -> StoreResult<Vec<FileAnalysisReport>> {
+        let reports_raw = sqlx::query_as!(
+            FileAnalysisReportRaw, r#"SELECT uid,
+                name,
+                file_hash,
+                file_name,
+                file_extension,
+                last_analysis_id,
+                has_started_analysis,
+                dynamic_analysis,
+                static_analysis,
+                severity,
+                bait_websites,
+                findings,
+                canary_plan,
+                assessment,
+                parent_uid,
+                source_url,
+                depth
+                FROM file_analysis_reports WHERE parent_uid = ?"#, parent_uid)
+            .fetch_all(&self.pool)
+            .await;
+        return match reports_raw {
+            Ok(rws) => Ok(rws.into_iter().map(FileAnalysisReport::from).collect()),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
     async fn update_file_report(&self, uid: &str, updated_file_analysis_report: FileAnalysisReport) -> StoreResult<FileAnalysisReport> {
         let json_string_findings = match serde_json::to_string::<Vec<Finding>>(&updated_file_analysis_report.findings) {
             Ok(r) => r,
@@ -138,12 +181,20 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
                 String::new()
             }
         };
+        let json_string_assessment = match serde_json::to_string::<RiskAssessment>(&updated_file_analysis_report.assessment) {
+            Ok(r) => r,
+            Err(_) => {
+                debug!("ERROR: could convert assessment to json string");
+                String::new()
+            }
+        };
         let result = sqlx::query!(r#"UPDATE file_analysis_reports
-                    SET has_started_analysis = ?, severity = ?, findings = ?, last_analysis_id = ? WHERE uid = ? 
+                    SET has_started_analysis = ?, severity = ?, findings = ?, assessment = ?, last_analysis_id = ? WHERE uid = ? 
                 "#,
                 updated_file_analysis_report.has_started_analysis,
                 updated_file_analysis_report.severity,
                 json_string_findings,
+                json_string_assessment,
                 updated_file_analysis_report.last_analysis_id,
                 uid
             )
@@ -218,6 +269,21 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
                 String::new()
             }
         };
+        let json_string_canary_plan = match serde_json::to_string::<CanaryPlan>(&report.canary_plan) {
+            Ok(r) => r,
+            Err(_) => {
+                debug!("ERROR: could convert canary plan to json string");
+                String::new()
+            }
+        };
+
+        let json_string_assessment = match serde_json::to_string::<RiskAssessment>(&report.assessment) {
+            Ok(r) => r,
+            Err(_) => {
+                debug!("ERROR: could convert assessment to json string");
+                String::new()
+            }
+        };
 
         let res = sqlx::query!(r#"INSERT INTO file_analysis_reports
                 (
@@ -232,8 +298,13 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
                     static_analysis,
                     severity,
                     bait_websites,
-                    findings)
-                VALUES (?,?,?,?,?,?,?,?,?,?,?,?)"#,
+                    findings,
+                    canary_plan,
+                    assessment,
+                    parent_uid,
+                    source_url,
+                    depth)
+                VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)"#,
             report.uid,
             report.name,
             report.file_hash,
@@ -245,7 +316,12 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
             report.static_analysis,
             report.severity,
             comma_sep_bait_websites,
-            json_string_findings
+            json_string_findings,
+            json_string_canary_plan,
+            json_string_assessment,
+            report.parent_uid,
+            report.source_url,
+            report.depth
         ).execute(&self.pool).await;
         match res {
             Ok(_) => {
@@ -257,3 +333,505 @@ impl FileAnalysisReportStoreTrait for FileAnalysisReportStore {
         }
     }
 }
+
+#[derive(Clone)]
+pub struct ConsoleLogStore {
+    pool: Pool<Sqlite>,
+}
+
+impl ConsoleLogStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+// user input is searched as a phrase, otherwise fts5 query syntax
+// characters (quotes, colons, parentheses) would make the query fail
+fn fts5_phrase(query: &str) -> String {
+    format!("\"{}\"", query.replace('"', "\"\""))
+}
+
+// console_logs is an fts5 virtual table whose column types cannot be inferred
+// at compile time, so the queries below are checked at runtime
+#[async_trait]
+impl ConsoleLogStoreTrait for ConsoleLogStore {
+    async fn index_console_logs(&self, entries: Vec<ConsoleLogEntry>) -> StoreResult<u64> {
+        let mut indexed: u64 = 0;
+        for entry in entries {
+            let res = sqlx::query(r#"INSERT INTO console_logs
+                    (text, file_hash, analysis_id, executed_on, timestamp)
+                VALUES (?,?,?,?,?)"#)
+                .bind(&entry.text)
+                .bind(&entry.file_hash)
+                .bind(&entry.analysis_id)
+                .bind(&entry.executed_on)
+                .bind(entry.timestamp)
+                .execute(&self.pool)
+                .await;
+            match res {
+                Ok(r) => indexed += r.rows_affected(),
+                Err(e) => return Err(StoreError::GenericError(e.to_string()))
+            }
+        }
+        return Ok(indexed);
+    }
+
+    async fn search_console_logs(&self, query: &str, file_hash: Option<&str>, limit: i64) -> StoreResult<Vec<ConsoleLogEntry>> {
+        let res = sqlx::query_as::<_, ConsoleLogEntry>(r#"SELECT
+                file_hash,
+                analysis_id,
+                executed_on,
+                CAST(timestamp AS INTEGER) AS timestamp,
+                text
+                FROM console_logs
+                WHERE console_logs MATCH ? AND (? IS NULL OR file_hash = ?)
+                ORDER BY rank
+                LIMIT ?"#)
+            .bind(fts5_phrase(query))
+            .bind(file_hash)
+            .bind(file_hash)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn delete_console_logs_by_file_hash(&self, file_hash: &str) -> StoreResult<u64> {
+        let res = sqlx::query(r#"DELETE FROM console_logs WHERE file_hash = ?"#)
+            .bind(file_hash)
+            .execute(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r.rows_affected()),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    // console output of every analysis of the sample, except the last analyses
+    // of the other reports of the same sample
+    async fn delete_console_logs_by_file_report(&self, file_hash: &str, file_report_uid: &str) -> StoreResult<u64> {
+        let res = sqlx::query(r#"DELETE FROM console_logs
+                WHERE file_hash = ?
+                AND analysis_id NOT IN (
+                    SELECT last_analysis_id FROM file_analysis_reports WHERE file_hash = ? AND uid != ?)"#)
+            .bind(file_hash)
+            .bind(file_hash)
+            .bind(file_report_uid)
+            .execute(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r.rows_affected()),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+}
+
+// sandbox events are very repetitive json, zstd shrinks them ~10x
+const ANALYSIS_EVENTS_COMPRESSION: &str = "zstd";
+const ANALYSIS_EVENTS_COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Clone)]
+pub struct AnalysisEventStore {
+    pool: Pool<Sqlite>,
+}
+
+impl AnalysisEventStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AnalysisEventStoreTrait for AnalysisEventStore {
+    async fn store_analysis_events(&self, events: AnalysisEvents) -> StoreResult<u64> {
+        let iocs = match serde_json::to_vec::<Vec<IoC>>(&events.iocs) {
+            Ok(r) => r,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        let iocs = match zstd::encode_all(iocs.as_slice(), ANALYSIS_EVENTS_COMPRESSION_LEVEL) {
+            Ok(r) => r,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        let received_at = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+            Ok(d) => d.as_millis() as i64,
+            Err(_) => 0
+        };
+        let res = sqlx::query!(
+            r#"INSERT INTO analysis_events (analysis_id, file_hash, received_at, iocs, compression) VALUES (?,?,?,?,?)"#,
+            events.analysis_id,
+            events.file_hash,
+            received_at,
+            iocs,
+            ANALYSIS_EVENTS_COMPRESSION
+        ).execute(&self.pool).await;
+        return match res {
+            Ok(r) => Ok(r.rows_affected()),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn get_analysis_events(&self, analysis_id: &str) -> StoreResult<AnalysisEvents> {
+        let rows = match sqlx::query!(
+            r#"SELECT file_hash, iocs, compression FROM analysis_events WHERE analysis_id = ? ORDER BY id"#,
+            analysis_id
+        ).fetch_all(&self.pool).await {
+            Ok(r) => r,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        if rows.is_empty() {
+            return Err(StoreError::NotFoundError);
+        }
+        let mut events = AnalysisEvents {
+            analysis_id: analysis_id.to_string(),
+            file_hash: rows[0].file_hash.clone(),
+            iocs: Vec::new()
+        };
+        for row in rows {
+            let iocs = match row.compression.as_str() {
+                ANALYSIS_EVENTS_COMPRESSION => match zstd::decode_all(row.iocs.as_slice()) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        debug!("ERROR: could not decompress stored events of {}: {:?}", analysis_id, e);
+                        continue;
+                    }
+                },
+                _ => row.iocs
+            };
+            match serde_json::from_slice::<Vec<IoC>>(&iocs) {
+                Ok(mut r) => events.iocs.append(&mut r),
+                Err(e) => debug!("ERROR: could not parse stored events of {}: {:?}", analysis_id, e)
+            }
+        }
+        return Ok(events);
+    }
+
+    async fn get_analysed_file_hashes(&self) -> StoreResult<Vec<String>> {
+        let res = sqlx::query_scalar!(r#"SELECT DISTINCT file_hash FROM analysis_events WHERE file_hash != '' ORDER BY file_hash"#)
+            .fetch_all(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn delete_analysis_events_by_file_hash(&self, file_hash: &str) -> StoreResult<u64> {
+        if let Err(e) = sqlx::query!(r#"DELETE FROM rejected_events WHERE file_hash = ?"#, file_hash)
+            .execute(&self.pool)
+            .await {
+                return Err(StoreError::GenericError(e.to_string()));
+            }
+        let res = sqlx::query!(r#"DELETE FROM analysis_events WHERE file_hash = ?"#, file_hash)
+            .execute(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r.rows_affected()),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn delete_analysis_events_by_analysis_id(&self, analysis_id: &str) -> StoreResult<u64> {
+        if let Err(e) = sqlx::query!(r#"DELETE FROM rejected_events WHERE analysis_id = ?"#, analysis_id)
+            .execute(&self.pool)
+            .await {
+                return Err(StoreError::GenericError(e.to_string()));
+            }
+        let res = sqlx::query!(r#"DELETE FROM analysis_events WHERE analysis_id = ?"#, analysis_id)
+            .execute(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r.rows_affected()),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn store_rejected_events(&self, events: Vec<RejectedEvent>) -> StoreResult<u64> {
+        let mut tx = match self.pool.begin().await {
+            Ok(t) => t,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        let mut stored: u64 = 0;
+        for event in events {
+            let res = sqlx::query!(r#"INSERT INTO rejected_events
+                    (analysis_id, file_hash, received_at, schema_version, event_index, ioc_type, error, event)
+                VALUES (?,?,?,?,?,?,?,?)"#,
+                event.analysis_id,
+                event.file_hash,
+                event.received_at,
+                event.schema_version,
+                event.event_index,
+                event.ioc_type,
+                event.error,
+                event.event
+            ).execute(&mut *tx).await;
+            match res {
+                Ok(r) => stored += r.rows_affected(),
+                Err(e) => return Err(StoreError::GenericError(e.to_string()))
+            }
+        }
+        return match tx.commit().await {
+            Ok(_) => Ok(stored),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn get_rejected_events(&self, analysis_id: &str) -> StoreResult<Vec<RejectedEvent>> {
+        let res = sqlx::query_as!(RejectedEvent, r#"SELECT analysis_id, file_hash, received_at, schema_version,
+                event_index, ioc_type, error, event
+            FROM rejected_events WHERE analysis_id = ? ORDER BY id"#, analysis_id)
+            .fetch_all(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct HashIntelStore {
+    pool: Pool<Sqlite>,
+}
+
+impl HashIntelStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl HashIntelStoreTrait for HashIntelStore {
+    async fn import_known_hashes(&self, hashes: Vec<KnownHash>) -> StoreResult<u64> {
+        // exports have millions of rows, a single transaction keeps the import fast
+        let mut tx = match self.pool.begin().await {
+            Ok(t) => t,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        let mut imported: u64 = 0;
+        for known_hash in hashes {
+            let res = sqlx::query!(r#"INSERT OR REPLACE INTO known_hashes
+                    (hash, hash_type, family, source, first_seen)
+                VALUES (?,?,?,?,?)"#,
+                known_hash.hash,
+                known_hash.hash_type,
+                known_hash.family,
+                known_hash.source,
+                known_hash.first_seen
+            ).execute(&mut *tx).await;
+            match res {
+                Ok(r) => imported += r.rows_affected(),
+                Err(e) => return Err(StoreError::GenericError(e.to_string()))
+            }
+        }
+        return match tx.commit().await {
+            Ok(_) => Ok(imported),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn find_known_hash(&self, hashes: &[String]) -> StoreResult<KnownHash> {
+        for hash in hashes {
+            let hash = hash.to_lowercase();
+            let res = sqlx::query_as!(KnownHash, r#"SELECT hash, hash_type, family, source, first_seen
+                FROM known_hashes WHERE hash = ?"#, hash)
+                .fetch_optional(&self.pool)
+                .await;
+            match res {
+                Ok(Some(r)) => return Ok(r),
+                Ok(None) => continue,
+                Err(e) => return Err(StoreError::GenericError(e.to_string()))
+            }
+        }
+        return Err(StoreError::NotFoundError);
+    }
+}
+
+#[derive(Clone)]
+pub struct ArtifactStore {
+    pool: Pool<Sqlite>,
+}
+
+impl ArtifactStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ArtifactStoreTrait for ArtifactStore {
+    async fn link_artifact(&self, artifact: Artifact, file_report_uid: &str, relation: &str, name: &str) -> StoreResult<Artifact> {
+        let mut tx = match self.pool.begin().await {
+            Ok(t) => t,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        let res = sqlx::query!(r#"INSERT OR IGNORE INTO artifacts
+                (sha256, size, stored_size, compression, encryption, ref_count, created_at)
+            VALUES (?,?,?,?,?,0,?)"#,
+            artifact.sha256,
+            artifact.size,
+            artifact.stored_size,
+            artifact.compression,
+            artifact.encryption,
+            artifact.created_at
+        ).execute(&mut *tx).await;
+        if let Err(e) = res {
+            return Err(StoreError::GenericError(e.to_string()));
+        }
+        let linked = match sqlx::query!(r#"INSERT OR IGNORE INTO file_report_artifacts
+                (file_report_uid, artifact_sha256, relation, name)
+            VALUES (?,?,?,?)"#,
+            file_report_uid,
+            artifact.sha256,
+            relation,
+            name
+        ).execute(&mut *tx).await {
+            Ok(r) => r.rows_affected(),
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        if linked > 0 {
+            let res = sqlx::query!(r#"UPDATE artifacts SET ref_count = ref_count + 1 WHERE sha256 = ?"#, artifact.sha256)
+                .execute(&mut *tx)
+                .await;
+            if let Err(e) = res {
+                return Err(StoreError::GenericError(e.to_string()));
+            }
+        }
+        let res = sqlx::query_as!(Artifact, r#"SELECT sha256, size, stored_size, compression, encryption, ref_count, created_at
+            FROM artifacts WHERE sha256 = ?"#, artifact.sha256)
+            .fetch_one(&mut *tx)
+            .await;
+        let stored = match res {
+            Ok(r) => r,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        return match tx.commit().await {
+            Ok(_) => Ok(stored),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn get_artifact(&self, sha256: &str) -> StoreResult<Artifact> {
+        let res = sqlx::query_as!(Artifact, r#"SELECT sha256, size, stored_size, compression, encryption, ref_count, created_at
+            FROM artifacts WHERE sha256 = ?"#, sha256)
+            .fetch_optional(&self.pool)
+            .await;
+        return match res {
+            Ok(Some(r)) => Ok(r),
+            Ok(None) => Err(StoreError::NotFoundError),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn get_report_artifacts(&self, file_report_uid: &str) -> StoreResult<Vec<ReportArtifact>> {
+        let res = sqlx::query_as!(ReportArtifact, r#"SELECT l.file_report_uid, a.sha256, l.relation, l.name,
+                a.size, a.ref_count, a.created_at
+            FROM file_report_artifacts l JOIN artifacts a ON a.sha256 = l.artifact_sha256
+            WHERE l.file_report_uid = ?
+            ORDER BY l.relation DESC, a.created_at"#, file_report_uid)
+            .fetch_all(&self.pool)
+            .await;
+        return match res {
+            Ok(r) => Ok(r),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn unlink_file_report(&self, file_report_uid: &str) -> StoreResult<Vec<String>> {
+        let mut tx = match self.pool.begin().await {
+            Ok(t) => t,
+            Err(e) => return Err(StoreError::GenericError(e.to_string()))
+        };
+        let linked = match sqlx::query_scalar!(r#"SELECT artifact_sha256 FROM file_report_artifacts WHERE file_report_uid = ?"#, file_report_uid)
+            .fetch_all(&mut *tx)
+            .await {
+                Ok(r) => r,
+                Err(e) => return Err(StoreError::GenericError(e.to_string()))
+            };
+        let res = sqlx::query!(r#"DELETE FROM file_report_artifacts WHERE file_report_uid = ?"#, file_report_uid)
+            .execute(&mut *tx)
+            .await;
+        if let Err(e) = res {
+            return Err(StoreError::GenericError(e.to_string()));
+        }
+        // an artifact linked twice to the report (sample and download) loses both references
+        let mut unreferenced: Vec<String> = Vec::new();
+        for sha256 in linked {
+            let res = sqlx::query_scalar!(r#"UPDATE artifacts SET ref_count = ref_count - 1 WHERE sha256 = ? RETURNING ref_count"#, sha256)
+                .fetch_optional(&mut *tx)
+                .await;
+            match res {
+                Ok(Some(_c)) if _c <= 0 && !unreferenced.contains(&sha256) => unreferenced.push(sha256),
+                Ok(_) => (),
+                Err(e) => return Err(StoreError::GenericError(e.to_string()))
+            }
+        }
+        for sha256 in unreferenced.iter() {
+            let res = sqlx::query!(r#"DELETE FROM artifacts WHERE sha256 = ?"#, sha256)
+                .execute(&mut *tx)
+                .await;
+            if let Err(e) = res {
+                return Err(StoreError::GenericError(e.to_string()));
+            }
+        }
+        return match tx.commit().await {
+            Ok(_) => Ok(unreferenced),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BaselineStore {
+    pool: Pool<Sqlite>,
+}
+
+impl BaselineStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl BaselineStoreTrait for BaselineStore {
+    async fn create_baseline_run(&self, run: BaselineRun) -> StoreResult<u64> {
+        let res = sqlx::query!(
+            r#"INSERT INTO baseline_runs (analysis_id, bait_website, requested_at, completed_at) VALUES (?,?,?,?)"#,
+            run.analysis_id,
+            run.bait_website,
+            run.requested_at,
+            run.completed_at
+        ).execute(&self.pool).await;
+        return match res {
+            Ok(r) => Ok(r.rows_affected()),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn complete_baseline_run(&self, analysis_id: &str, completed_at: i64) -> StoreResult<BaselineRun> {
+        let res = sqlx::query_as!(BaselineRun, r#"UPDATE baseline_runs SET completed_at = ? WHERE analysis_id = ?
+            RETURNING analysis_id, bait_website, requested_at, completed_at"#, completed_at, analysis_id)
+            .fetch_optional(&self.pool)
+            .await;
+        return match res {
+            Ok(Some(r)) => Ok(r),
+            Ok(None) => Err(StoreError::NotFoundError),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+
+    async fn get_latest_baseline_run(&self, bait_website: &str, completed: bool) -> StoreResult<BaselineRun> {
+        let res = sqlx::query_as!(BaselineRun, r#"SELECT analysis_id, bait_website, requested_at, completed_at
+            FROM baseline_runs WHERE bait_website = ? AND (? = 0 OR completed_at IS NOT NULL)
+            ORDER BY requested_at DESC LIMIT 1"#, bait_website, completed)
+            .fetch_optional(&self.pool)
+            .await;
+        return match res {
+            Ok(Some(r)) => Ok(r),
+            Ok(None) => Err(StoreError::NotFoundError),
+            Err(e) => Err(StoreError::GenericError(e.to_string()))
+        }
+    }
+}
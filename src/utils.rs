@@ -4,12 +4,27 @@ use serde::{Deserialize};
 use regex::Regex;
 use serde_yaml::Error;
 use std::env;
+use std::sync::LazyLock;
+
+static INLINE_SCRIPT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<script\b([^>]*)>(.*?)</script\s*>").unwrap()
+});
 
 pub fn contains_html_like_code(input: &str) -> bool {
     let html_regex = Regex::new(r"<\s*!?[a-zA-Z][a-zA-Z0-9]*\b[^>]*>|</\s*[a-zA-Z][a-zA-Z0-9]*\s*>").unwrap();
     html_regex.is_match(input)
 }
 
+// code of the inline <script> elements of an html document, scripts loaded with `src` are skipped
+pub fn inline_scripts(html: &str) -> Vec<String> {
+    INLINE_SCRIPT
+        .captures_iter(html)
+        .filter(|c| !c[1].to_lowercase().contains("src="))
+        .map(|c| c.get(2).map(|m| m.as_str().trim()).unwrap_or("").to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
 pub fn parse_yaml<T: for<'a> Deserialize<'a>>(p: PathBuf) -> Result<T, Error> {
     let mut f = fs::File::open(p).unwrap();
     let mut buf: String = String::new();